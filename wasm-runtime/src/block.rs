@@ -1,114 +1,407 @@
 /* Mod Block
-** - Função para calculo da Hash do bloco com base nos dados contidos no bloco
+** - Função para calculo da Hash do bloco com base nos dados contidos no bloco,
+**   com a função hash escolhida no genesis (mod hash). A hash do bloco e a do
+**   bloco anterior são do tipo `Hash` (32 bytes)
+** - O cabeçalho do bloco contém a raiz dos recibos das transações (mod receipt)
+**   e a raiz do estado depois da aplicação do bloco (mod state)
+** - Selo do bloco (mod consensus): o produtor faz parte da hash e assina o id
+**   da cadeia, a altura e a hash do bloco. Blocos sem produtor não possuem selo
+** - BlockHeader: dados do bloco sem as transações, utilizado na sincronização
+** - Versão do bloco: a versão 1 (BLOCK_VERSION) calcula a hash sobre uma
+**   codificação binária canônica, inteiros com tamanho fixo e textos prefixados
+**   pelo tamanho. A versão 0 (blocos gravados sem o campo) concatena os campos
+**   em texto e é mantida apenas para a hash dos blocos existentes: novos blocos
+**   são da versão atual e um bloco não pode ter versão menor que o anterior
 */
-use crate::transaction::Transaction;
+use crate::{
+    hash::{Hash, Hasher},
+    transaction::{Transaction, TransactionSignature},
+    wallet,
+};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-#[derive(Debug, Clone)]
+// Versão dos blocos produzidos pelo nó
+pub const BLOCK_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
+    // Versão da codificação do bloco, 0 nos blocos gravados antes da versão
+    #[serde(default)]
+    pub version: u32,
     // identificação única do bloco
     pub id: u64,
     //timestamp do bloco
     pub timestamp: u64,
     //Hash do bloco
-    pub hash: String,
+    pub hash: Hash,
     //Hash do bloco anterior
-    pub hash_previous_block: String,
+    pub hash_previous_block: Hash,
+    //Raiz de Merkle dos recibos das transações do bloco
+    #[serde(default)]
+    pub receipts_root: String,
+    //Raiz da árvore do estado depois de aplicar as transações do bloco
+    #[serde(default)]
+    pub state_root: String,
+    //Chave pública do produtor do bloco e sua assinatura da hash do bloco
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub producer: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
     //conjunto de transação incluídas no bloco
     pub transactions: HashMap<u64, Transaction>,
 }
 
+// Cabeçalho do bloco, sem o corpo (transações)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    #[serde(default)]
+    pub version: u32,
+    pub id: u64,
+    pub timestamp: u64,
+    pub hash: Hash,
+    pub hash_previous_block: Hash,
+    pub receipts_root: String,
+    #[serde(default)]
+    pub state_root: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub producer: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
 impl Block {
     pub fn new(
         id: u64,
-        hash_previous_block: &str,
+        hash_previous_block: Hash,
+        receipts_root: &str,
+        state_root: &str,
         transactions: HashMap<u64, Transaction>,
+        hasher: &dyn Hasher,
     ) -> Self {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Erro ao obter o timestamp")
-            .as_secs() as u64;
-        let hash = Block::calculate_block_hash(id, timestamp, &hash_previous_block, &transactions);
+            .as_secs();
+        let hash = Block::calculate_block_hash(
+            BLOCK_VERSION,
+            id,
+            timestamp,
+            &hash_previous_block,
+            receipts_root,
+            state_root,
+            "",
+            &transactions,
+            hasher,
+        );
 
         Block {
+            version: BLOCK_VERSION,
             id,
             timestamp,
-            hash: hash.to_string(),
-            hash_previous_block: hash_previous_block.to_string(),
+            hash,
+            hash_previous_block,
+            receipts_root: receipts_root.to_string(),
+            state_root: state_root.to_string(),
+            producer: String::new(),
+            signature: String::new(),
+            transactions,
+        }
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            version: self.version,
+            id: self.id,
+            timestamp: self.timestamp,
+            hash: self.hash,
+            hash_previous_block: self.hash_previous_block,
+            receipts_root: self.receipts_root.clone(),
+            state_root: self.state_root.clone(),
+            producer: self.producer.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+
+    //Monta o bloco a partir do cabeçalho e do corpo recebidos separadamente
+    pub fn from_parts(header: BlockHeader, transactions: HashMap<u64, Transaction>) -> Self {
+        Block {
+            version: header.version,
+            id: header.id,
+            timestamp: header.timestamp,
+            hash: header.hash,
+            hash_previous_block: header.hash_previous_block,
+            receipts_root: header.receipts_root,
+            state_root: header.state_root,
+            producer: header.producer,
+            signature: header.signature,
             transactions,
         }
     }
 
-    //Função para calculo do block hash
+    //Recalcula a hash a partir dos dados atuais do bloco, incluindo o produtor
+    pub fn calculate_hash(&self, hasher: &dyn Hasher) -> Hash {
+        Block::calculate_block_hash(
+            self.version,
+            self.id,
+            self.timestamp,
+            &self.hash_previous_block,
+            &self.receipts_root,
+            &self.state_root,
+            &self.producer,
+            &self.transactions,
+            hasher,
+        )
+    }
+
+    //Define o produtor do bloco, a hash é recalculada e a assinatura descartada
+    pub fn set_producer(&mut self, producer: &str, hasher: &dyn Hasher) {
+        self.producer = producer.to_string();
+        self.signature = String::new();
+        self.hash = self.calculate_hash(hasher);
+    }
+
+    /* Mensagem assinada pelo produtor: o id da cadeia, a altura e a hash do
+     ** bloco. Com a altura na mensagem duas assinaturas na mesma altura são
+     ** checadas apenas com os cabeçalhos (mod stake), com o id da cadeia um
+     ** cabeçalho assinado em outra rede não é aceito como evidência
+     */
+    pub fn signing_hash(&self, chain_id: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"block");
+        hasher.update((chain_id.len() as u64).to_be_bytes());
+        hasher.update(chain_id.as_bytes());
+        hasher.update(self.id.to_be_bytes());
+        hasher.update(self.hash.as_bytes());
+        hasher.finalize().into()
+    }
+
+    //Checa a assinatura do produtor, blocos sem produtor não podem ter assinatura
+    pub fn check_signature(&self, chain_id: &str) -> Result<(), String> {
+        if self.producer.is_empty() {
+            return if self.signature.is_empty() {
+                Ok(())
+            } else {
+                Err(String::from("Assinatura sem produtor"))
+            };
+        }
+        let signature = TransactionSignature {
+            public_key: self.producer.clone(),
+            signature: self.signature.clone(),
+        };
+        wallet::verify_signature(&signature, &self.signing_hash(chain_id))
+            .map_err(|_| String::from("Assinatura do produtor invalida"))
+    }
+
+    //Função para calculo do block hash, com a codificação da versão do bloco
+    #[allow(clippy::too_many_arguments)]
     pub fn calculate_block_hash(
+        version: u32,
         id: u64,
         timestamp: u64,
-        hash_previous_block: &str,
+        hash_previous_block: &Hash,
+        receipts_root: &str,
+        state_root: &str,
+        producer: &str,
         transactions: &HashMap<u64, Transaction>,
-    ) -> String {
-        let mut hasher = Sha256::new();
+        hasher: &dyn Hasher,
+    ) -> Hash {
+        if version == 0 {
+            return Block::legacy_block_hash(
+                id,
+                timestamp,
+                hash_previous_block,
+                receipts_root,
+                state_root,
+                producer,
+                transactions,
+                hasher,
+            );
+        }
 
+        /* Codificação canônica: inteiros big-endian de tamanho fixo, textos com o
+         ** tamanho antes do conteúdo. O conteúdo de cada transação entra pela hash
+         ** dos dados assinados, seguida das assinaturas
+         */
+        let mut data = b"block".to_vec();
+        data.extend(version.to_be_bytes());
+        data.extend(id.to_be_bytes());
+        data.extend(timestamp.to_be_bytes());
+        data.extend(hash_previous_block.as_bytes());
+        put_str(&mut data, receipts_root);
+        put_str(&mut data, state_root);
+        put_str(&mut data, producer);
+
+        let mut ordered = transactions.iter().collect::<Vec<_>>();
+        ordered.sort_by_key(|(tx_id, _)| **tx_id);
+        data.extend((ordered.len() as u64).to_be_bytes());
+        for (tx_id, tx) in ordered {
+            data.extend(tx_id.to_be_bytes());
+            data.extend(tx.signing_hash());
+            let signatures = tx
+                .signature
+                .iter()
+                .chain(&tx.signatures)
+                .collect::<Vec<_>>();
+            // O marcador separa a assinatura do remetente das assinaturas multisig
+            data.push(tx.signature.is_some() as u8);
+            data.extend((signatures.len() as u64).to_be_bytes());
+            for signature in signatures {
+                put_str(&mut data, &signature.public_key);
+                put_str(&mut data, &signature.signature);
+            }
+        }
+        hasher.hash(&data)
+    }
+
+    // Hash da versão 0: campos concatenados em texto, apenas para os blocos existentes
+    #[allow(clippy::too_many_arguments)]
+    fn legacy_block_hash(
+        id: u64,
+        timestamp: u64,
+        hash_previous_block: &Hash,
+        receipts_root: &str,
+        state_root: &str,
+        producer: &str,
+        transactions: &HashMap<u64, Transaction>,
+        hasher: &dyn Hasher,
+    ) -> Hash {
         // transformar todos os dados do bloco em uma única string
-        let mut data = format!("{}{}{}", id, timestamp, hash_previous_block);
+        let mut data = format!(
+            "{}{}{}{}{}{}",
+            id, timestamp, hash_previous_block, receipts_root, state_root, producer
+        );
 
         //transformas os dados do vector transação em uma única string
+        //as transações são ordenadas pelo id, a ordem de iteração do HashMap não é fixa
+        let mut ordered = transactions.iter().collect::<Vec<_>>();
+        ordered.sort_by_key(|(tx_id, _)| **tx_id);
 
-        for (tx_id, tx) in transactions {
+        for (tx_id, tx) in ordered {
             data.push_str(&format!("{}{}{}{}", tx_id, tx.from, tx.to, tx.value));
+            data.push_str(&tx.kind.hash_data());
+            // O nonce zero não adiciona dados, mantendo a hash dos blocos antigos
+            if tx.nonce > 0 {
+                data.push_str(&format!("nonce{}", tx.nonce));
+            }
+            data.push_str(&tx.signature_hash_data());
         }
-        //Utilizando macro sha256 para criar a hash da variável data
-        hasher.update(data);
-
-        let result = format!("{:x}", hasher.finalize());
-        result
+        //Utilizando a função hash da cadeia para criar a hash da variável data
+        hasher.hash(data.as_bytes())
     }
 }
 
+// Texto prefixado pelo tamanho em bytes
+fn put_str(data: &mut Vec<u8>, value: &str) {
+    data.extend((value.len() as u64).to_be_bytes());
+    data.extend(value.as_bytes());
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transaction::Transaction;
+    use crate::{
+        hash::{Blake2bHasher, Sha256Hasher},
+        transaction::Transaction,
+    };
 
     #[test]
     fn test_calculate_block_hash() {
         let transactions = HashMap::from([
             (
                 4,
-                Transaction {
-                    from: "0xEf8801eaf234ff82801821FFe2d780237F9967".to_string(),
-                    to: "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962".to_string(),
-                    value: 4.0,
-                },
+                Transaction::new(
+                    "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                    "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                    4.0,
+                ),
             ),
             (
                 5,
-                Transaction {
-                    from: "0xEf8801eaf234ff82801821FFe2d780237F9967".to_string(),
-                    to: "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962".to_string(),
-                    value: 5.0,
-                },
+                Transaction::new(
+                    "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                    "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                    5.0,
+                ),
             ),
         ]);
 
         let test_block = Block::new(
             0,
-            "a843dbfe51f762c3ca17f62633392c3a8538d45425d7f5ad1da877822c1073ae",
+            "a843dbfe51f762c3ca17f62633392c3a8538d45425d7f5ad1da877822c1073ae"
+                .parse()
+                .unwrap(),
+            "",
+            "",
             transactions,
+            &Sha256Hasher,
         );
 
         let hash = Block::calculate_block_hash(
+            test_block.version,
             test_block.id,
             test_block.timestamp,
             &test_block.hash_previous_block,
+            &test_block.receipts_root,
+            &test_block.state_root,
+            &test_block.producer,
             &test_block.transactions,
+            &Sha256Hasher,
+        );
+
+        assert_eq!(hash, test_block.hash);
+        assert_ne!(test_block.calculate_hash(&Blake2bHasher), hash);
+    }
+
+    #[test]
+    fn test_block_hash_encoding() {
+        // Na codificação antiga os campos concatenados se confundem
+        let mut first = Block::new(1, Hash::ZERO, "ab", "c", HashMap::new(), &Sha256Hasher);
+        let mut second = first.clone();
+        second.receipts_root = String::from("a");
+        second.state_root = String::from("bc");
+        assert_ne!(
+            first.calculate_hash(&Sha256Hasher),
+            second.calculate_hash(&Sha256Hasher)
+        );
+        first.version = 0;
+        second.version = 0;
+        assert_eq!(
+            first.calculate_hash(&Sha256Hasher),
+            second.calculate_hash(&Sha256Hasher)
         );
 
-        assert!(
-            !hash.is_empty()
+        // Blocos gravados sem a versão continuam com a hash antiga
+        let mut legacy = serde_json::to_value(&first).unwrap();
+        legacy.as_object_mut().unwrap().remove("version");
+        legacy["hash"] = serde_json::to_value(first.calculate_hash(&Sha256Hasher)).unwrap();
+        let legacy = serde_json::from_value::<Block>(legacy).unwrap();
+        assert_eq!(legacy.version, 0);
+        assert_eq!(legacy.calculate_hash(&Sha256Hasher), legacy.hash);
+
+        // Assinaturas e o id das transações fazem parte da hash
+        let mut transaction = Transaction::new("0x1", "0x2", 1.0);
+        let unsigned = Block::new(
+            1,
+            Hash::ZERO,
+            "",
+            "",
+            HashMap::from([(1, transaction.clone())]),
+            &Sha256Hasher,
         );
+        transaction.signatures.push(TransactionSignature {
+            public_key: String::from("ab"),
+            signature: String::from("c"),
+        });
+        let mut signed = unsigned.clone();
+        signed.transactions.insert(1, transaction);
+        assert_ne!(signed.calculate_hash(&Sha256Hasher), unsigned.hash);
+        let mut moved = unsigned.clone();
+        moved.transactions = HashMap::from([(2, unsigned.transactions[&1].clone())]);
+        assert_ne!(moved.calculate_hash(&Sha256Hasher), unsigned.hash);
     }
 }
@@ -12,13 +12,61 @@
 **    - Checa a hash criada a partir dos dados do bloco
 ** - Função para simular a corrupção do valor de um transação em um dado
 ** bloco
+** - Transações podem publicar e chamar contratos wasm, executados durante
+**   a aplicação do bloco (mod contract)
+** - Os blocos e o estado das contas são gravados no Storage da blockchain,
+**   uma blockchain criada sobre um Storage existente continua a cadeia salva.
+**   Um erro de escrita do Storage é retornado pela produção e importação do bloco
+** - Forks: blocos de outros ramos ficam na árvore de blocos (mod fork). Quando um
+**   ramo fica mais longo que a cadeia atual acontece a reorganização: os blocos
+**   acima do ponto de bifurcação são desfeitos (registro de desfazer gravado em
+**   `undo/<id>`), os blocos do novo ramo são aplicados e as transações dos blocos
+**   órfãos que não estão no novo ramo voltam para as transações pendentes
+** - Modelo das transações escolhido no genesis: contas (padrão) ou UTXO (mod utxo),
+**   no modo UTXO novas transações pendentes são checadas contra gasto duplo
+** - Consenso escolhido no genesis (mod consensus): na prova de autoridade o nó
+**   só produz os blocos da sua vez, selados com a chave do validador, e os
+**   blocos importados precisam do selo do validador da vez. Na prova de
+**   participação (mod stake) um bloco de outro ramo assinado pelo mesmo produtor
+**   na altura de um bloco da cadeia gera uma transação de evidência
+** - Finalidade (mod finality): o validador local envia um pré-commit para cada
+**   checkpoint adicionado à cadeia (`take_precommits`). Com mais de 2/3 do peso
+**   dos validadores o checkpoint fica final: ramos que desfazem blocos finalizados
+**   são recusados e `is_chain_valid` checa só o encadeamento das hashes até ele.
+**   Pré-commits de checkpoints ainda não recebidos ficam guardados até o bloco
+**   chegar, em quantidade limitada
+** - Índices (mod index): bloco pela hash, transação para bloco e endereço para
+**   transações, gravados com as alterações de cada bloco e desfeitos com ele
+** - Eventos: assinantes (`subscribe`) recebem cada bloco adicionado a cadeia e
+**   cada transação aceita nas pendentes
 * */
 
-use crate::{block::Block, transaction::Transaction};
+use crate::{
+    block::{Block, BLOCK_VERSION},
+    consensus::{self, Consensus},
+    contract::{self, Execution, ExecutionStatus},
+    debug, error,
+    finality::{self, Checkpoint, Precommit},
+    fork::BlockTree,
+    genesis::{Genesis, LedgerMode},
+    hash::{self, Hash, Hasher},
+    index::{self, HistoryPage},
+    info,
+    multisig::{self, MultisigAccount},
+    receipt::{self, Event, Receipt},
+    stake::{self, ValidatorStake},
+    state::{self, Account, AccountProof},
+    storage::{load, save, MemoryStorage, OverlayStorage, Storage},
+    transaction::{Transaction, TransactionKind},
+    utxo::{self, OutPoint, TxOutput},
+    wallet, warn,
+};
+use ed25519_dalek::SigningKey;
 //use parity_scale_codec_derive::{Decode, Encode};
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
     time::{SystemTime, UNIX_EPOCH},
 };
 
@@ -29,28 +77,156 @@ pub struct Blockchain {
     block_size: usize,
     pub pending_transactions: HashMap<u64, Transaction>,
     transaction_counter: u64,
+    //Armazenamento dos blocos e do estado das contas
+    storage: Box<dyn Storage>,
+    //Blocos de ramos laterais, fora da cadeia canônica
+    side_blocks: BlockTree,
+    //Assinantes dos eventos da blockchain
+    subscribers: Vec<SyncSender<ChainEvent>>,
+    //Modelo das transações, definido no genesis
+    ledger: LedgerMode,
+    //Confere o saldo do remetente das transações (`Genesis::checks_balances`)
+    check_balances: bool,
+    //Regras de produção e validação dos blocos, definidas no genesis
+    consensus: Box<dyn Consensus>,
+    //Função hash dos blocos, definida no genesis
+    hasher: Box<dyn Hasher>,
+    //Identificador da rede, definido no genesis, incluído na assinatura dos blocos
+    chain_id: String,
+    //Intervalo dos checkpoints da finalidade, definido no genesis
+    checkpoint_interval: u64,
+    //Chave do validador local, assina os blocos e os pré-commits
+    validator_key: Option<SigningKey>,
+    //Pré-commits do validador local ainda não enviados aos peers
+    precommits: Vec<Precommit>,
+    //Pré-commits de checkpoints acima da cadeia, aplicados quando o bloco chega
+    future_precommits: Vec<Precommit>,
+}
+
+// Eventos enviados aos assinantes da blockchain
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    // Bloco adicionado a cadeia (minerado, importado ou aplicado na reorganização)
+    NewBlock(Block),
+    // Transação aceita nas transações pendentes
+    PendingTransaction { id: u64, transaction: Transaction },
+}
+
+// Eventos guardados para cada assinante, um assinante com a fila cheia é removido
+pub const SUBSCRIBER_CAPACITY: usize = 1024;
+
+// Chaves utilizadas no Storage
+// Pré-commits guardados para checkpoints ainda não recebidos, até
+// `FUTURE_CHECKPOINTS` checkpoints acima da cadeia
+const MAX_FUTURE_PRECOMMITS: usize = 1024;
+const FUTURE_CHECKPOINTS: u64 = 2;
+
+const HEIGHT_KEY: &str = "chain/height";
+const TRANSACTION_COUNTER_KEY: &str = "chain/transaction_counter";
+const GENESIS_KEY: &str = "chain/genesis";
+
+fn block_key(id: u64) -> String {
+    format!("block/{}", id)
+}
+
+/* Maior valor que a transação pode debitar do remetente: o valor transferido
+** mais a taxa máxima do gas das publicações e chamadas de contrato
+*/
+fn max_debit(transaction: &Transaction) -> f64 {
+    match &transaction.kind {
+        TransactionKind::Transfer
+        | TransactionKind::RegisterMultisig { .. }
+        | TransactionKind::Bond { .. } => transaction.value,
+        TransactionKind::Deploy { code, gas_price } => {
+            transaction.value + contract::deploy_gas(code) as f64 * gas_price
+        }
+        TransactionKind::Call {
+            gas_limit,
+            gas_price,
+            ..
+        } => transaction.value + *gas_limit as f64 * gas_price,
+        _ => 0.0,
+    }
+}
+
+fn undo_key(id: u64) -> String {
+    format!("undo/{}", id)
+}
+
+// Raiz do estado calculada depois da aplicação do bloco
+fn state_root_key(id: u64) -> String {
+    format!("state_root/{}", id)
 }
 
 impl Blockchain {
-    //Função de criação da blockchain
+    //Função de criação da blockchain em memória
     pub fn new() -> Self {
-        //Ao criar uma nova blockchain, o block genesis é criado
-        //Hardcode block_genesis
+        Blockchain::with_storage(Box::new(MemoryStorage::new()))
+            .expect("Erro ao criar a cadeia em memória")
+    }
+
+    /* Cria a blockchain sobre um Storage. Se o Storage já contém uma cadeia
+     ** os blocos salvos são carregados, caso contrário o bloco genesis é criado.
+     ** Storage corrompido ou incompleto retorna erro
+     * */
+    pub fn with_storage(storage: Box<dyn Storage>) -> Result<Self, String> {
+        Blockchain::with_genesis(storage, &Genesis::default())
+    }
+
+    /* Cria a blockchain com os parâmetros do genesis: os saldos iniciais são
+     ** aplicados ao estado. Se o Storage já contém uma cadeia ela é carregada.
+     ** Parâmetros de consenso inválidos no genesis retornam erro
+     * */
+    pub fn with_genesis(mut storage: Box<dyn Storage>, genesis: &Genesis) -> Result<Self, String> {
+        if let Some(height) = load::<u64>(storage.as_ref(), HEIGHT_KEY) {
+            return Blockchain::load(storage, height);
+        }
+
+        match genesis.ledger {
+            LedgerMode::Account => {
+                for (address, balance) in &genesis.alloc {
+                    state::add_balance(storage.as_mut(), address, *balance);
+                }
+            }
+            LedgerMode::Utxo => utxo::create_genesis_outputs(storage.as_mut(), &genesis.alloc),
+        }
+        consensus::init_state(storage.as_mut(), &genesis.consensus)
+            .map_err(|erro| format!("Parâmetros de consenso inválidos no genesis: {}", erro))?;
+        save(storage.as_mut(), GENESIS_KEY, genesis);
+
+        /* Ao criar uma nova blockchain, o block genesis é criado. A hash do
+         ** genesis é a hash dos parâmetros serializados: nós com parâmetros
+         ** diferentes têm genesis diferentes e não se conectam
+         * */
+        let hasher = hash::from_algorithm(genesis.hash_algorithm);
+        let genesis_hash =
+            hasher.hash(&serde_json::to_vec(genesis).expect("Erro ao serializar o genesis"));
         let genesis_block = Block {
+            version: BLOCK_VERSION,
             id: 0,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Erro ao obter o timestamp")
-                .as_secs(),
-            hash_previous_block: "0x000000000".to_string(),
-            hash: "0x000000000".to_string(),
+            timestamp: genesis.timestamp.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Erro ao obter o timestamp")
+                    .as_secs()
+            }),
+            hash_previous_block: Hash::ZERO,
+            hash: genesis_hash,
+            receipts_root: receipt::receipts_root(&[]),
+            state_root: state::state_root(storage.as_ref()),
+            producer: String::new(),
+            signature: String::new(),
             transactions: HashMap::new(),
         };
-        //variável mutável que representa o vetor da cadeia de blocos
-        let mut chain = vec![];
-
+        save(
+            storage.as_mut(),
+            &state_root_key(0),
+            &genesis_block.state_root,
+        );
+        index::index_block(storage.as_mut(), &genesis_block)?;
+        index::set_version(storage.as_mut());
         //block_genesis como primeiro elemento da cadeia de blocos da nova blockchain
-        chain.push(genesis_block.clone());
+        let chain = vec![genesis_block];
 
         //tamanho do bloco fixo, indica que cada bloco pode conter 5 transações
         let block_size = 5;
@@ -61,295 +237,3015 @@ impl Blockchain {
         //Vetor que armazena temporariamente as transações
         let pending_transactions = HashMap::new();
 
-        let blockchain = Blockchain {
+        let mut blockchain = Blockchain {
             chain,
             block_size,
             pending_transactions,
             transaction_counter,
+            storage,
+            side_blocks: BlockTree::new(),
+            subscribers: Vec::new(),
+            ledger: genesis.ledger,
+            check_balances: genesis.checks_balances(),
+            consensus: consensus::from_params(&genesis.consensus, &genesis.chain_id),
+            hasher,
+            chain_id: genesis.chain_id.clone(),
+            checkpoint_interval: genesis.checkpoint_interval,
+            validator_key: None,
+            precommits: Vec::new(),
+            future_precommits: Vec::new(),
         };
-
-        println!(
-            "Blockchain criada com sucesso! Bloco genesis:\n {:?}\n",
-            &blockchain.chain[0]
+        blockchain.save_block(0);
+        save(
+            blockchain.storage.as_mut(),
+            TRANSACTION_COUNTER_KEY,
+            &transaction_counter,
         );
-        blockchain
+
+        info!("Blockchain criada com sucesso!"; genesis = blockchain.chain[0].hash);
+        debug!("Bloco genesis: {:?}", &blockchain.chain[0]);
+        Ok(blockchain)
     }
 
-    /* Ao completar 5 no pending_transactions, um novo bloco é minerado,
-     ** sua hash é calculada e o bloco e adicionado a cadeia de blocos
+    /* Carrega os blocos e o contador de transações gravados no Storage. Os
+     ** índices gravados em outro formato são reconstruídos a partir dos blocos.
+     ** Blocos ausentes ou que não encadeiam retornam erro
      * */
+    fn load(mut storage: Box<dyn Storage>, height: u64) -> Result<Self, String> {
+        let chain = (0..=height)
+            .map(|id| {
+                load::<Block>(storage.as_ref(), &block_key(id))
+                    .filter(|block| block.id == id)
+                    .ok_or_else(|| format!("Bloco {} ausente no storage", id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(block) = chain
+            .windows(2)
+            .find(|pair| pair[1].hash_previous_block != pair[0].hash)
+        {
+            return Err(format!("Bloco {} não encadeia no storage", block[1].id));
+        }
+        let transaction_counter =
+            load::<u64>(storage.as_ref(), TRANSACTION_COUNTER_KEY).unwrap_or(1);
+        let genesis = load::<Genesis>(storage.as_ref(), GENESIS_KEY).unwrap_or_default();
+        if !index::is_current(storage.as_ref()) {
+            info!("Reconstruindo os índices"; height = height);
+            index::rebuild(storage.as_mut(), &chain)
+                .and_then(|()| storage.flush())
+                .map_err(|erro| format!("Erro ao reconstruir os índices: {}", erro))?;
+        }
 
-    pub fn mine_block(&mut self) {
-        let id = self.chain.len() as u64;
-        /* Pega a hash do último bloco da cadeia é copia seu valor para o **previous_hash do  ** novo blo criado
-         */
-        let block_previous_hash = self.chain.last().unwrap().hash.clone();
-
-        //Copia o vetor das pending_transactions, para o vetor transações do bloco
-        let transactions = self.pending_transactions.clone();
-        //Nova instância do tipo Blok
-        let new_block = Block::new(id, &block_previous_hash, transactions);
-
-        //Adiciona a blockchain o novo bloco instanciado.
-        self.chain.push(new_block.clone());
-        //Limpa o vetor de pending_transactions
-        self.pending_transactions.clear();
+        info!("Blockchain carregada do storage"; height = height);
+        Ok(Blockchain {
+            chain,
+            block_size: 5,
+            pending_transactions: HashMap::new(),
+            transaction_counter,
+            storage,
+            side_blocks: BlockTree::new(),
+            subscribers: Vec::new(),
+            ledger: genesis.ledger,
+            check_balances: genesis.checks_balances(),
+            consensus: consensus::from_params(&genesis.consensus, &genesis.chain_id),
+            hasher: hash::from_algorithm(genesis.hash_algorithm),
+            chain_id: genesis.chain_id,
+            checkpoint_interval: genesis.checkpoint_interval,
+            validator_key: None,
+            precommits: Vec::new(),
+            future_precommits: Vec::new(),
+        })
+    }
 
-        println!("Novo bloco adicionado a cadeia \n: {:?}\n", new_block);
+    // Grava o bloco da posição `id` da cadeia e atualiza a altura salva
+    fn save_block(&mut self, id: usize) {
+        save(
+            self.storage.as_mut(),
+            &block_key(id as u64),
+            &self.chain[id],
+        );
+        save(
+            self.storage.as_mut(),
+            HEIGHT_KEY,
+            &(self.chain.len() as u64 - 1),
+        );
     }
-    // Função que instancia uma nova transação
 
-    pub fn create_transaction(&mut self, from: &str, to: &str, value: f64) -> u64 {
-        let transaction = Transaction {
-            from: from.to_string(),
-            to: to.to_string(),
-            value,
-        };
-        let transaction_id = self.transaction_counter;
+    // Altura da cadeia, o bloco genesis tem altura 0
+    pub fn height(&self) -> u64 {
+        self.chain.len() as u64 - 1
+    }
 
-        self.pending_transactions
-            .insert(transaction_id, transaction);
+    pub fn genesis_hash(&self) -> Hash {
+        self.chain[0].hash
+    }
 
-        if self.pending_transactions.len() == self.block_size {
-            self.mine_block();
-        }
-        self.transaction_counter += 1;
+    // Parâmetros do genesis gravados na criação da cadeia
+    pub fn genesis(&self) -> Genesis {
+        load(self.storage.as_ref(), GENESIS_KEY).unwrap_or_default()
+    }
 
-        transaction_id
+    pub fn last_block(&self) -> &Block {
+        self.chain.last().unwrap()
     }
 
-    /* Checa a validade do bloco:
-     ** Checa se previous_hash e a hash do bloco anterior são iguais
-     ** Checa se o id do bloco é igual o id do bloco anterior +1
-     ** Calcula a hash do current_block e checa se bate com a hash do cabeçalho do bloco
-     */
+    // Blocos conhecidos dos ramos laterais
+    pub fn side_blocks(&self) -> &BlockTree {
+        &self.side_blocks
+    }
 
-    fn is_block_valid(&self, block: &Block, previous_block: &Block) -> Result<String, String> {
-        if block.hash_previous_block != previous_block.hash {
-            Err(String::from("Hash do Bloco Anterior incompatível"))
-        } else if block.id != previous_block.id + 1 {
-            Err(String::from("Não corresponde ao próximo bloco da cadeia"))
-        } else if Block::calculate_block_hash(
-            block.id,
-            block.timestamp,
-            &block.hash_previous_block,
-            &block.transactions,
-        ) != block.hash
-        {
-            Err(String::from("Hash invalida"))
-        } else {
-            Ok(String::from("valido"))
-        }
+    // Novo assinante dos eventos da blockchain, com fila de SUBSCRIBER_CAPACITY eventos
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent> {
+        let (sender, receiver) = mpsc::sync_channel(SUBSCRIBER_CAPACITY);
+        self.subscribers.push(sender);
+        receiver
     }
-    /* Função checa a integridade da blockchain.
-     ** Chama a função is_block_valid()
-     * */
 
-    pub fn is_chain_valid(&self) -> bool {
-        /*
-         ** checa a encadeação dos blocos, começando pelo primeiro bloco da cadeia,
-         ** após o genesis_block, id: 1
-         * */
-        for i in 1..self.chain.len() {
-            let current_block = &self.chain[i];
-            let previous_block = &self.chain[i - 1];
-
-            let result = self.is_block_valid(&current_block, &previous_block);
-
-            match result {
-                Ok(_) => continue,
-                Err(erro) => {
-                    println!(
-                        "Bloco id {}: {} Blockchain corrompida!",
-                        current_block.id, erro
-                    );
-                    return false;
+    /* Envia o evento aos assinantes sem bloquear. Assinantes encerrados e
+     ** assinantes lentos, com a fila cheia, são removidos
+     * */
+    fn notify(&mut self, event: ChainEvent) {
+        self.subscribers
+            .retain(|subscriber| match subscriber.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("Assinante lento removido"; capacity = SUBSCRIBER_CAPACITY);
+                    false
                 }
-            }
-        }
-        println!("Blockchain valida");
-        true
+                Err(TrySendError::Disconnected(_)) => false,
+            });
     }
-    // Possibilita a corrupção de uma dada transação em um dado bloco na blockchain
 
-    pub fn corrupt_block(&mut self, block_id: usize, transaction_id: u64, new_value: f64) {
-        // Checa a existência do bloco e da transação dentro do bloco
+    // Procura o bloco pela hash na cadeia canônica e nos ramos laterais
+    // Bloco conhecido pela hash, na cadeia ou em um ramo lateral
+    pub fn is_known_block(&self, hash: &Hash) -> bool {
+        self.find_block(hash).is_some()
+    }
 
-        if block_id < self.chain.len() && transaction_id <= self.transaction_counter {
-            if let Some(corrupt_transaction) =
-                self.chain[block_id].transactions.get_mut(&transaction_id)
-            {
-                corrupt_transaction.value = new_value;
-                println!(
-                    "Bloco id {} corrompido! Transação id: {} alterada! \nNovo valor: {:?}",
-                    block_id, transaction_id, corrupt_transaction
-                );
-                // log(&format!(
-                //     "Bloco id {} corrompido! Transação id: {} alterada! \nNovo valor: {:?}",
-                //     block_id, transaction_id, corrupt_transaction
-                // ));
-            } else {
-                // log(&format!(
-                //     "Bloco id {} ou transação posição: {} não existe na cadeia de blocos",
-                //     block_id, transaction_id
-                // ));
-                println!(
-                    "Bloco id {} ou transação posição: {} não existe na cadeia de blocos",
-                    block_id, transaction_id
-                );
-            }
+    fn find_block(&self, hash: &Hash) -> Option<&Block> {
+        self.block_by_hash(hash)
+            .or_else(|| self.side_blocks.get(hash))
+    }
 
-            self.is_chain_valid();
+    // Garante que o próximo id de transação seja pelo menos `next_id`
+    fn bump_transaction_counter(&mut self, next_id: u64) {
+        if next_id > self.transaction_counter {
+            self.transaction_counter = next_id;
+            save(
+                self.storage.as_mut(),
+                TRANSACTION_COUNTER_KEY,
+                &self.transaction_counter,
+            );
         }
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_blockchain_struct() {
-        let mut blockchain = Blockchain::new();
-        for i in 1..=12 {
-            blockchain.create_transaction(
-                "0xEf8801eaf234ff82801821FFe2d780237F9967",
-                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
-                0.0 + i as f64,
-            );
+    /* Adiciona uma transação recebida de outro nó às pendentes, mantendo o id
+     ** atribuído pelo nó de origem. Transações repetidas são identificadas pela
+     ** hash do conteúdo: retorna false se a transação já está pendente ou na
+     ** cadeia. Se o id já está em uso por outra transação, ela recebe um id local
+     * */
+    pub fn add_pending_transaction(
+        &mut self,
+        transaction_id: u64,
+        transaction: Transaction,
+    ) -> bool {
+        let hash = transaction.hash();
+        if self.is_known_transaction(&hash) {
+            return false;
         }
-        // checar tamanho da cadeia de blocos, considerando
-        // a criação de 3 blocos além do genesis
-        let chain_lenght = blockchain.chain;
-        assert_eq!(chain_lenght.len(), 3);
+        if let Err(erro) = self.check_transaction(&transaction) {
+            warn!("Transação recusada"; transaction_id = transaction_id, erro = erro);
+            return false;
+        }
+        let transaction_id = if self.is_transaction_id_used(transaction_id) {
+            let local_id = self.next_transaction_id();
+            debug!("Id da transação em uso"; transaction_id = transaction_id, local_id = local_id);
+            local_id
+        } else {
+            self.bump_transaction_counter(transaction_id + 1);
+            transaction_id
+        };
+        self.pending_transactions
+            .insert(transaction_id, transaction.clone());
+        self.notify(ChainEvent::PendingTransaction {
+            id: transaction_id,
+            transaction,
+        });
+        true
+    }
 
-        // teste criou 12 transações, ou seja deve existir 2 transações
-        // na lista de transações pendentes.
-        let pending_transaction_check = blockchain.pending_transactions;
-        assert_eq!(pending_transaction_check.len(), 2);
+    // A transação com o conteúdo da hash já está pendente ou na cadeia
+    fn is_known_transaction(&self, hash: &Hash) -> bool {
+        index::transaction_by_hash(self.storage.as_ref(), hash).is_some()
+            || self
+                .pending_transactions
+                .values()
+                .any(|pending| pending.hash() == *hash)
+    }
 
-        //checar tamanho do bloco
-        let block_size_check = blockchain.block_size;
-        assert_eq!(block_size_check, 5);
+    fn is_transaction_id_used(&self, transaction_id: u64) -> bool {
+        self.pending_transactions.contains_key(&transaction_id)
+            || index::transaction_block(self.storage.as_ref(), transaction_id).is_some()
+    }
 
-        //checar o transaction_counter, deve ser +1 número de transações criadas
-        let transaction_counter_check = blockchain.transaction_counter;
-        assert_eq!(transaction_counter_check, 13);
+    // Reserva o próximo id local de transação
+    fn next_transaction_id(&mut self) -> u64 {
+        let transaction_id = self.transaction_counter;
+        self.transaction_counter += 1;
+        save(
+            self.storage.as_mut(),
+            TRANSACTION_COUNTER_KEY,
+            &self.transaction_counter,
+        );
+        transaction_id
     }
-    #[test]
-    fn test_block_is_valid() {
-        let mut blockchain = Blockchain::new();
 
-        for i in 1..=12 {
-            blockchain.create_transaction(
-                "0xEf8801eaf234ff82801821FFe2d780237F9967",
-                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
-                0.0 + i as f64,
-            );
-        }
+    // Consulta o estado da conta de um endereço
+    pub fn account(&self, address: &str) -> Account {
+        state::get_account(self.storage.as_ref(), address)
+    }
 
-        //Utiliza os dois últimos blocos da cadeia
-        let current_block = blockchain.chain[2].clone();
-        let previous_block = blockchain.chain[1].clone();
+    // Conta multisig registrada no endereço
+    pub fn multisig_account(&self, address: &str) -> Option<MultisigAccount> {
+        multisig::get_account(self.storage.as_ref(), address)
+    }
 
-        //testa se os blocos são validos
-        let result = blockchain.is_block_valid(&current_block, &previous_block);
-        assert_eq!(result, Ok(String::from("valido")));
+    pub fn balance(&self, address: &str) -> f64 {
+        self.account(address).balance
     }
 
-    #[test]
-    fn test_invalid_previous_hash() {
-        let mut blockchain = Blockchain::new();
+    // Nonce da próxima transação do endereço, depois das transações pendentes
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        self.pending_transactions
+            .values()
+            .filter(|pending| pending.uses_nonce() && pending.from == address)
+            .map(|pending| pending.nonce + 1)
+            .fold(self.account(address).nonce, u64::max)
+    }
 
-        for i in 1..=12 {
-            blockchain.create_transaction(
-                "0xEf8801eaf234ff82801821FFe2d780237F9967",
-                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
-                0.0 + i as f64,
-            );
-        }
+    // Saldo da conta depois do bloco `block_id`, consultado na raiz do estado do bloco
+    pub fn balance_at(&self, address: &str, block_id: u64) -> Result<f64, String> {
+        let block = self
+            .block_by_id(block_id)
+            .ok_or_else(|| format!("Bloco {} não encontrado", block_id))?;
+        state::get_account_at(self.storage.as_ref(), &block.state_root, address)
+            .map(|account| account.balance)
+    }
 
-        //Utiliza os dois últimos blocos da cadeia
-        let current_block = blockchain.chain[2].clone();
-        let mut previous_block = blockchain.chain[1].clone();
+    // Raiz do estado atual
+    pub fn state_root(&self) -> String {
+        state::state_root(self.storage.as_ref())
+    }
 
-        // alteração da hash do bloco anterior
-        previous_block.hash = String::from("0x0000a52");
+    /* Prova do estado da conta na raiz do bloco `block_id`, verificável com
+     ** `state::verify_account_proof` e o cabeçalho do bloco
+     * */
+    pub fn account_proof(&self, address: &str, block_id: u64) -> Result<AccountProof, String> {
+        let block = self
+            .chain
+            .get(block_id as usize)
+            .ok_or_else(|| format!("Bloco {} não encontrado", block_id))?;
+        state::account_proof(self.storage.as_ref(), &block.state_root, address)
+    }
 
-        // checa se a previous_hash do bloco atual é a mesma hash do bloco anterior,
-        // deve retornar erro hash do bloco anterior incompatível
-        let result = blockchain.is_block_valid(&current_block, &previous_block);
-        assert_eq!(
-            result,
-            Err(String::from("Hash do Bloco Anterior incompatível"))
-        );
+    pub fn ledger(&self) -> LedgerMode {
+        self.ledger
     }
-    #[test]
-    fn test_invalid_block_id() {
-        let mut blockchain = Blockchain::new();
 
-        for i in 1..=12 {
-            blockchain.create_transaction(
-                "0xEf8801eaf234ff82801821FFe2d780237F9967",
-                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
-                0.0 + i as f64,
-            );
-        }
+    // Validadores atuais da prova de autoridade, em ordem de vez
+    pub fn validators(&self) -> Vec<String> {
+        consensus::validators(self.storage.as_ref())
+    }
 
-        //Utiliza os dois últimos blocos da cadeia
-        let mut current_block = blockchain.chain[2].clone();
-        let previous_block = blockchain.chain[1].clone();
+    // Participação atual dos validadores da prova de participação
+    pub fn stake_validators(&self) -> Vec<ValidatorStake> {
+        stake::validators(self.storage.as_ref())
+    }
 
-        //altera o id do bloco atual
-        current_block.id = 5;
+    // Valor vinculado pelo delegador a cada validador
+    pub fn delegations(&self, delegator: &str) -> BTreeMap<String, f64> {
+        stake::delegations(self.storage.as_ref(), delegator)
+    }
 
-        //checar se retorna o erro de bloco não é o próximo da cadeia
-        let result = blockchain.is_block_valid(&current_block, &previous_block);
-        assert_eq!(
-            result,
-            Err(String::from("Não corresponde ao próximo bloco da cadeia"))
-        );
+    // Chave do validador local, utilizada para selar os blocos produzidos
+    pub fn set_validator_key(&mut self, key: SigningKey) {
+        self.consensus.set_key(key.clone());
+        self.validator_key = Some(key);
     }
-    #[test]
 
-    fn test_invalid_hash() {
-        let mut blockchain = Blockchain::new();
+    // Último checkpoint finalizado, o genesis quando nenhum foi finalizado
+    pub fn finalized(&self) -> Checkpoint {
+        finality::finalized(self.storage.as_ref()).unwrap_or_else(|| Checkpoint {
+            id: 0,
+            hash: self.genesis_hash(),
+        })
+    }
 
-        for i in 1..=12 {
-            blockchain.create_transaction(
-                "0xEf8801eaf234ff82801821FFe2d780237F9967",
-                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
-                0.0 + i as f64,
-            );
+    /* Adiciona o pré-commit de um validador a um checkpoint da cadeia.
+     ** Retorna true quando o voto finaliza o checkpoint. O pré-commit de um
+     ** checkpoint ainda não recebido fica guardado até o bloco chegar
+     * */
+    pub fn add_precommit(&mut self, vote: Precommit) -> Result<bool, String> {
+        if !finality::is_checkpoint(vote.block_id, self.checkpoint_interval) {
+            return Err(String::from("Bloco não é checkpoint"));
+        }
+        if vote.block_id <= self.finalized().id {
+            return Err(String::from("Checkpoint já finalizado"));
+        }
+        vote.verify()?;
+        if vote.block_id > self.height() {
+            return self.buffer_precommit(vote).map(|()| false);
+        }
+        let voting_power = self
+            .consensus
+            .voting_power(self.storage.as_ref(), vote.block_id);
+        if !voting_power
+            .iter()
+            .any(|(validator, _)| *validator == vote.validator)
+        {
+            return Err(String::from("Pré-commit de quem não é validador"));
         }
+        // O voto duplo é detectado mesmo quando uma das hashes está fora da cadeia
+        finality::check_vote(self.storage.as_ref(), &vote)?;
+        if self.chain[vote.block_id as usize].hash != vote.block_hash {
+            return Err(String::from("Pré-commit em bloco fora da cadeia"));
+        }
+        finality::add_vote(self.storage.as_mut(), &vote)?;
 
-        //Utiliza os dois últimos blocos da cadeia
-        let mut current_block = blockchain.chain[2].clone();
-        let previous_block = blockchain.chain[1].clone();
+        let checkpoint = vote.checkpoint();
+        let voted = finality::voted_power(self.storage.as_ref(), &checkpoint, &voting_power);
+        if !finality::has_supermajority(voted, &voting_power) {
+            return Ok(false);
+        }
+        finality::set_finalized(self.storage.as_mut(), &checkpoint);
+        // Ramos até o checkpoint finalizado não podem mais se tornar canônicos
+        self.side_blocks.prune(checkpoint.id + 1);
+        info!("Checkpoint finalizado"; id = checkpoint.id, hash = checkpoint.hash);
+        Ok(true)
+    }
 
-        //Altera a hash do bloco atual
-        current_block.hash = String::from("Invalid_hash");
+    // Guarda o pré-commit de um checkpoint acima da cadeia, sem repetições
+    fn buffer_precommit(&mut self, vote: Precommit) -> Result<(), String> {
+        if vote.block_id > self.height() + FUTURE_CHECKPOINTS * self.checkpoint_interval {
+            return Err(String::from("Checkpoint desconhecido"));
+        }
+        if self.future_precommits.contains(&vote) {
+            return Err(String::from("Pré-commit repetido"));
+        }
+        if self.future_precommits.len() >= MAX_FUTURE_PRECOMMITS {
+            return Err(String::from("Limite de pré-commits guardados atingido"));
+        }
+        self.future_precommits.push(vote);
+        Ok(())
+    }
 
-        //Testa se a função retorna o erro de Hash invalida
-        let result = blockchain.is_block_valid(&current_block, &previous_block);
-        assert_eq!(result, Err(String::from("Hash invalida")));
+    // Aplica os pré-commits guardados dos checkpoints que chegaram à cadeia
+    fn add_future_precommits(&mut self) {
+        let height = self.height();
+        let (ready, future) = std::mem::take(&mut self.future_precommits)
+            .into_iter()
+            .partition::<Vec<_>, _>(|vote| vote.block_id <= height);
+        self.future_precommits = future;
+        for vote in ready {
+            if let Err(erro) = self.add_precommit(vote) {
+                debug!("Pré-commit guardado recusado"; erro = erro);
+            }
+        }
     }
 
-    #[test]
-    fn test_is_chain_valid() {
-        let mut blockchain = Blockchain::new();
+    // Pré-commits do validador local para enviar aos peers
+    pub fn take_precommits(&mut self) -> Vec<Precommit> {
+        std::mem::take(&mut self.precommits)
+    }
 
-        for i in 1..=12 {
-            blockchain.create_transaction(
-                "0xEf8801eaf234ff82801821FFe2d780237F9967",
-                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+    /* Pré-commit do validador local quando o último bloco da cadeia é um
+     ** checkpoint. A altura assinada é gravada antes do envio e nenhuma outra hash
+     ** é assinada nessa altura ou abaixo dela, nem depois de uma reorganização
+     * */
+    fn precommit_checkpoint(&mut self) {
+        let Some(key) = &self.validator_key else {
+            return;
+        };
+        let block = self.last_block();
+        if !finality::is_checkpoint(block.id, self.checkpoint_interval) {
+            return;
+        }
+        let validator = finality::public_key(key);
+        if let Some(signed) = finality::last_signed(self.storage.as_ref(), &validator) {
+            if block.id <= signed {
+                debug!("Pré-commit não enviado, altura já assinada"; id = block.id, signed = signed);
+                return;
+            }
+        }
+        let vote = Precommit::sign(
+            key,
+            &Checkpoint {
+                id: block.id,
+                hash: block.hash,
+            },
+        );
+        finality::set_last_signed(self.storage.as_mut(), &validator, vote.block_id);
+        if let Err(erro) = self.storage.flush() {
+            error!("Altura assinada não gravada, pré-commit não enviado"; id = vote.block_id, erro = erro);
+            return;
+        }
+        match self.add_precommit(vote.clone()) {
+            Ok(_) => self.precommits.push(vote),
+            Err(erro) => debug!("Pré-commit não enviado"; id = vote.block_id, erro = erro),
+        }
+    }
+
+    /* Checa uma nova transação antes de aceitá-la nas pendentes. A transação
+     ** precisa da assinatura do remetente e de um nonce ainda não utilizado na
+     ** cadeia nem nas pendentes. No modo UTXO as entradas precisam estar
+     ** disponíveis na cadeia e não podem ter sido gastas por outra transação
+     ** pendente (gasto duplo)
+     * */
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), String> {
+        match &transaction.kind {
+            TransactionKind::Call { gas_price, .. } => contract::check_gas_price(*gas_price)?,
+            TransactionKind::Deploy { code, gas_price } => {
+                contract::check_gas_price(*gas_price)?;
+                contract::check_code_size(code)?;
+            }
+            _ => {}
+        }
+        if let TransactionKind::Evidence { first, second } = &transaction.kind {
+            // Transações do sistema não têm remetente, valem pelo conteúdo
+            transaction.check_system()?;
+            if !stake::enabled(self.storage.as_ref()) {
+                return Err(String::from("Evidência fora da prova de participação"));
+            }
+            return stake::check_evidence(first, second, &self.chain_id);
+        }
+        Blockchain::verify_sender(self.storage.as_ref(), transaction)?;
+        if transaction.uses_nonce() {
+            if transaction.nonce < self.account(&transaction.from).nonce {
+                return Err(String::from("Nonce já utilizado"));
+            }
+            if self.pending_transactions.values().any(|pending| {
+                pending.uses_nonce()
+                    && pending.from == transaction.from
+                    && pending.nonce == transaction.nonce
+            }) {
+                return Err(String::from("Nonce já utilizado nas transações pendentes"));
+            }
+        }
+        match self.ledger {
+            LedgerMode::Account if self.check_balances => {
+                // O saldo precisa cobrir também as transações pendentes do remetente
+                let pending = self
+                    .pending_transactions
+                    .values()
+                    .filter(|pending| pending.from == transaction.from && !pending.is_system())
+                    .map(max_debit)
+                    .sum::<f64>();
+                state::check_debit(
+                    self.storage.as_ref(),
+                    &transaction.from,
+                    transaction.value,
+                    pending + max_debit(transaction),
+                )
+            }
+            LedgerMode::Account => Ok(()),
+            LedgerMode::Utxo if matches!(transaction.kind, TransactionKind::Governance { .. }) => {
+                Ok(())
+            }
+            LedgerMode::Utxo => utxo::check_pending(
+                self.storage.as_ref(),
+                transaction,
+                &self.pending_transactions,
+            ),
+        }
+    }
+
+    // Saídas do endereço que podem ser gastas, sem as gastas pelas transações pendentes
+    pub fn unspent_outputs(&self, address: &str) -> Vec<(OutPoint, TxOutput)> {
+        utxo::available_outputs(self.storage.as_ref(), address, &self.pending_transactions)
+    }
+
+    /* Monta uma transferência no modelo da cadeia: no modo UTXO as saídas do
+     ** remetente são escolhidas e o excedente volta como troco
+     * */
+    pub fn build_transfer(&self, from: &str, to: &str, value: f64) -> Result<Transaction, String> {
+        match self.ledger {
+            LedgerMode::Account => Ok(Transaction::new(from, to, value)),
+            LedgerMode::Utxo => utxo::build_transfer(&self.unspent_outputs(from), from, to, value),
+        }
+    }
+
+    // Consulta o recibo de uma transação já minerada
+    pub fn receipt(&self, transaction_id: u64) -> Option<Receipt> {
+        receipt::get_receipt(self.storage.as_ref(), transaction_id)
+    }
+
+    // Recibos das transações de um bloco, ordenados pelo id da transação
+    pub fn receipts(&self, block_id: u64) -> Vec<Receipt> {
+        receipt::get_block_receipts(self.storage.as_ref(), block_id)
+    }
+
+    // Consulta um valor do armazenamento de um contrato
+    pub fn contract_storage(&self, contract: &str, key: &[u8]) -> Option<Vec<u8>> {
+        contract::get_storage(self.storage.as_ref(), contract, key)
+    }
+
+    // Bloco da cadeia canônica pelo id
+    pub fn block_by_id(&self, id: u64) -> Option<&Block> {
+        self.chain.get(id as usize)
+    }
+
+    // Bloco da cadeia canônica pela hash, consultado no índice
+    pub fn block_by_hash(&self, hash: &Hash) -> Option<&Block> {
+        let id = index::block_id(self.storage.as_ref(), hash)?;
+        self.block_by_id(id).filter(|block| block.hash == *hash)
+    }
+
+    // Transação incluída na cadeia canônica e o bloco que a contém
+    pub fn find_transaction(&self, transaction_id: u64) -> Option<(&Block, &Transaction)> {
+        let block_id = index::transaction_block(self.storage.as_ref(), transaction_id)?;
+        let block = self.block_by_id(block_id)?;
+        Some((block, block.transactions.get(&transaction_id)?))
+    }
+
+    // Transações da cadeia canônica que envolvem o endereço, em ordem da cadeia
+    pub fn history(&self, address: &str) -> Vec<(u64, &Transaction)> {
+        index::address_transactions(self.storage.as_ref(), address)
+            .into_iter()
+            .filter_map(|id| {
+                self.find_transaction(id)
+                    .map(|(_, transaction)| (id, transaction))
+            })
+            .collect()
+    }
+
+    /* Página do histórico de transferências do endereço, até `limit` transferências
+     ** a partir do cursor (posição no histórico depois da página anterior)
+     * */
+    pub fn history_page(&self, address: &str, cursor: Option<u64>, limit: usize) -> HistoryPage {
+        let storage = self.storage.as_ref();
+        let mut transfers = Vec::new();
+        let mut next_cursor = cursor;
+        // Transações que falharam ou não moveram valor são puladas, a página é completada
+        while transfers.len() < limit {
+            let (ids, next) =
+                index::address_page(storage, address, next_cursor, limit - transfers.len());
+            transfers.extend(ids.into_iter().filter_map(|id| {
+                let (block, transaction) = self.find_transaction(id)?;
+                index::transfer(storage, address, id, block.id, transaction)
+            }));
+            next_cursor = next;
+            if next_cursor.is_none() {
+                break;
+            }
+        }
+        HistoryPage {
+            transfers,
+            next_cursor,
+        }
+    }
+
+    /* Ao completar 5 no pending_transactions, um novo bloco é minerado,
+     ** sua hash é calculada e o bloco e adicionado a cadeia de blocos.
+     ** Se o consenso não permite a produção do bloco as transações continuam pendentes
+     * */
+
+    pub fn mine_block(&mut self) {
+        if let Err(erro) = self.try_mine_block() {
+            warn!("Bloco não produzido"; id = self.chain.len(), erro = erro);
+        }
+    }
+
+    // Produz o próximo bloco, falha se o consenso não permite a produção pelo nó local
+    pub fn try_mine_block(&mut self) -> Result<(), String> {
+        let id = self.chain.len() as u64;
+        let producer = self.consensus.prepare(self.storage.as_ref(), id)?;
+        /* Pega a hash do último bloco da cadeia é copia seu valor para o **previous_hash do  ** novo blo criado
+         */
+        let block_previous_hash = self.chain.last().unwrap().hash;
+
+        //Copia o vetor das pending_transactions, para o vetor transações do bloco
+        let transactions = self.pending_transactions.clone();
+
+        //Aplica as transações do bloco ao estado das contas
+        let mut overlay = self.begin_block();
+        let receipts = Blockchain::apply_transactions(
+            &mut overlay,
+            self.ledger,
+            self.check_balances,
+            &self.chain_id,
+            id,
+            &transactions,
+        );
+        self.consensus.finalize_block(&mut overlay, id, &producer);
+        receipt::save_receipts(&mut overlay, id, &receipts);
+        let state_root = Blockchain::save_state_root(&mut overlay, id);
+
+        //Nova instância do tipo Blok
+        let receipts_root = receipt::receipts_root(&receipts);
+        let mut new_block = Block::new(
+            id,
+            block_previous_hash,
+            &receipts_root,
+            &state_root,
+            transactions,
+            self.hasher.as_ref(),
+        );
+        //Selo do consenso, em caso de erro o estado não é alterado
+        if !producer.is_empty() {
+            new_block.set_producer(&producer, self.hasher.as_ref());
+            if let Err(erro) = self.consensus.seal(&mut new_block) {
+                self.storage = overlay.discard();
+                return Err(erro);
+            }
+        }
+        if let Err(erro) = index::index_block(&mut overlay, &new_block) {
+            self.storage = overlay.discard();
+            return Err(erro);
+        }
+        self.commit_block(id, overlay);
+
+        //Adiciona a blockchain o novo bloco instanciado.
+        self.chain.push(new_block.clone());
+        self.save_block(self.chain.len() - 1);
+        self.persist_block()?;
+        self.notify(ChainEvent::NewBlock(new_block.clone()));
+        //Limpa o vetor de pending_transactions
+        self.pending_transactions.clear();
+        self.precommit_checkpoint();
+        self.add_future_precommits();
+
+        info!(
+            "Novo bloco adicionado a cadeia";
+            id = new_block.id,
+            hash = new_block.hash,
+            transactions = new_block.transactions.len()
+        );
+        debug!("Novo bloco: {:?}", new_block);
+        Ok(())
+    }
+
+    /* Importa um bloco produzido por outro nó.
+     ** - Se o bloco continua a cadeia, suas transações são executadas novamente e o
+     **   bloco é checado com as mesmas regras de is_block_valid. Se o bloco for
+     **   inválido o estado não é alterado
+     ** - Se o bloco aponta para outro bloco conhecido, ele é guardado na árvore de
+     **   blocos e, se o seu ramo ficar mais longo que a cadeia, acontece a reorganização
+     * */
+    pub fn import_block(&mut self, block: Block) -> Result<(), String> {
+        if self.find_block(&block.hash).is_some() {
+            return Err(String::from("Bloco já existe"));
+        }
+
+        if block.hash_previous_block == self.last_block().hash {
+            self.append_block(block)?;
+            let block = self.last_block().clone();
+            self.remove_pending(&block);
+            info!("Bloco importado"; id = block.id, hash = block.hash);
+            return Ok(());
+        }
+
+        // Bloco de um ramo lateral, o bloco anterior precisa ser conhecido e o
+        // ramo não pode desfazer blocos finalizados
+        if block.id <= self.finalized().id {
+            return Err(String::from("Bloco abaixo do checkpoint finalizado"));
+        }
+        let previous_block = self
+            .find_block(&block.hash_previous_block)
+            .ok_or_else(|| String::from("Bloco anterior desconhecido"))?;
+        Blockchain::check_block_header(
+            &block,
+            previous_block,
+            self.hasher.as_ref(),
+            &self.chain_id,
+        )?;
+        self.report_double_sign(&block);
+
+        let (id, hash) = (block.id, block.hash);
+        self.side_blocks.insert(block);
+        info!("Bloco adicionado a um ramo lateral"; id = id, hash = hash);
+        if id > self.height() {
+            self.reorganize(&hash)?;
+        }
+        Ok(())
+    }
+
+    /* Na prova de participação, um bloco de outro ramo assinado pelo produtor de
+     ** um bloco da cadeia na mesma altura é evidência de assinatura dupla
+     * */
+    fn report_double_sign(&mut self, block: &Block) {
+        let Some(existing) = self.chain.get(block.id as usize) else {
+            return;
+        };
+        let (first, second) = (existing.header(), block.header());
+        if !stake::enabled(self.storage.as_ref())
+            || stake::check_evidence(&first, &second, &self.chain_id).is_err()
+        {
+            return;
+        }
+        warn!("Assinatura dupla detectada"; validator = block.producer, height = block.id);
+        // Transação do sistema: sem remetente, destino, valor ou assinatura
+        let mut evidence = Transaction::new("", "", 0.0);
+        evidence.kind = TransactionKind::Evidence {
+            first: Box::new(first),
+            second: Box::new(second),
+        };
+        self.submit_transaction(evidence);
+    }
+
+    /* Reorganização: torna canônico o ramo terminado em `tip`. Os blocos da cadeia
+     ** acima do ponto de bifurcação são desfeitos e passam para a árvore de blocos.
+     ** Se um bloco do novo ramo for inválido, ele e seus descendentes são descartados
+     ** e a cadeia anterior é restaurada
+     * */
+    fn reorganize(&mut self, tip: &Hash) -> Result<(), String> {
+        let branch = self.side_blocks.branch(tip);
+        let fork_id = branch[0].id - 1;
+        if self.chain[fork_id as usize].hash != branch[0].hash_previous_block {
+            return Err(String::from("Ramo sem ponto de bifurcação na cadeia"));
+        }
+        if fork_id < self.finalized().id {
+            return Err(String::from("Reorganização desfaz blocos finalizados"));
+        }
+
+        let mut orphaned = vec![];
+        while self.height() > fork_id {
+            orphaned.push(self.rollback_block());
+        }
+        orphaned.reverse();
+
+        for block in &branch {
+            self.side_blocks.remove(&block.hash);
+            if let Err(erro) = self.append_block(block.clone()) {
+                warn!("Ramo inválido, reorganização desfeita"; id = block.id, erro = erro);
+                self.side_blocks.remove_with_descendants(&block.hash);
+                while self.height() > fork_id {
+                    let block = self.rollback_block();
+                    self.side_blocks.insert(block);
+                }
+                let mut orphaned = orphaned.into_iter();
+                for block in orphaned.by_ref() {
+                    if let Err(restore) = self.append_block(block.clone()) {
+                        error!("Erro ao restaurar bloco da cadeia anterior"; id = block.id, erro = restore);
+                        self.side_blocks.insert(block);
+                        for block in orphaned {
+                            self.side_blocks.insert(block);
+                        }
+                        return Err(format!(
+                            "{}, erro ao restaurar a cadeia anterior: {}",
+                            erro, restore
+                        ));
+                    }
+                }
+                return Err(erro);
+            }
+        }
+
+        for block in &branch {
+            self.remove_pending(block);
+        }
+        /* Transações dos blocos órfãos que não estão no novo ramo voltam a ficar
+         ** pendentes e são enviadas aos assinantes como novas pendentes
+         * */
+        for block in orphaned {
+            let mut transactions = block.transactions.iter().collect::<Vec<_>>();
+            transactions.sort_by_key(|(transaction_id, _)| **transaction_id);
+            for (transaction_id, transaction) in transactions {
+                if self.is_known_transaction(&transaction.hash()) {
+                    continue;
+                }
+                let transaction_id = if self.is_transaction_id_used(*transaction_id) {
+                    self.next_transaction_id()
+                } else {
+                    *transaction_id
+                };
+                self.pending_transactions
+                    .insert(transaction_id, transaction.clone());
+                self.notify(ChainEvent::PendingTransaction {
+                    id: transaction_id,
+                    transaction: transaction.clone(),
+                });
+            }
+            self.side_blocks.insert(block);
+        }
+
+        info!(
+            "Cadeia reorganizada";
+            fork_id = fork_id,
+            height = self.height(),
+            hash = self.last_block().hash
+        );
+        Ok(())
+    }
+
+    /* Adiciona o bloco ao final da cadeia, executando suas transações e checando
+     ** o bloco. Se o bloco for inválido nada é alterado
+     * */
+    fn append_block(&mut self, block: Block) -> Result<(), String> {
+        let previous_block = self.chain.last().unwrap();
+        Blockchain::check_block_header(
+            &block,
+            previous_block,
+            self.hasher.as_ref(),
+            &self.chain_id,
+        )?;
+        self.consensus.verify(self.storage.as_ref(), &block)?;
+        index::check_transaction_ids(self.storage.as_ref(), &block)?;
+
+        let mut overlay = self.begin_block();
+        let receipts = Blockchain::apply_transactions(
+            &mut overlay,
+            self.ledger,
+            self.check_balances,
+            &self.chain_id,
+            block.id,
+            &block.transactions,
+        );
+        self.consensus
+            .finalize_block(&mut overlay, block.id, &block.producer);
+        receipt::save_receipts(&mut overlay, block.id, &receipts);
+        Blockchain::save_state_root(&mut overlay, block.id);
+        if let Err(erro) = Blockchain::validate_block(
+            &overlay,
+            &block,
+            self.last_block(),
+            self.hasher.as_ref(),
+            &self.chain_id,
+        )
+        .and_then(|_| index::index_block(&mut overlay, &block))
+        {
+            self.storage = overlay.discard();
+            return Err(erro);
+        }
+        self.commit_block(block.id, overlay);
+
+        self.chain.push(block.clone());
+        self.save_block(self.chain.len() - 1);
+        self.persist_block()?;
+        self.notify(ChainEvent::NewBlock(block));
+        self.precommit_checkpoint();
+        self.add_future_precommits();
+        Ok(())
+    }
+
+    /* Grava no disco o bloco adicionado ao final da cadeia, antes de publicá-lo
+     ** aos assinantes e de alterar as pendentes. Em caso de erro de escrita o
+     ** bloco é desfeito e a cadeia, o estado e as pendentes ficam como antes
+     * */
+    fn persist_block(&mut self) -> Result<(), String> {
+        if let Err(erro) = self.storage.flush() {
+            let block = self.rollback_block();
+            error!("Erro ao gravar o bloco, bloco desfeito"; id = block.id, erro = erro);
+            return Err(erro);
+        }
+        Ok(())
+    }
+
+    /* Desfaz o último bloco da cadeia: restaura os valores anteriores gravados no
+     ** registro de desfazer do bloco e remove o bloco do storage
+     * */
+    fn rollback_block(&mut self) -> Block {
+        let block = self.chain.pop().expect("Cadeia sem blocos");
+        let height = self.height();
+        let storage = self.storage.as_mut();
+        let undo: Vec<(Vec<u8>, Option<Vec<u8>>)> =
+            load(storage, &undo_key(block.id)).unwrap_or_default();
+        for (key, value) in undo {
+            match value {
+                Some(value) => storage.set(&key, &value),
+                None => storage.remove(&key),
+            }
+        }
+        storage.remove(undo_key(block.id).as_bytes());
+        storage.remove(block_key(block.id).as_bytes());
+        save(storage, HEIGHT_KEY, &height);
+        debug!("Bloco desfeito"; id = block.id, hash = block.hash);
+        block
+    }
+
+    // Camada sobre o storage para aplicar as transações de um bloco
+    fn begin_block(&mut self) -> OverlayStorage {
+        let base = std::mem::replace(&mut self.storage, Box::new(MemoryStorage::new()));
+        OverlayStorage::new(base)
+    }
+
+    // Grava a raiz do estado depois da aplicação do bloco, checada na validação
+    fn save_state_root(storage: &mut dyn Storage, block_id: u64) -> String {
+        let state_root = state::state_root(storage);
+        save(storage, &state_root_key(block_id), &state_root);
+        state_root
+    }
+
+    // Grava as alterações do bloco e o registro para desfazê-las
+    fn commit_block(&mut self, block_id: u64, overlay: OverlayStorage) {
+        let undo = overlay.undo_log();
+        self.storage = overlay.commit();
+        save(self.storage.as_mut(), &undo_key(block_id), &undo);
+    }
+
+    /* Transações incluídas no bloco deixam de estar pendentes, comparadas pela
+     ** hash do conteúdo. Pendentes com o id de outra transação do bloco recebem
+     ** um novo id local
+     * */
+    fn remove_pending(&mut self, block: &Block) {
+        let included = block
+            .transactions
+            .values()
+            .map(Transaction::hash)
+            .collect::<HashSet<_>>();
+        self.pending_transactions
+            .retain(|_, transaction| !included.contains(&transaction.hash()));
+        let next_transaction_id = block.transactions.keys().max().map_or(0, |id| id + 1);
+        self.bump_transaction_counter(next_transaction_id);
+
+        let colliding = block
+            .transactions
+            .keys()
+            .filter(|id| self.pending_transactions.contains_key(id))
+            .copied()
+            .collect::<Vec<_>>();
+        for transaction_id in colliding {
+            let transaction = self.pending_transactions.remove(&transaction_id).unwrap();
+            let local_id = self.next_transaction_id();
+            self.pending_transactions.insert(local_id, transaction);
+        }
+    }
+
+    /* Checa as assinaturas da transação: toda transação precisa da assinatura do
+     ** remetente, remetentes multisig registrados exigem as M assinaturas da conta.
+     ** Apenas as transações do sistema (evidências) não têm remetente nem assinatura
+     * */
+    fn verify_sender(storage: &dyn Storage, transaction: &Transaction) -> Result<(), String> {
+        if transaction.is_system() {
+            return transaction.check_system();
+        }
+        match multisig::get_account(storage, &transaction.from) {
+            Some(account) => multisig::verify_transaction(&account, transaction),
+            None if !transaction.signatures.is_empty() => {
+                Err(String::from("Conta multisig não registrada"))
+            }
+            None => wallet::verify_transaction(transaction),
+        }
+    }
+
+    /* Aplica as transações ao estado, em ordem de id, gerando um recibo para
+     ** cada transação
+     * */
+    fn apply_transactions(
+        storage: &mut dyn Storage,
+        ledger: LedgerMode,
+        check_balances: bool,
+        chain_id: &str,
+        block_id: u64,
+        transactions: &HashMap<u64, Transaction>,
+    ) -> Vec<Receipt> {
+        let mut transaction_ids = transactions.keys().copied().collect::<Vec<_>>();
+        transaction_ids.sort();
+        let mut receipts = vec![];
+        for transaction_id in transaction_ids {
+            let transaction = &transactions[&transaction_id];
+            let execution = Blockchain::apply_transaction(
+                storage,
+                ledger,
+                check_balances,
+                chain_id,
+                block_id,
+                transaction_id,
+                transaction,
+            );
+            if let ExecutionStatus::Failed(erro) = &execution.status {
+                warn!("Transação falhou"; transaction_id = transaction_id, erro = erro);
+            } else if execution.status == ExecutionStatus::OutOfGas {
+                warn!("Transação falhou, gas esgotado"; transaction_id = transaction_id);
+            }
+            let events = execution
+                .logs
+                .into_iter()
+                .map(|message| Event {
+                    contract: transaction.to.clone(),
+                    message,
+                })
+                .collect();
+            receipts.push(Receipt {
+                transaction_id,
+                block_id,
+                status: execution.status,
+                gas_used: execution.gas_used,
+                events,
+                state_root: state::state_root(storage),
+            });
+        }
+        receipts
+    }
+
+    /* Aplica uma transação ao estado. Transferências sempre são aplicadas,
+     ** publicações e chamadas de contrato que falham não alteram o estado
+     ** (exceto a cobrança do gas). Com `check_balances` a transação que
+     ** debitaria mais que o saldo do remetente falha sem alterar o saldo
+     * */
+    fn apply_transaction(
+        storage: &mut dyn Storage,
+        ledger: LedgerMode,
+        check_balances: bool,
+        chain_id: &str,
+        block_id: u64,
+        transaction_id: u64,
+        transaction: &Transaction,
+    ) -> Execution {
+        // Transações sem assinatura válida ou com nonce fora de ordem não alteram o estado
+        if let Err(erro) = Blockchain::verify_sender(storage, transaction) {
+            return Execution::failed(erro, 0);
+        }
+        if transaction.uses_nonce() {
+            let expected = state::get_account(storage, &transaction.from).nonce;
+            if transaction.nonce != expected {
+                return Execution::failed(format!("Nonce inválido, esperado {}", expected), 0);
+            }
+            state::increment_nonce(storage, &transaction.from);
+        }
+
+        // No modo UTXO apenas transações Spend e votos de governança alteram o estado
+        if ledger == LedgerMode::Utxo
+            && !matches!(transaction.kind, TransactionKind::Governance { .. })
+        {
+            return match utxo::apply_spend(storage, transaction) {
+                Ok(()) => Execution::default(),
+                Err(erro) => Execution::failed(erro, 0),
+            };
+        }
+
+        if check_balances && !transaction.is_system() {
+            if let Err(erro) = state::check_debit(
+                storage,
+                &transaction.from,
+                transaction.value,
+                max_debit(transaction),
+            ) {
+                return Execution::failed(erro, 0);
+            }
+        }
+
+        match &transaction.kind {
+            TransactionKind::Transfer => {
+                state::apply_transaction(storage, transaction);
+                Execution::default()
+            }
+            TransactionKind::Deploy { code, gas_price } => {
+                if let Err(erro) = contract::check_gas_price(*gas_price)
+                    .and_then(|()| contract::check_code_size(code))
+                {
+                    return Execution::failed(erro, 0);
+                }
+                // A taxa do gas da publicação é queimada, mesmo se a publicação falhar
+                let gas_used = contract::deploy_gas(code);
+                let fee = gas_used as f64 * gas_price;
+                if fee > 0.0 {
+                    if state::get_account(storage, &transaction.from).balance < fee {
+                        return Execution::failed(
+                            String::from("Saldo insuficiente para pagar o gas"),
+                            0,
+                        );
+                    }
+                    state::add_balance(storage, &transaction.from, -fee);
+                }
+                let address = contract::contract_address(&transaction.from, transaction.nonce);
+                if let Err(erro) = contract::deploy(storage, &address, code) {
+                    return Execution::failed(erro, gas_used);
+                }
+                state::transfer(storage, &transaction.from, &address, transaction.value);
+                info!("Contrato publicado"; contract = address, transaction_id = transaction_id);
+                Execution::succeeded(gas_used)
+            }
+            TransactionKind::Call {
+                function,
+                args,
+                gas_limit,
+                gas_price,
+            } => {
+                if let Err(erro) = contract::check_gas_price(*gas_price) {
+                    return Execution::failed(erro, 0);
+                }
+                // O remetente precisa ter saldo para pagar todo o gas_limit
+                let max_fee = *gas_limit as f64 * gas_price;
+                if max_fee > 0.0 && state::get_account(storage, &transaction.from).balance < max_fee
+                {
+                    return Execution::failed(
+                        String::from("Saldo insuficiente para pagar o gas"),
+                        0,
+                    );
+                }
+                state::add_balance(storage, &transaction.from, -max_fee);
+
+                let execution = contract::execute(
+                    storage,
+                    &transaction.to,
+                    &transaction.from,
+                    transaction.value,
+                    function,
+                    args,
+                    *gas_limit,
+                );
+
+                // Devolve o gas não utilizado, a taxa do gas consumido é queimada
+                let refund = (gas_limit - execution.gas_used) as f64 * gas_price;
+                state::add_balance(storage, &transaction.from, refund);
+
+                if execution.is_success() {
+                    contract::commit(storage, &transaction.to, &execution);
+                    state::apply_transaction(storage, transaction);
+                }
+                execution
+            }
+            TransactionKind::Spend { .. } => Execution::failed(
+                String::from("Transações Spend exigem o modo UTXO no genesis"),
+                0,
+            ),
+            TransactionKind::RegisterMultisig {
+                threshold,
+                public_keys,
+            } => {
+                let account = MultisigAccount {
+                    threshold: *threshold,
+                    public_keys: public_keys.clone(),
+                };
+                if account.address() != transaction.to {
+                    return Execution::failed(
+                        String::from("Destino não é o endereço da conta multisig"),
+                        0,
+                    );
+                }
+                if let Err(erro) = multisig::register(storage, &account) {
+                    return Execution::failed(erro, 0);
+                }
+                state::apply_transaction(storage, transaction);
+                info!("Conta multisig registrada"; address = transaction.to, threshold = threshold);
+                Execution::default()
+            }
+            TransactionKind::Governance { action } => {
+                // O voto é da chave que assinou a transação
+                let Some(signature) = &transaction.signature else {
+                    return Execution::failed(String::from("Voto de governança sem assinatura"), 0);
+                };
+                match consensus::vote(storage, &signature.public_key, action) {
+                    Ok(applied) => {
+                        info!("Voto de governança"; transaction_id = transaction_id, applied = applied);
+                        Execution::default()
+                    }
+                    Err(erro) => Execution::failed(erro, 0),
+                }
+            }
+            TransactionKind::Bond { validator } => {
+                match stake::bond(storage, &transaction.from, validator, transaction.value) {
+                    Ok(()) => Execution::default(),
+                    Err(erro) => Execution::failed(erro, 0),
+                }
+            }
+            TransactionKind::Unbond { validator, amount } => {
+                match stake::unbond(storage, &transaction.from, validator, *amount, block_id) {
+                    Ok(()) => Execution::default(),
+                    Err(erro) => Execution::failed(erro, 0),
+                }
+            }
+            TransactionKind::Evidence { first, second } => {
+                match stake::slash(storage, first, second, chain_id) {
+                    Ok(slashed) => {
+                        warn!("Validador punido"; validator = first.producer, height = first.id, slashed = slashed);
+                        Execution::default()
+                    }
+                    Err(erro) => Execution::failed(erro, 0),
+                }
+            }
+        }
+    }
+
+    /* Função que instancia uma nova transação, sem assinatura.
+     ** Transações sem assinatura ficam no bloco mas não alteram o estado,
+     ** transferências assinadas usam `send_transaction`
+     * */
+
+    pub fn create_transaction(&mut self, from: &str, to: &str, value: f64) -> u64 {
+        self.submit_transaction(Transaction::new(from, to, value))
+    }
+
+    // Transferência assinada pelo remetente `key` com o seu próximo nonce, retorna o id
+    pub fn send_transaction(&mut self, key: &SigningKey, to: &str, value: f64) -> u64 {
+        let transaction =
+            Transaction::new(&wallet::derive_address(&key.verifying_key()), to, value);
+        self.submit_signed(key, transaction)
+    }
+
+    /* Publica um contrato wasm, retorna o id da transação.
+     ** O endereço do contrato é `contract::contract_address(from, nonce)`, com o
+     ** próximo nonce do remetente (`next_nonce`) antes do envio. A taxa cobrada
+     ** do remetente é o gas da publicação (`contract::deploy_gas`) vezes `gas_price`
+     * */
+    pub fn deploy_contract(
+        &mut self,
+        key: &SigningKey,
+        code: Vec<u8>,
+        value: f64,
+        gas_price: f64,
+    ) -> u64 {
+        let mut transaction =
+            Transaction::new(&wallet::derive_address(&key.verifying_key()), "", value);
+        transaction.kind = TransactionKind::Deploy { code, gas_price };
+        self.submit_signed(key, transaction)
+    }
+
+    /* Chama a função exportada de um contrato, retorna o id da transação.
+     ** A taxa cobrada do remetente é o gas consumido vezes `gas_price`
+     * */
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_contract(
+        &mut self,
+        key: &SigningKey,
+        contract: &str,
+        function: &str,
+        args: Vec<i64>,
+        value: f64,
+        gas_limit: u64,
+        gas_price: f64,
+    ) -> u64 {
+        let mut transaction = Transaction::new(
+            &wallet::derive_address(&key.verifying_key()),
+            contract,
+            value,
+        );
+        transaction.kind = TransactionKind::Call {
+            function: function.to_string(),
+            args,
+            gas_limit,
+            gas_price,
+        };
+        self.submit_signed(key, transaction)
+    }
+
+    // Assina a transação com o próximo nonce do remetente e a adiciona às pendentes
+    fn submit_signed(&mut self, key: &SigningKey, mut transaction: Transaction) -> u64 {
+        transaction.nonce = self.next_nonce(&transaction.from);
+        wallet::sign_transaction(key, &mut transaction)
+            .expect("Chave não corresponde ao remetente");
+        self.submit_transaction(transaction)
+    }
+
+    // Adiciona a transação as pendentes e minera um novo bloco quando completar o bloco
+    pub fn submit_transaction(&mut self, transaction: Transaction) -> u64 {
+        let transaction_id = self.transaction_counter;
+
+        self.pending_transactions
+            .insert(transaction_id, transaction.clone());
+        self.notify(ChainEvent::PendingTransaction {
+            id: transaction_id,
+            transaction,
+        });
+
+        if self.pending_transactions.len() == self.block_size {
+            self.mine_block();
+        }
+        self.transaction_counter += 1;
+        save(
+            self.storage.as_mut(),
+            TRANSACTION_COUNTER_KEY,
+            &self.transaction_counter,
+        );
+
+        transaction_id
+    }
+
+    /* Checa a validade do bloco:
+     ** Checa se previous_hash e a hash do bloco anterior são iguais
+     ** Checa se o id do bloco é igual o id do bloco anterior +1
+     ** Calcula a hash do current_block e checa se bate com a hash do cabeçalho do bloco
+     ** Checa se a raiz dos recibos gravados bate com a raiz do cabeçalho do bloco
+     ** Checa se a raiz do estado calculada na aplicação bate com a do cabeçalho
+     */
+
+    fn is_block_valid(&self, block: &Block, previous_block: &Block) -> Result<String, String> {
+        Blockchain::validate_block(
+            self.storage.as_ref(),
+            block,
+            previous_block,
+            self.hasher.as_ref(),
+            &self.chain_id,
+        )
+    }
+
+    fn validate_block(
+        storage: &dyn Storage,
+        block: &Block,
+        previous_block: &Block,
+        hasher: &dyn Hasher,
+        chain_id: &str,
+    ) -> Result<String, String> {
+        Blockchain::check_block_header(block, previous_block, hasher, chain_id)?;
+        let receipts = receipt::get_block_receipts(storage, block.id);
+        if receipts.len() != block.transactions.len()
+            || receipts
+                .iter()
+                .any(|receipt| !block.transactions.contains_key(&receipt.transaction_id))
+        {
+            Err(String::from(
+                "Transações do bloco não correspondem aos recibos",
+            ))
+        } else if receipt::receipts_root(&receipts) != block.receipts_root {
+            Err(String::from("Raiz dos recibos invalida"))
+        } else if load::<String>(storage, &state_root_key(block.id)).as_ref()
+            != Some(&block.state_root)
+        {
+            Err(String::from("Raiz do estado invalida"))
+        } else {
+            Ok(String::from("valido"))
+        }
+    }
+
+    // Encadeamento do bloco com o bloco anterior
+    fn check_block_link(block: &Block, previous_block: &Block) -> Result<(), String> {
+        if block.hash_previous_block != previous_block.hash {
+            Err(String::from("Hash do Bloco Anterior incompatível"))
+        } else if block.id != previous_block.id + 1 {
+            Err(String::from("Não corresponde ao próximo bloco da cadeia"))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Checagens do cabeçalho do bloco, não dependem do estado
+    fn check_block_header(
+        block: &Block,
+        previous_block: &Block,
+        hasher: &dyn Hasher,
+        chain_id: &str,
+    ) -> Result<(), String> {
+        Blockchain::check_block_link(block, previous_block)?;
+        // Blocos da versão antiga apenas antes dos blocos da versão atual
+        if block.version < previous_block.version || block.version > BLOCK_VERSION {
+            return Err(format!("Versão do bloco inválida: {}", block.version));
+        }
+        if block.calculate_hash(hasher) != block.hash {
+            Err(String::from("Hash invalida"))
+        } else {
+            block.check_signature(chain_id)
+        }
+    }
+    /* Função checa a integridade da blockchain.
+     ** Chama a função is_block_valid()
+     * */
+    pub fn is_chain_valid(&self) -> bool {
+        match self.validate_chain() {
+            Ok(()) => {
+                info!("Blockchain valida"; height = self.chain.len() - 1);
+                true
+            }
+            Err((block_id, erro)) => {
+                error!("Blockchain corrompida!"; block_id = block_id, erro = erro);
+                false
+            }
+        }
+    }
+
+    /* Checa a cadeia e retorna o id do primeiro bloco inválido com a regra
+     ** que falhou
+     * */
+    pub fn validate_chain(&self) -> Result<(), (u64, String)> {
+        self.validate_blocks(&self.chain)
+    }
+
+    /* Checa uma sequência de blocos a partir do genesis com as regras, o estado e
+     ** o checkpoint finalizado desta cadeia, sem alterá-la (cópias alteradas pelo mod tamper)
+     * */
+    pub fn validate_blocks(&self, chain: &[Block]) -> Result<(), (u64, String)> {
+        let finalized = self.finalized();
+        if chain.get(finalized.id as usize).map(|block| &block.hash) != Some(&finalized.hash) {
+            return Err((finalized.id, String::from("Checkpoint finalizado ausente")));
+        }
+        /*
+         ** checa a encadeação dos blocos, começando pelo primeiro bloco da cadeia,
+         ** após o genesis_block, id: 1. Até o checkpoint finalizado os corpos dos
+         ** blocos não são checados novamente, apenas o encadeamento das hashes
+         * */
+        for i in 1..chain.len() {
+            let current_block = &chain[i];
+            let previous_block = &chain[i - 1];
+
+            let result = if current_block.id <= finalized.id {
+                Blockchain::check_block_link(current_block, previous_block)
+            } else {
+                self.is_block_valid(current_block, previous_block)
+                    .map(|_| ())
+            };
+            result.map_err(|erro| (i as u64, erro))?;
+        }
+        Ok(())
+    }
+
+    // Função hash dos blocos da cadeia
+    pub fn hasher(&self) -> &dyn Hasher {
+        self.hasher.as_ref()
+    }
+
+    /* Possibilita a corrupção de uma dada transação em um dado bloco na blockchain.
+     ** Outros ataques estão no mod tamper
+     * */
+    pub fn corrupt_block(
+        &mut self,
+        block_id: usize,
+        transaction_id: u64,
+        new_value: f64,
+    ) -> Result<(), String> {
+        // Checa a existência do bloco e da transação dentro do bloco
+        let corrupt_transaction = self
+            .chain
+            .get_mut(block_id)
+            .ok_or_else(|| format!("Bloco {} não existe na cadeia", block_id))?
+            .transactions
+            .get_mut(&transaction_id)
+            .ok_or_else(|| {
+                format!(
+                    "Transação {} não existe no bloco {}",
+                    transaction_id, block_id
+                )
+            })?;
+        corrupt_transaction.value = new_value;
+        warn!(
+            "Bloco corrompido! Transação alterada!";
+            block_id = block_id,
+            transaction_id = transaction_id,
+            value = new_value
+        );
+        // A corrupção também é gravada no storage
+        self.save_block(block_id);
+        self.is_chain_valid();
+        Ok(())
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Sha256Hasher;
+    use crate::{
+        contract::tests::GAS_LIMIT,
+        genesis::{ConsensusParams, StakeParams},
+        wallet::tests::test_account,
+    };
+
+    #[test]
+    fn test_blockchain_struct() {
+        let mut blockchain = Blockchain::new();
+        for i in 1..=12 {
+            blockchain.create_transaction(
+                "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+        // checar tamanho da cadeia de blocos, considerando
+        // a criação de 3 blocos além do genesis
+        let chain_lenght = blockchain.chain;
+        assert_eq!(chain_lenght.len(), 3);
+
+        // teste criou 12 transações, ou seja deve existir 2 transações
+        // na lista de transações pendentes.
+        let pending_transaction_check = blockchain.pending_transactions;
+        assert_eq!(pending_transaction_check.len(), 2);
+
+        //checar tamanho do bloco
+        let block_size_check = blockchain.block_size;
+        assert_eq!(block_size_check, 5);
+
+        //checar o transaction_counter, deve ser +1 número de transações criadas
+        let transaction_counter_check = blockchain.transaction_counter;
+        assert_eq!(transaction_counter_check, 13);
+    }
+    #[test]
+    fn test_block_is_valid() {
+        let mut blockchain = Blockchain::new();
+
+        for i in 1..=12 {
+            blockchain.create_transaction(
+                "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+
+        //Utiliza os dois últimos blocos da cadeia
+        let current_block = blockchain.chain[2].clone();
+        let previous_block = blockchain.chain[1].clone();
+
+        //testa se os blocos são validos
+        let result = blockchain.is_block_valid(&current_block, &previous_block);
+        assert_eq!(result, Ok(String::from("valido")));
+    }
+
+    #[test]
+    fn test_invalid_previous_hash() {
+        let mut blockchain = Blockchain::new();
+
+        for i in 1..=12 {
+            blockchain.create_transaction(
+                "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+
+        //Utiliza os dois últimos blocos da cadeia
+        let current_block = blockchain.chain[2].clone();
+        let mut previous_block = blockchain.chain[1].clone();
+
+        // alteração da hash do bloco anterior
+        previous_block.hash = Hash([0xa5; 32]);
+
+        // checa se a previous_hash do bloco atual é a mesma hash do bloco anterior,
+        // deve retornar erro hash do bloco anterior incompatível
+        let result = blockchain.is_block_valid(&current_block, &previous_block);
+        assert_eq!(
+            result,
+            Err(String::from("Hash do Bloco Anterior incompatível"))
+        );
+    }
+    #[test]
+    fn test_invalid_block_id() {
+        let mut blockchain = Blockchain::new();
+
+        for i in 1..=12 {
+            blockchain.create_transaction(
+                "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+
+        //Utiliza os dois últimos blocos da cadeia
+        let mut current_block = blockchain.chain[2].clone();
+        let previous_block = blockchain.chain[1].clone();
+
+        //altera o id do bloco atual
+        current_block.id = 5;
+
+        //checar se retorna o erro de bloco não é o próximo da cadeia
+        let result = blockchain.is_block_valid(&current_block, &previous_block);
+        assert_eq!(
+            result,
+            Err(String::from("Não corresponde ao próximo bloco da cadeia"))
+        );
+    }
+    #[test]
+
+    fn test_invalid_hash() {
+        let mut blockchain = Blockchain::new();
+
+        for i in 1..=12 {
+            blockchain.create_transaction(
+                "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+
+        //Utiliza os dois últimos blocos da cadeia
+        let mut current_block = blockchain.chain[2].clone();
+        let previous_block = blockchain.chain[1].clone();
+
+        //Altera a hash do bloco atual
+        current_block.hash = Hash([1; 32]);
+
+        //Testa se a função retorna o erro de Hash invalida
+        let result = blockchain.is_block_valid(&current_block, &previous_block);
+        assert_eq!(result, Err(String::from("Hash invalida")));
+    }
+
+    #[test]
+    fn test_is_chain_valid() {
+        let mut blockchain = Blockchain::new();
+
+        for i in 1..=12 {
+            blockchain.create_transaction(
+                "0xEf8801eaf234ff82801821FFe2d780237F9967",
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+
+        let result = blockchain.is_chain_valid();
+        assert!(result);
+    }
+    #[test]
+    fn test_blockchain_storage() {
+        use crate::storage::HostStorage;
+
+        let mut blockchain = Blockchain::with_storage(Box::new(HostStorage)).unwrap();
+        let (key, from) = test_account(101);
+        for i in 1..=12 {
+            blockchain.send_transaction(
+                &key,
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+                0.0 + i as f64,
+            );
+        }
+
+        // 10 transações mineradas (1 + 2 + ... + 10), as 2 pendentes não alteram o saldo
+        assert_eq!(
+            blockchain.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            55.0
+        );
+        assert_eq!(blockchain.balance(&from), -55.0);
+        assert_eq!(blockchain.account(&from).nonce, 10);
+
+        // Uma nova blockchain sobre o mesmo storage carrega a cadeia salva
+        let reloaded = Blockchain::with_storage(Box::new(HostStorage)).unwrap();
+        assert_eq!(reloaded.chain.len(), 3);
+        assert_eq!(reloaded.chain[2].hash, blockchain.chain[2].hash);
+        assert_eq!(reloaded.transaction_counter, 13);
+        assert!(reloaded.is_chain_valid());
+
+        // Storage incompleto ou corrompido retorna erro em vez de encerrar o nó
+        let mut storage = HostStorage;
+        let block = storage.get(b"block/1").unwrap();
+        storage.remove(b"block/1");
+        assert_eq!(
+            Blockchain::with_storage(Box::new(HostStorage)).err(),
+            Some(String::from("Bloco 1 ausente no storage"))
+        );
+        storage.set(b"block/1", b"{ invalido");
+        assert!(Blockchain::with_storage(Box::new(HostStorage)).is_err());
+        storage.set(b"block/1", &storage.get(b"block/2").unwrap());
+        assert!(Blockchain::with_storage(Box::new(HostStorage)).is_err());
+        let mut unlinked = blockchain.chain[1].clone();
+        unlinked.hash_previous_block = Hash::ZERO;
+        save(&mut storage, "block/1", &unlinked);
+        assert_eq!(
+            Blockchain::with_storage(Box::new(HostStorage)).err(),
+            Some(String::from("Bloco 1 não encadeia no storage"))
+        );
+        storage.set(b"block/1", &block);
+        assert!(Blockchain::with_storage(Box::new(HostStorage)).is_ok());
+    }
+
+    // Storage em memória com erro de escrita
+    #[derive(Debug, Default)]
+    struct FailingStorage(MemoryStorage);
+
+    impl Storage for FailingStorage {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.0.get(key)
+        }
+
+        fn set(&mut self, key: &[u8], value: &[u8]) {
+            self.0.set(key, value)
+        }
+
+        fn remove(&mut self, key: &[u8]) {
+            self.0.remove(key)
+        }
+
+        fn flush(&mut self) -> Result<(), String> {
+            Err(String::from("Disco cheio"))
+        }
+    }
+
+    #[test]
+    fn test_storage_write_error() {
+        let mut blockchain = Blockchain::with_storage(Box::new(FailingStorage::default())).unwrap();
+        let state_root = blockchain.state_root();
+        let id = blockchain.send_transaction(&test_account(101).0, "0x2", 4.0);
+        let events = blockchain.subscribe();
+        assert_eq!(
+            blockchain.try_mine_block(),
+            Err(String::from("Disco cheio"))
+        );
+        // O bloco não gravado é desfeito: a transação continua pendente
+        assert_eq!(blockchain.height(), 0);
+        assert!(blockchain.pending_transactions.contains_key(&id));
+        assert_eq!(blockchain.state_root(), state_root);
+        assert_eq!(blockchain.balance("0x2"), 0.0);
+        assert!(blockchain.receipt(id).is_none());
+        assert!(events.try_recv().is_err());
+
+        let mut producer = Blockchain::new();
+        let transaction_id = producer.send_transaction(&test_account(101).0, "0x2", 4.0);
+        producer.mine_block();
+        let mut importer = Blockchain::with_storage(Box::new(FailingStorage::default())).unwrap();
+        let transaction = producer.chain[1].transactions[&transaction_id].clone();
+        importer.submit_transaction(transaction);
+        assert_eq!(
+            importer.import_block(producer.chain[1].clone()),
+            Err(String::from("Disco cheio"))
+        );
+        assert_eq!(importer.height(), 0);
+        assert_eq!(importer.pending_transactions.len(), 1);
+        assert_eq!(importer.balance("0x2"), 0.0);
+        assert!(importer.is_chain_valid());
+        // Com o bloco desfeito ele pode ser importado novamente
+        assert_ne!(
+            importer.import_block(producer.chain[1].clone()),
+            Err(String::from("Bloco já existe"))
+        );
+    }
+
+    #[test]
+    fn test_genesis() {
+        let genesis = Genesis {
+            chain_id: String::from("rede-teste"),
+            timestamp: Some(1_700_000_000),
+            alloc: [(
+                String::from("0xEf8801eaf234ff82801821FFe2d780237F9967"),
+                100.0,
+            )]
+            .into(),
+            ..Genesis::default()
+        };
+        let blockchain =
+            Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+
+        assert_eq!(blockchain.genesis(), genesis);
+        assert_eq!(blockchain.chain[0].timestamp, 1_700_000_000);
+        assert_eq!(
+            blockchain.genesis_hash(),
+            hash::Sha256Hasher.hash(&serde_json::to_vec(&genesis).unwrap())
+        );
+        // Parâmetros diferentes geram outra hash do genesis
+        let other = Blockchain::with_genesis(
+            Box::new(MemoryStorage::new()),
+            &Genesis {
+                chain_id: String::from("outra-rede"),
+                ..genesis.clone()
+            },
+        )
+        .unwrap();
+        assert_ne!(other.genesis_hash(), blockchain.genesis_hash());
+        assert_eq!(
+            blockchain.balance("0xEf8801eaf234ff82801821FFe2d780237F9967"),
+            100.0
+        );
+        assert_eq!(Blockchain::new().genesis().chain_id, "local");
+
+        // Parâmetros de consenso inválidos são um erro, não um pânico
+        let invalid = Genesis {
+            consensus: ConsensusParams::Authority { validators: vec![] },
+            ..genesis
+        };
+        assert_eq!(
+            Blockchain::with_genesis(Box::new(MemoryStorage::new()), &invalid).err(),
+            Some(String::from(
+                "Parâmetros de consenso inválidos no genesis: Prova de autoridade sem validadores"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_hash_algorithm() {
+        use crate::{
+            genesis::HashAlgorithm,
+            hash::{Keccak256Hasher, Sha256Hasher},
+        };
+
+        let genesis = Genesis {
+            timestamp: Some(1_700_000_000),
+            hash_algorithm: HashAlgorithm::Keccak256,
+            ..Genesis::default()
+        };
+        let mut blockchain =
+            Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+        blockchain.send_transaction(&test_account(101).0, "0x2", 4.0);
+        blockchain.mine_block();
+
+        let block = blockchain.last_block().clone();
+        assert_eq!(block.calculate_hash(&Keccak256Hasher), block.hash);
+        assert_ne!(block.calculate_hash(&Sha256Hasher), block.hash);
+        assert!(blockchain.is_chain_valid());
+
+        // Uma cadeia com outra função hash tem outro genesis e recusa o bloco
+        let mut other = Blockchain::with_genesis(
+            Box::new(MemoryStorage::new()),
+            &Genesis {
+                hash_algorithm: HashAlgorithm::Sha256,
+                ..genesis
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            other.import_block(block),
+            Err(String::from("Bloco anterior desconhecido"))
+        );
+    }
+
+    #[test]
+    fn test_signed_transactions() {
+        let (key, from) = test_account(101);
+        let mut blockchain = Blockchain::new();
+
+        let mut signed = Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 4.0);
+        wallet::sign_transaction(&key, &mut signed).unwrap();
+        let mut tampered = signed.clone();
+        tampered.value = 40.0;
+        let unsigned = Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 1.0);
+        // Transações sem assinatura ou com assinatura inválida são recusadas nas pendentes
+        assert_eq!(blockchain.check_transaction(&signed), Ok(()));
+        assert_eq!(
+            blockchain.check_transaction(&tampered),
+            Err(String::from("Assinatura inválida"))
+        );
+        assert_eq!(
+            blockchain.check_transaction(&unsigned),
+            Err(String::from("Transação sem assinatura"))
+        );
+        let signed_id = blockchain.submit_transaction(signed.clone());
+        let tampered_id = blockchain.submit_transaction(tampered);
+        let unsigned_id = blockchain.submit_transaction(unsigned);
+        blockchain.mine_block();
+
+        // A transação com assinatura inválida fica no bloco, mas não altera o estado
+        assert!(blockchain.receipt(signed_id).unwrap().is_success());
+        assert_eq!(
+            blockchain.receipt(tampered_id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Assinatura inválida"))
+        );
+        assert_eq!(
+            blockchain.receipt(unsigned_id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Transação sem assinatura"))
+        );
+        assert_eq!(
+            blockchain.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            4.0
+        );
+        assert_eq!(blockchain.account(&from).nonce, 1);
+        assert!(blockchain.is_chain_valid());
+
+        // A mesma transação assinada não é aplicada novamente
+        assert_eq!(
+            blockchain.check_transaction(&signed),
+            Err(String::from("Nonce já utilizado"))
+        );
+        let replay_id = blockchain.submit_transaction(signed.clone());
+        let mut skipped = signed;
+        skipped.nonce = 2;
+        wallet::sign_transaction(&key, &mut skipped).unwrap();
+        let skipped_id = blockchain.submit_transaction(skipped.clone());
+        blockchain.mine_block();
+        for id in [replay_id, skipped_id] {
+            assert_eq!(
+                blockchain.receipt(id).unwrap().status,
+                ExecutionStatus::Failed(String::from("Nonce inválido, esperado 1"))
+            );
+        }
+        assert_eq!(
+            blockchain.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            4.0
+        );
+
+        // O próximo nonce considera as transações pendentes do remetente
+        assert_eq!(blockchain.next_nonce(&from), 1);
+        blockchain.send_transaction(&key, "0x2", 1.0);
+        assert_eq!(blockchain.next_nonce(&from), 2);
+        let mut duplicate = skipped;
+        duplicate.nonce = 1;
+        wallet::sign_transaction(&key, &mut duplicate).unwrap();
+        assert_eq!(
+            blockchain.check_transaction(&duplicate),
+            Err(String::from("Nonce já utilizado nas transações pendentes"))
+        );
+        blockchain.mine_block();
+        assert_eq!(blockchain.balance("0x2"), 1.0);
+        assert_eq!(blockchain.account(&from).nonce, 2);
+    }
+
+    #[test]
+    fn test_multisig_transactions() {
+        let keys = (1..=3)
+            .map(|seed| wallet::keypair_from_seed(&[seed; 32]))
+            .collect::<Vec<_>>();
+        let public_keys = keys
+            .iter()
+            .map(|key| crate::storage::encode_hex(key.verifying_key().as_bytes()))
+            .collect::<Vec<_>>();
+        let account = MultisigAccount::new(2, &public_keys).unwrap();
+        let address = account.address();
+        let mut blockchain = Blockchain::new();
+        let (funder, funder_address) = test_account(101);
+
+        /* O endereço de uma conta ainda não registrada não se distingue de um
+         ** endereço comum: a transferência é aceita e o saldo fica bloqueado até o
+         ** registro, sem chave que assine por ele
+         */
+        let early_id = blockchain.send_transaction(&funder, &address, 2.0);
+        blockchain.mine_block();
+        assert!(blockchain.receipt(early_id).unwrap().is_success());
+        let mut locked = Transaction::new(&address, "0x2", 1.0);
+        let signatures = [
+            multisig::sign_partial(&keys[0], &locked),
+            multisig::sign_partial(&keys[1], &locked),
+        ];
+        multisig::combine(&mut locked, &signatures).unwrap();
+        assert_eq!(
+            blockchain.check_transaction(&locked).err(),
+            Some(String::from("Conta multisig não registrada"))
+        );
+
+        let mut register = account.register_transaction(&funder_address, 10.0);
+        register.nonce = 1;
+        wallet::sign_transaction(&funder, &mut register).unwrap();
+        let mut again = register.clone();
+        again.nonce = 2;
+        wallet::sign_transaction(&funder, &mut again).unwrap();
+        let register_id = blockchain.submit_transaction(register);
+        let again_id = blockchain.submit_transaction(again);
+        blockchain.mine_block();
+        assert!(blockchain.receipt(register_id).unwrap().is_success());
+        assert!(!blockchain.receipt(again_id).unwrap().is_success());
+        assert_eq!(blockchain.multisig_account(&address), Some(account.clone()));
+        assert_eq!(blockchain.balance(&address), 12.0);
+
+        // Sem assinaturas ou com apenas uma a transferência falha
+        let unsigned = Transaction::new(&address, "0x2", 4.0);
+        let first = multisig::sign_partial(&keys[0], &unsigned);
+        let second = multisig::sign_partial(&keys[1], &unsigned);
+        let mut partial = unsigned.clone();
+        multisig::combine(&mut partial, &[first]).unwrap();
+        let mut signed = partial.clone();
+        multisig::combine(&mut signed, &[second]).unwrap();
+
+        let unsigned_id = blockchain.submit_transaction(unsigned);
+        let partial_id = blockchain.submit_transaction(partial);
+        let signed_id = blockchain.submit_transaction(signed.clone());
+        blockchain.mine_block();
+        assert_eq!(
+            blockchain.receipt(unsigned_id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Assinaturas insuficientes: 0 de 2"))
+        );
+        assert_eq!(
+            blockchain.receipt(partial_id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Assinaturas insuficientes: 1 de 2"))
+        );
+        assert!(blockchain.receipt(signed_id).unwrap().is_success());
+        assert_eq!(blockchain.balance(&address), 8.0);
+        assert_eq!(blockchain.balance("0x2"), 4.0);
+
+        // A mesma transação com todas as assinaturas não é aceita uma segunda vez
+        assert_eq!(
+            blockchain.check_transaction(&signed).err(),
+            Some(String::from("Nonce já utilizado"))
+        );
+        let replay_id = blockchain.submit_transaction(signed);
+        blockchain.mine_block();
+        assert_eq!(
+            blockchain.receipt(replay_id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Nonce inválido, esperado 1"))
+        );
+        assert_eq!(blockchain.balance(&address), 8.0);
+        assert_eq!(blockchain.balance("0x2"), 4.0);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_utxo_ledger() {
+        let (key, owner) = test_account(101);
+        let genesis = Genesis {
+            alloc: [(owner.clone(), 10.0)].into(),
+            ledger: LedgerMode::Utxo,
+            ..Genesis::default()
+        };
+        let mut blockchain =
+            Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+        assert_eq!(blockchain.unspent_outputs(&owner).len(), 1);
+        let signed = |mut transaction: Transaction| {
+            wallet::sign_transaction(&key, &mut transaction).unwrap();
+            transaction
+        };
+
+        // Transferências de conta não alteram o estado no modo UTXO
+        let transfer_id = blockchain.send_transaction(&key, "0x2", 1.0);
+        let spend = signed(blockchain.build_transfer(&owner, "0x2", 3.0).unwrap());
+        assert!(blockchain.check_transaction(&spend).is_ok());
+        let spend_id = blockchain.submit_transaction(spend.clone());
+
+        // A mesma saída não pode ser gasta por outra transação pendente
+        let double = signed(
+            utxo::build_transfer(
+                &utxo::unspent_outputs(blockchain.storage.as_ref(), &owner),
+                &owner,
+                "0x3",
+                3.0,
+            )
+            .unwrap(),
+        );
+        assert!(blockchain.check_transaction(&double).is_err());
+        assert!(blockchain.build_transfer(&owner, "0x3", 1.0).is_err());
+        blockchain.mine_block();
+
+        assert!(!blockchain.receipt(transfer_id).unwrap().is_success());
+        assert!(blockchain.receipt(spend_id).unwrap().is_success());
+        assert_eq!(blockchain.balance(&owner), 7.0);
+        assert_eq!(blockchain.balance("0x2"), 3.0);
+
+        // Saída já gasta na cadeia
+        assert!(blockchain.check_transaction(&spend).is_err());
+        let replay_id = blockchain.submit_transaction(spend);
+        let change_id = blockchain.submit_transaction(signed(
+            blockchain.build_transfer(&owner, "0x3", 7.0).unwrap(),
+        ));
+        blockchain.mine_block();
+        assert!(!blockchain.receipt(replay_id).unwrap().is_success());
+        assert!(blockchain.receipt(change_id).unwrap().is_success());
+        assert_eq!(blockchain.balance(&owner), 0.0);
+        assert_eq!(blockchain.balance("0x3"), 7.0);
+        assert!(blockchain.unspent_outputs(&owner).is_empty());
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_contract_transactions() {
+        let mut blockchain = Blockchain::new();
+        let (key, owner) = test_account(101);
+
+        let address = contract::contract_address(&owner, blockchain.next_nonce(&owner));
+        blockchain.deploy_contract(&key, contract::tests::counter_code(), 0.0, 0.0);
+        blockchain.call_contract(&key, &address, "add", vec![5], 1.5, GAS_LIMIT, 0.0);
+        blockchain.call_contract(&key, &address, "add", vec![7], 0.0, GAS_LIMIT, 0.0);
+        // chamada que falha não altera o armazenamento nem transfere o valor
+        blockchain.call_contract(&key, &address, "fail", vec![], 3.0, GAS_LIMIT, 0.0);
+        blockchain.send_transaction(&key, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 1.0);
+
+        // 5 transações, um bloco minerado
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(
+            blockchain.contract_storage(&address, b"total"),
+            Some(12i64.to_le_bytes().to_vec())
+        );
+        assert_eq!(blockchain.balance(&address), 1.5);
+        assert_eq!(blockchain.balance(&owner), -2.5);
+        // A chamada que falhou também consome o nonce
+        assert_eq!(blockchain.account(&owner).nonce, 5);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_contract_gas_fees() {
+        let mut blockchain = Blockchain::new();
+        let (faucet_key, faucet) = test_account(101);
+        let (user_key, user) = test_account(102);
+        let user = user.as_str();
+
+        blockchain.send_transaction(&faucet_key, user, 100.0);
+        let address = contract::contract_address(&faucet, blockchain.next_nonce(&faucet));
+        blockchain.deploy_contract(&faucet_key, contract::tests::counter_code(), 0.0, 0.0);
+        blockchain.mine_block();
+
+        // Taxa cobrada apenas pelo gas consumido, o restante é devolvido
+        blockchain.call_contract(&user_key, &address, "add", vec![5], 0.0, GAS_LIMIT, 0.0001);
+        blockchain.mine_block();
+        let after_add = blockchain.balance(user);
+        assert!(after_add < 100.0 && after_add > 100.0 - GAS_LIMIT as f64 * 0.0001);
+
+        // Gas esgotado: todo o gas_limit é cobrado, a transação continua no bloco
+        blockchain.call_contract(&user_key, &address, "loop", vec![], 0.0, GAS_LIMIT, 0.0001);
+        blockchain.mine_block();
+        assert_eq!(blockchain.chain[3].transactions.len(), 1);
+        assert!((blockchain.balance(user) - (after_add - 10.0)).abs() < 1e-9);
+
+        // Saldo insuficiente para o gas_limit, nada é cobrado
+        let before = blockchain.balance(user);
+        blockchain.call_contract(&user_key, &address, "add", vec![1], 0.0, 1_000_000_000, 1.0);
+        blockchain.mine_block();
+        assert_eq!(blockchain.balance(user), before);
+
+        // Preço do gas negativo falha sem creditar o remetente
+        let id =
+            blockchain.call_contract(&user_key, &address, "add", vec![1], 0.0, GAS_LIMIT, -1.0);
+        blockchain.mine_block();
+        assert_eq!(
+            blockchain.receipt(id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Preço do gas inválido: -1"))
+        );
+        assert_eq!(blockchain.balance(user), before);
+        // Preços negativos ou não finitos são recusados nas pendentes
+        for gas_price in [-1.0, f64::NAN, f64::INFINITY] {
+            let mut call = Transaction::new(user, &address, 0.0);
+            call.kind = TransactionKind::Call {
+                function: String::from("add"),
+                args: vec![1],
+                gas_limit: GAS_LIMIT,
+                gas_price,
+            };
+            assert!(blockchain.check_transaction(&call).is_err());
+        }
+        assert_eq!(
+            blockchain.contract_storage(&address, b"total"),
+            Some(5i64.to_le_bytes().to_vec())
+        );
+
+        // Deploy cobra gas por byte do bytecode ao preço informado
+        let code = contract::tests::counter_code();
+        let before = blockchain.balance(user);
+        let id = blockchain.deploy_contract(&user_key, code.clone(), 0.0, 0.0001);
+        blockchain.mine_block();
+        let receipt = blockchain.receipt(id).unwrap();
+        assert_eq!(receipt.status, ExecutionStatus::Success);
+        assert_eq!(receipt.gas_used, contract::deploy_gas(&code));
+        let fee = contract::deploy_gas(&code) as f64 * 0.0001;
+        assert!((blockchain.balance(user) - (before - fee)).abs() < 1e-9);
+
+        // Bytecode acima do máximo é recusado nas pendentes e falha no bloco
+        let mut deploy = Transaction::new(user, "", 0.0);
+        deploy.kind = TransactionKind::Deploy {
+            code: vec![0; contract::MAX_CODE_SIZE + 1],
+            gas_price: 0.0,
+        };
+        assert!(blockchain.check_transaction(&deploy).is_err());
+        let id =
+            blockchain.deploy_contract(&user_key, vec![0; contract::MAX_CODE_SIZE + 1], 0.0, 0.0);
+        blockchain.mine_block();
+        assert!(matches!(
+            blockchain.receipt(id).unwrap().status,
+            ExecutionStatus::Failed(_)
+        ));
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_receipts() {
+        let mut blockchain = Blockchain::new();
+        let (key, owner) = test_account(101);
+
+        let address = contract::contract_address(&owner, blockchain.next_nonce(&owner));
+        blockchain.deploy_contract(&key, contract::tests::counter_code(), 0.0, 0.0);
+        let add_id = blockchain.call_contract(&key, &address, "add", vec![5], 0.0, GAS_LIMIT, 0.0);
+        let fail_id = blockchain.call_contract(&key, &address, "fail", vec![], 0.0, GAS_LIMIT, 0.0);
+        blockchain.mine_block();
+        let pending_id = blockchain.send_transaction(&key, &address, 1.0);
+
+        let add_receipt = blockchain.receipt(add_id).unwrap();
+        assert!(add_receipt.is_success());
+        assert_eq!(add_receipt.block_id, 1);
+        assert!(add_receipt.gas_used > 0);
+        assert_eq!(
+            add_receipt.events,
+            vec![Event {
+                contract: address.clone(),
+                message: owner.clone(),
+            }]
+        );
+
+        let fail_receipt = blockchain.receipt(fail_id).unwrap();
+        assert!(matches!(fail_receipt.status, ExecutionStatus::Failed(_)));
+        // A chamada que falhou altera apenas o nonce do remetente
+        assert_ne!(fail_receipt.state_root, add_receipt.state_root);
+        assert_eq!(
+            blockchain.contract_storage(&address, b"total"),
+            Some(5i64.to_le_bytes().to_vec())
+        );
+        assert_eq!(blockchain.account(&owner).nonce, 3);
+
+        // Transação pendente ainda não possui recibo
+        assert_eq!(blockchain.receipt(pending_id), None);
+
+        let receipts = blockchain.receipts(1);
+        assert_eq!(receipts.len(), 3);
+        assert_eq!(
+            receipt::receipts_root(&receipts),
+            blockchain.chain[1].receipts_root
+        );
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let mut blockchain = Blockchain::new();
+        let receiver = blockchain.subscribe();
+        let (key, _) = test_account(101);
+        for i in 1..=5 {
+            blockchain.send_transaction(
+                &key,
+                "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
                 0.0 + i as f64,
             );
         }
 
-        let result = blockchain.is_chain_valid();
-        assert!(result);
+        let events = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 6);
+        assert!(matches!(
+            events[0],
+            ChainEvent::PendingTransaction { id: 1, .. }
+        ));
+        // A quinta transação completa o bloco, que é enviado depois dela
+        match &events[5] {
+            ChainEvent::NewBlock(block) => assert_eq!(block.transactions.len(), 5),
+            event => panic!("Evento inesperado: {:?}", event),
+        }
+
+        // Assinantes encerrados são removidos
+        drop(receiver);
+        blockchain.mine_block();
+        assert!(blockchain.subscribers.is_empty());
+
+        // Assinante que não consome os eventos é removido com a fila cheia
+        let slow = blockchain.subscribe();
+        for _ in 0..=SUBSCRIBER_CAPACITY {
+            blockchain.mine_block();
+        }
+        assert!(blockchain.subscribers.is_empty());
+        assert_eq!(slow.try_iter().count(), SUBSCRIBER_CAPACITY);
+    }
+
+    #[test]
+    fn test_import_block() {
+        let mut producer = Blockchain::new();
+        let mut importer = Blockchain::new();
+        producer.send_transaction(
+            &test_account(101).0,
+            "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            4.0,
+        );
+        producer.mine_block();
+
+        // Bloco com a raiz dos recibos alterada é recusado sem alterar o estado
+        let mut tampered = producer.chain[1].clone();
+        tampered.receipts_root = receipt::receipts_root(&[]);
+        tampered.hash = tampered.calculate_hash(&Sha256Hasher);
+        assert_eq!(
+            importer.import_block(tampered),
+            Err(String::from("Raiz dos recibos invalida"))
+        );
+        assert_eq!(
+            importer.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            0.0
+        );
+
+        // Raiz do estado diferente da calculada na aplicação do bloco
+        let mut tampered = producer.chain[1].clone();
+        tampered.state_root = importer.state_root();
+        tampered.hash = tampered.calculate_hash(&Sha256Hasher);
+        assert_eq!(
+            importer.import_block(tampered),
+            Err(String::from("Raiz do estado invalida"))
+        );
+
+        // Bloco novo com a codificação antiga da hash depois de um bloco da versão atual
+        let mut legacy = producer.chain[1].clone();
+        legacy.version = 0;
+        legacy.hash = legacy.calculate_hash(&Sha256Hasher);
+        assert_eq!(
+            importer.import_block(legacy),
+            Err(String::from("Versão do bloco inválida: 0"))
+        );
+
+        importer.import_block(producer.chain[1].clone()).unwrap();
+        assert_eq!(importer.height(), 1);
+        assert_eq!(importer.state_root(), producer.chain[1].state_root);
+        assert_eq!(
+            importer.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            4.0
+        );
+        assert_eq!(importer.transaction_counter, 2);
+        assert!(importer.is_chain_valid());
+
+        // O mesmo bloco não pode ser importado duas vezes
+        assert!(importer.import_block(producer.chain[1].clone()).is_err());
+
+        // Bloco que reutiliza o id de uma transação da cadeia é recusado
+        producer.send_transaction(&test_account(101).0, "0x2", 1.0);
+        producer.mine_block();
+        let mut reused = producer.chain[2].clone();
+        let transaction = reused.transactions.remove(&2).unwrap();
+        reused.transactions.insert(1, transaction);
+        reused.hash = reused.calculate_hash(&Sha256Hasher);
+        assert_eq!(
+            importer.import_block(reused),
+            Err(String::from("Id de transação 1 já utilizado na cadeia"))
+        );
+        assert_eq!(importer.height(), 1);
+        assert_eq!(importer.find_transaction(1).unwrap().0.id, 1);
+    }
+
+    #[test]
+    fn test_pending_deduplication() {
+        let mut producer = Blockchain::new();
+        let mut node = Blockchain::new();
+        let (key, from) = test_account(101);
+        let mut transfer = Transaction::new(&from, "0x2", 4.0);
+        wallet::sign_transaction(&key, &mut transfer).unwrap();
+
+        // A mesma transação com outro id não é adicionada novamente
+        assert!(node.add_pending_transaction(1, transfer.clone()));
+        assert!(!node.add_pending_transaction(7, transfer.clone()));
+        assert_eq!(node.pending_transactions.len(), 1);
+
+        // Outra transação com um id em uso recebe um id local
+        let (other_key, other_from) = test_account(102);
+        let mut other = Transaction::new(&other_from, "0x3", 2.0);
+        wallet::sign_transaction(&other_key, &mut other).unwrap();
+        assert!(node.add_pending_transaction(1, other.clone()));
+        assert_eq!(node.pending_transactions[&2].hash(), other.hash());
+
+        // O bloco de outro nó remove as pendentes pelo conteúdo. A pendente com
+        // o id de uma transação do bloco recebe um novo id local
+        producer.send_transaction(&test_account(103).0, "0x4", 1.0);
+        producer.submit_transaction(transfer.clone());
+        producer.mine_block();
+        assert_eq!(producer.chain[1].transactions[&2].hash(), transfer.hash());
+        node.import_block(producer.chain[1].clone()).unwrap();
+        assert_eq!(node.pending_transactions.len(), 1);
+        assert!(node
+            .pending_transactions
+            .values()
+            .all(|pending| pending.hash() == other.hash()));
+        assert!(!node.pending_transactions.contains_key(&2));
+
+        // Transação já incluída na cadeia não volta às pendentes
+        assert!(!node.add_pending_transaction(9, transfer));
+        assert_eq!(node.pending_transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_proof_of_authority() {
+        use crate::consensus::{
+            tests::{public_key, validator_keys},
+            GovernanceAction, ProofOfAuthority,
+        };
+
+        let keys = validator_keys(3);
+        let genesis = Genesis {
+            timestamp: Some(1_700_000_000),
+            consensus: ConsensusParams::Authority {
+                validators: keys[..2].iter().map(public_key).collect(),
+            },
+            ..Genesis::default()
+        };
+        let nodes = keys
+            .iter()
+            .map(|key| {
+                let mut node =
+                    Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+                node.set_validator_key(key.clone());
+                node
+            })
+            .collect::<Vec<_>>();
+        let [mut first, mut second, mut third] = nodes.try_into().unwrap();
+
+        // Votos dos dois validadores incluem o terceiro
+        let action = GovernanceAction::AddValidator {
+            public_key: public_key(&keys[2]),
+        };
+        let mut votes = vec![];
+        for key in &keys[..2] {
+            let mut vote = Transaction::new(&wallet::derive_address(&key.verifying_key()), "", 0.0);
+            vote.kind = TransactionKind::Governance {
+                action: action.clone(),
+            };
+            wallet::sign_transaction(key, &mut vote).unwrap();
+            second.submit_transaction(vote.clone());
+            votes.push(vote);
+        }
+
+        // O bloco 1 pertence ao segundo validador
+        assert!(first.try_mine_block().is_err());
+        assert_eq!(first.height(), 0);
+        second.try_mine_block().unwrap();
+        let block = second.chain[1].clone();
+        assert_eq!(block.producer, public_key(&keys[1]));
+        assert_eq!(second.validators().len(), 3);
+
+        // Selo de outro validador e bloco sem selo são recusados
+        let mut forged = block.clone();
+        ProofOfAuthority::with_key("local", keys[0].clone())
+            .seal(&mut forged)
+            .unwrap();
+        assert_eq!(
+            first.import_block(forged),
+            Err(String::from("Assinatura do produtor invalida"))
+        );
+        let mut unsealed = block.clone();
+        unsealed.set_producer("", &Sha256Hasher);
+        assert_eq!(
+            first.import_block(unsealed),
+            Err(String::from("Produtor fora da sua vez"))
+        );
+
+        first.import_block(block.clone()).unwrap();
+        third.import_block(block).unwrap();
+        assert_eq!(first.validators(), second.validators());
+
+        // O bloco 2 pertence ao validador incluído pela governança
+        assert!(first.try_mine_block().is_err());
+        third.try_mine_block().unwrap();
+        first.import_block(third.chain[2].clone()).unwrap();
+        assert!(first.is_chain_valid());
+
+        // O voto já aplicado não pode ser repetido: o nonce do validador avançou
+        let replay = votes.remove(0);
+        assert_eq!(
+            first.check_transaction(&replay).err(),
+            Some(String::from("Nonce já utilizado"))
+        );
+        let replay_id = first.submit_transaction(replay);
+        first.try_mine_block().unwrap();
+        assert_eq!(
+            first.receipt(replay_id).unwrap().status,
+            ExecutionStatus::Failed(String::from("Nonce inválido, esperado 1"))
+        );
+        assert_eq!(first.validators().len(), 3);
+    }
+
+    #[test]
+    fn test_proof_of_stake() {
+        use crate::consensus::tests::{public_key, validator_keys};
+
+        let keys = validator_keys(2);
+        let validators = keys.iter().map(public_key).collect::<Vec<_>>();
+        let (delegator_key, delegator) = test_account(101);
+        let genesis = Genesis {
+            timestamp: Some(1_700_000_000),
+            alloc: [(delegator.clone(), 10.0)].into(),
+            consensus: ConsensusParams::Stake(StakeParams {
+                validators: validators.iter().map(|key| (key.clone(), 10.0)).collect(),
+                epoch_length: 3,
+                ..StakeParams::default()
+            }),
+            ..Genesis::default()
+        };
+        let mut nodes = keys
+            .iter()
+            .map(|key| {
+                let mut node =
+                    Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+                node.set_validator_key(key.clone());
+                node
+            })
+            .collect::<Vec<_>>();
+
+        // Cada bloco é produzido pelo validador sorteado e importado pelo outro nó
+        let mut bond = Transaction::new(&delegator, "", 10.0);
+        bond.kind = TransactionKind::Bond {
+            validator: validators[0].clone(),
+        };
+        wallet::sign_transaction(&delegator_key, &mut bond).unwrap();
+        nodes[0].submit_transaction(bond.clone());
+        nodes[1].submit_transaction(bond);
+        for _ in 0..6 {
+            let producer = (0..2).find(|i| nodes[*i].try_mine_block().is_ok()).unwrap();
+            let block = nodes[producer].last_block().clone();
+            nodes[1 - producer].import_block(block).unwrap();
+        }
+        assert_eq!(nodes[0].state_root(), nodes[1].state_root());
+        assert_eq!(nodes[0].delegations(&delegator)[&validators[0]], 10.0);
+        let stakes = nodes[0].stake_validators();
+        assert_eq!(stakes[0].stake + stakes[1].stake, 30.0);
+        // A recompensa de cada bloco é criada para os delegadores do produtor
+        let rewards = nodes[0].balance(&delegator)
+            + keys
+                .iter()
+                .map(|key| nodes[0].balance(&wallet::derive_address(&key.verifying_key())))
+                .sum::<f64>();
+        assert_eq!(rewards, 6.0);
+
+        // Bloco fora da vez
+        let id = nodes[0].height() + 1;
+        let expected = stake::slot_producer(nodes[0].storage.as_ref(), id).unwrap();
+        let wrong = validators.iter().position(|key| *key != expected).unwrap();
+        let right = 1 - wrong;
+        assert!(nodes[wrong].try_mine_block().is_err());
+        nodes[right].try_mine_block().unwrap();
+        let block = nodes[right].last_block().clone();
+        let mut forged = block.clone();
+        forged.set_producer(&validators[wrong], &Sha256Hasher);
+        crate::consensus::seal_block(&keys[wrong], "local", &mut forged).unwrap();
+        assert_eq!(
+            nodes[wrong].import_block(forged),
+            Err(String::from("Produtor fora da sua vez"))
+        );
+        nodes[wrong].import_block(block.clone()).unwrap();
+
+        // Assinatura dupla: o nó que recebe o bloco conflitante envia a evidência
+        let mut conflicting = block.clone();
+        conflicting.timestamp += 1;
+        conflicting.set_producer(&validators[right], &Sha256Hasher);
+        let mut other_network = conflicting.clone();
+        crate::consensus::seal_block(&keys[right], "local", &mut conflicting).unwrap();
+        crate::consensus::seal_block(&keys[right], "outra-rede", &mut other_network).unwrap();
+        nodes[wrong].import_block(conflicting.clone()).unwrap();
+        let (evidence_id, evidence) = nodes[wrong]
+            .pending_transactions
+            .iter()
+            .map(|(id, transaction)| (*id, transaction.clone()))
+            .next()
+            .unwrap();
+        assert!(matches!(evidence.kind, TransactionKind::Evidence { .. }));
+        assert!(evidence.is_system());
+        assert_eq!(nodes[right].check_transaction(&evidence), Ok(()));
+        // A evidência é uma transação do sistema: sem remetente nem valor
+        let mut with_sender = evidence.clone();
+        with_sender.from = String::from("0x1");
+        assert_eq!(
+            nodes[right].check_transaction(&with_sender),
+            Err(String::from("Transação do sistema inválida"))
+        );
+        // Cabeçalho assinado em outra rede não é evidência nesta
+        let mut forged = evidence.clone();
+        forged.kind = TransactionKind::Evidence {
+            first: Box::new(block.header()),
+            second: Box::new(other_network.header()),
+        };
+        assert_eq!(
+            nodes[right].check_transaction(&forged),
+            Err(String::from("Assinatura do produtor invalida"))
+        );
+        nodes[right].add_pending_transaction(evidence_id, evidence);
+
+        // O validador punido sai do conjunto, o outro produz os próximos blocos
+        let producer = (0..2).find(|i| nodes[*i].try_mine_block().is_ok()).unwrap();
+        let block = nodes[producer].last_block().clone();
+        nodes[1 - producer].import_block(block).unwrap();
+        assert!(nodes[wrong].receipt(evidence_id).unwrap().is_success());
+        assert_eq!(
+            nodes[wrong].stake_validators()[0].public_key,
+            validators[wrong]
+        );
+        assert_eq!(nodes[wrong].stake_validators().len(), 1);
+        assert!(nodes[right].try_mine_block().is_err());
+        nodes[wrong].try_mine_block().unwrap();
+        assert!(nodes[wrong].is_chain_valid());
+    }
+
+    #[test]
+    fn test_balance_checks() {
+        use crate::consensus::tests::{public_key, validator_keys};
+
+        let keys = validator_keys(1);
+        let validator = public_key(&keys[0]);
+        let (funded_key, funded) = test_account(101);
+        let (attacker_key, attacker) = test_account(102);
+        let (accomplice_key, accomplice) = test_account(103);
+        let genesis = Genesis {
+            alloc: [(funded.clone(), 10.0)].into(),
+            consensus: ConsensusParams::Stake(StakeParams {
+                validators: [(validator.clone(), 10.0)].into(),
+                ..StakeParams::default()
+            }),
+            ..Genesis::default()
+        };
+        let mut blockchain =
+            Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+        blockchain.set_validator_key(keys[0].clone());
+        let signed = |blockchain: &Blockchain, key: &SigningKey, mut transaction: Transaction| {
+            transaction.nonce = blockchain.next_nonce(&transaction.from);
+            wallet::sign_transaction(key, &mut transaction).unwrap();
+            transaction
+        };
+
+        // Transferência sem saldo seguida do vínculo do valor recebido
+        let overdraft = signed(
+            &blockchain,
+            &attacker_key,
+            Transaction::new(&attacker, &accomplice, 1000.0),
+        );
+        assert_eq!(
+            blockchain.check_transaction(&overdraft),
+            Err(String::from(
+                "Saldo insuficiente: 0 disponível, 1000 necessário"
+            ))
+        );
+        let overdraft_id = blockchain.submit_transaction(overdraft);
+        let mut bond = Transaction::new(&accomplice, "", 1000.0);
+        bond.kind = TransactionKind::Bond {
+            validator: validator.clone(),
+        };
+        let bond = signed(&blockchain, &accomplice_key, bond);
+        assert!(blockchain.check_transaction(&bond).is_err());
+        let bond_id = blockchain.submit_transaction(bond);
+        blockchain.try_mine_block().unwrap();
+        assert!(!blockchain.receipt(overdraft_id).unwrap().is_success());
+        assert!(!blockchain.receipt(bond_id).unwrap().is_success());
+        assert_eq!(blockchain.balance(&attacker), 0.0);
+        assert_eq!(blockchain.balance(&accomplice), 0.0);
+        assert!(blockchain.delegations(&accomplice).is_empty());
+        assert_eq!(blockchain.stake_validators()[0].stake, 10.0);
+
+        // Valor negativo creditaria o remetente
+        let negative = signed(
+            &blockchain,
+            &attacker_key,
+            Transaction::new(&attacker, &funded, -5.0),
+        );
+        assert_eq!(
+            blockchain.check_transaction(&negative),
+            Err(String::from("Valor da transação inválido: -5"))
+        );
+
+        // O saldo precisa cobrir as transações pendentes do remetente e as taxas
+        blockchain.send_transaction(&funded_key, &attacker, 6.0);
+        let second = signed(
+            &blockchain,
+            &funded_key,
+            Transaction::new(&funded, &attacker, 6.0),
+        );
+        assert!(blockchain.check_transaction(&second).is_err());
+        let mut call = Transaction::new(&funded, &accomplice, 0.0);
+        call.kind = TransactionKind::Call {
+            function: String::from("add"),
+            args: vec![],
+            gas_limit: GAS_LIMIT,
+            gas_price: 1.0,
+        };
+        assert!(blockchain
+            .check_transaction(&signed(&blockchain, &funded_key, call))
+            .is_err());
+        let fits = signed(
+            &blockchain,
+            &funded_key,
+            Transaction::new(&funded, &attacker, 4.0),
+        );
+        assert_eq!(blockchain.check_transaction(&fits), Ok(()));
+        blockchain.submit_transaction(fits);
+        blockchain.try_mine_block().unwrap();
+        assert_eq!(blockchain.balance(&funded), 0.0);
+        assert_eq!(blockchain.balance(&attacker), 10.0);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_finality() {
+        use crate::consensus::tests::{public_key, validator_keys};
+
+        let keys = validator_keys(3);
+        let genesis = Genesis {
+            timestamp: Some(1_700_000_000),
+            consensus: ConsensusParams::Authority {
+                validators: keys.iter().map(public_key).collect(),
+            },
+            checkpoint_interval: 2,
+            ..Genesis::default()
+        };
+        let mut nodes = keys
+            .iter()
+            .map(|key| {
+                let mut node =
+                    Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+                node.set_validator_key(key.clone());
+                node
+            })
+            .collect::<Vec<_>>();
+        let transaction_id = nodes[1].send_transaction(&test_account(101).0, "0x2", 4.0);
+
+        // Blocos 1 e 2 produzidos na vez de cada validador, o bloco 2 é checkpoint
+        for _ in 0..2 {
+            let producer = (0..3).find(|i| nodes[*i].try_mine_block().is_ok()).unwrap();
+            let block = nodes[producer].last_block().clone();
+            for (i, node) in nodes.iter_mut().enumerate() {
+                if i != producer {
+                    node.import_block(block.clone()).unwrap();
+                }
+            }
+        }
+        let fork = nodes[0].chain[1].clone();
+        let votes = nodes
+            .iter_mut()
+            .map(|node| node.take_precommits())
+            .collect::<Vec<_>>();
+        assert!(votes.iter().all(|votes| votes.len() == 1));
+        assert_eq!(nodes[0].finalized().id, 0);
+
+        // A altura assinada fica gravada: nenhuma outra hash é assinada nela
+        let signer = public_key(&keys[1]);
+        assert_eq!(
+            finality::last_signed(nodes[1].storage.as_ref(), &signer),
+            Some(2)
+        );
+        let hash = nodes[1].chain[2].hash;
+        nodes[1].chain[2].hash = Hash([9; 32]);
+        nodes[1].precommit_checkpoint();
+        assert!(nodes[1].take_precommits().is_empty());
+        nodes[1].chain[2].hash = hash;
+
+        // Pré-commits de checkpoints ainda não recebidos ficam guardados
+        let mut late = Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+        for vote in &votes {
+            assert_eq!(late.add_precommit(vote[0].clone()), Ok(false));
+        }
+        assert_eq!(
+            late.add_precommit(votes[0][0].clone()),
+            Err(String::from("Pré-commit repetido"))
+        );
+        let distant = Precommit::sign(
+            &keys[0],
+            &Checkpoint {
+                id: 6,
+                hash: Hash([1; 32]),
+            },
+        );
+        assert_eq!(
+            late.add_precommit(distant),
+            Err(String::from("Checkpoint desconhecido"))
+        );
+        for id in 1..=2 {
+            late.import_block(nodes[0].chain[id].clone()).unwrap();
+        }
+        assert_eq!(late.finalized().id, 2);
+
+        // Pré-commits inválidos
+        let mut wrong_hash = votes[1][0].clone();
+        wrong_hash.block_hash = fork.hash;
+        assert!(nodes[0].add_precommit(wrong_hash).is_err());
+        let outsider = Precommit::sign(
+            &validator_keys(4)[3],
+            &Checkpoint {
+                id: 2,
+                hash: nodes[0].chain[2].hash,
+            },
+        );
+        assert_eq!(
+            nodes[0].add_precommit(outsider),
+            Err(String::from("Pré-commit de quem não é validador"))
+        );
+        assert!(nodes[0].add_precommit(votes[0][0].clone()).is_err());
+
+        // Com 2 de 3 votos o checkpoint ainda não é final, com 3 de 3 é
+        let mut alternative = fork;
+        alternative.timestamp += 1;
+        alternative.set_producer("", &Sha256Hasher);
+        nodes[0].side_blocks.insert(alternative.clone());
+        assert_eq!(nodes[0].add_precommit(votes[1][0].clone()), Ok(false));
+
+        // Segundo voto do validador em outra hash da mesma altura
+        let double = Precommit::sign(
+            &keys[1],
+            &Checkpoint {
+                id: 2,
+                hash: alternative.hash,
+            },
+        );
+        assert_eq!(
+            nodes[0].add_precommit(double),
+            Err(String::from("Pré-commit duplo do validador na altura 2"))
+        );
+
+        assert_eq!(nodes[0].add_precommit(votes[2][0].clone()), Ok(true));
+        assert_eq!(nodes[0].finalized().id, 2);
+        // Os ramos laterais até o checkpoint finalizado são descartados
+        assert!(nodes[0].side_blocks().is_empty());
+
+        // Ramos que desfazem blocos finalizados são recusados
+        assert_eq!(
+            nodes[0].import_block(alternative),
+            Err(String::from("Bloco abaixo do checkpoint finalizado"))
+        );
+
+        // Os corpos dos blocos finalizados não são checados novamente
+        nodes[0].corrupt_block(1, transaction_id, 40.0).unwrap();
+        assert!(nodes[0].is_chain_valid());
+        nodes[0].chain[2].hash = nodes[0].chain[1].hash;
+        assert!(!nodes[0].is_chain_valid());
     }
+
+    #[test]
+    fn test_state_proofs() {
+        let mut blockchain = Blockchain::new();
+        let (key, _) = test_account(101);
+        blockchain.send_transaction(&key, "0x2", 4.0);
+        blockchain.mine_block();
+        blockchain.send_transaction(&key, "0x2", 1.0);
+        blockchain.mine_block();
+        assert_eq!(blockchain.last_block().state_root, blockchain.state_root());
+
+        // Cliente leve: apenas os cabeçalhos e as provas
+        let headers = blockchain
+            .chain
+            .iter()
+            .map(|block| block.header())
+            .collect::<Vec<_>>();
+        for (block_id, balance) in [(0, 0.0), (1, 4.0), (2, 5.0)] {
+            let proof = blockchain.account_proof("0x2", block_id).unwrap();
+            let root = &headers[block_id as usize].state_root;
+            let account = state::verify_account_proof(root, &proof).unwrap();
+            assert_eq!(account.balance, balance);
+        }
+        let proof = blockchain.account_proof("0x2", 1).unwrap();
+        assert!(state::verify_account_proof(&headers[2].state_root, &proof).is_err());
+        assert!(blockchain.account_proof("0x2", 3).is_err());
+    }
+
+    /* Duas cadeias com o mesmo genesis: `main` com 1 bloco e `fork` com 2 blocos.
+     ** As transações dos dois ramos são de remetentes diferentes
+     */
+    fn forked_chains() -> (Blockchain, Blockchain) {
+        let mut main = Blockchain::new();
+        main.send_transaction(
+            &test_account(101).0,
+            "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            4.0,
+        );
+        main.mine_block();
+
+        let mut fork = Blockchain::new();
+        let (key, from) = test_account(102);
+        let mut transaction =
+            Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 10.0);
+        wallet::sign_transaction(&key, &mut transaction).unwrap();
+        fork.add_pending_transaction(10, transaction);
+        fork.mine_block();
+        fork.mine_block();
+        (main, fork)
+    }
+
+    #[test]
+    fn test_reorganization() {
+        let (mut main, fork) = forked_chains();
+        let orphaned_hash = main.chain[1].hash;
+        let events = main.subscribe();
+
+        // Ramo do mesmo tamanho da cadeia, a cadeia atual é mantida
+        main.import_block(fork.chain[1].clone()).unwrap();
+        assert_eq!(main.last_block().hash, orphaned_hash);
+        assert_eq!(main.side_blocks().len(), 1);
+
+        // Ramo mais longo, a cadeia é reorganizada
+        main.import_block(fork.chain[2].clone()).unwrap();
+        assert_eq!(main.height(), 2);
+        assert_eq!(main.last_block().hash, fork.last_block().hash);
+        assert_eq!(
+            main.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            10.0
+        );
+        assert!(main.receipt(10).is_some());
+        assert!(main.is_chain_valid());
+
+        // O bloco órfão fica na árvore e sua transação volta a ficar pendente
+        assert!(main.side_blocks().contains(&orphaned_hash));
+        assert!(main.receipt(1).is_none());
+        assert!(main.pending_transactions.contains_key(&1));
+        // Os assinantes recebem os blocos do novo ramo e a transação que voltou às pendentes
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events.last(),
+            Some(ChainEvent::PendingTransaction { id: 1, .. })
+        ));
+
+        main.mine_block();
+        assert_eq!(
+            main.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            14.0
+        );
+    }
+
+    #[test]
+    fn test_indexes_after_reorganization() {
+        let (mut main, fork) = forked_chains();
+        let address = "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962";
+        let orphaned_hash = main.chain[1].hash;
+        assert_eq!(main.block_by_hash(&orphaned_hash).unwrap().id, 1);
+        assert_eq!(main.find_transaction(1).unwrap().0.id, 1);
+        assert_eq!(main.history(address).len(), 1);
+
+        main.import_block(fork.chain[1].clone()).unwrap();
+        main.import_block(fork.chain[2].clone()).unwrap();
+
+        // Os índices acompanham a cadeia canônica depois da reorganização
+        assert!(main.block_by_hash(&orphaned_hash).is_none());
+        assert_eq!(main.block_by_hash(&fork.chain[2].hash).unwrap().id, 2);
+        assert!(main.find_transaction(1).is_none());
+        let (block, transaction) = main.find_transaction(10).unwrap();
+        assert_eq!((block.hash, transaction.value), (fork.chain[1].hash, 10.0));
+        assert_eq!(
+            main.history(address)
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            vec![10]
+        );
+
+        // A transação órfã volta a cadeia no próximo bloco
+        main.mine_block();
+        assert_eq!(main.find_transaction(1).unwrap().0.id, 3);
+        assert_eq!(main.history(address).len(), 2);
+        assert!(main.history("0x3").is_empty());
+    }
+
+    #[test]
+    fn test_account_history() {
+        let mut blockchain = Blockchain::new();
+        let (key, sender) = test_account(101);
+        let (recipient_key, recipient) = test_account(102);
+        let recipient = recipient.as_str();
+        for value in 1..=7 {
+            blockchain.send_transaction(&key, recipient, value as f64);
+        }
+        blockchain.send_transaction(&recipient_key, "0x3", 10.0);
+        blockchain.mine_block();
+        assert_eq!(blockchain.height(), 2);
+
+        // Saldo em cada altura, a partir das raízes do estado dos blocos
+        assert_eq!(blockchain.balance_at(recipient, 0), Ok(0.0));
+        assert_eq!(blockchain.balance_at(recipient, 1), Ok(15.0));
+        assert_eq!(blockchain.balance_at(recipient, 2), Ok(18.0));
+        assert_eq!(
+            blockchain.balance_at(recipient, 2),
+            Ok(blockchain.balance(recipient))
+        );
+        assert!(blockchain.balance_at(recipient, 3).is_err());
+
+        // Páginas de 3 transferências, a última página não tem cursor
+        let mut pages = vec![];
+        let mut cursor = None;
+        loop {
+            let page = blockchain.history_page(recipient, cursor, 3);
+            pages.push(page.transfers);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(
+            pages.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 3, 2]
+        );
+        let last = &pages[2][1];
+        assert_eq!(last.direction, index::Direction::Outgoing);
+        assert_eq!((last.block_id, last.counterparty.as_str()), (2, "0x3"));
+        assert_eq!(pages[0][0].direction, index::Direction::Incoming);
+        assert!(blockchain.history_page("0x4", None, 3).transfers.is_empty());
+
+        // Diretório de dados com os índices no formato anterior: o histórico era
+        // uma lista por endereço e não havia versão, os índices são reconstruídos
+        let ids = index::address_transactions(blockchain.storage.as_ref(), recipient);
+        let mut storage =
+            std::mem::replace(&mut blockchain.storage, Box::new(MemoryStorage::new()));
+        save(
+            storage.as_mut(),
+            &format!("index/address/{}", recipient),
+            &ids,
+        );
+        storage.remove(format!("index/address/{}/0", recipient).as_bytes());
+        storage.remove(b"index/version");
+        let mut reloaded = Blockchain::with_storage(storage).unwrap();
+        assert_eq!(ids.len(), 8);
+        assert_eq!(
+            index::address_transactions(reloaded.storage.as_ref(), recipient),
+            ids
+        );
+        assert_eq!(
+            reloaded.history_page(recipient, None, 3).transfers,
+            pages[0]
+        );
+
+        // Transferência que falhou fica no índice mas não no histórico de transferências
+        let mut failed = Transaction::new(&sender, recipient, 50.0);
+        failed.nonce = 9;
+        wallet::sign_transaction(&key, &mut failed).unwrap();
+        let failed_id = reloaded.submit_transaction(failed);
+        reloaded.mine_block();
+        assert!(!reloaded.receipt(failed_id).unwrap().is_success());
+        assert_eq!(reloaded.balance(recipient), 18.0);
+        assert_eq!(
+            index::address_count(reloaded.storage.as_ref(), recipient),
+            9
+        );
+        let page = reloaded.history_page(recipient, Some(6), 3);
+        assert_eq!(page.transfers, pages[2]);
+        assert_eq!(page.next_cursor, None);
+        let first = reloaded.history_page(recipient, None, 8);
+        assert_eq!(first.transfers.len(), 8);
+        assert!(first
+            .transfers
+            .iter()
+            .all(|t| t.transaction_id != failed_id));
+    }
+
+    #[test]
+    fn test_invalid_branch() {
+        let (mut main, fork) = forked_chains();
+        let hash = main.last_block().hash;
+
+        assert_eq!(
+            main.import_block(fork.chain[2].clone()),
+            Err(String::from("Bloco anterior desconhecido"))
+        );
+
+        // O bloco inválido só é executado na reorganização, a cadeia anterior é restaurada
+        let mut tampered = fork.chain[2].clone();
+        tampered.receipts_root = fork.chain[1].receipts_root.clone();
+        tampered.hash = tampered.calculate_hash(&Sha256Hasher);
+        main.import_block(fork.chain[1].clone()).unwrap();
+        assert_eq!(
+            main.import_block(tampered.clone()),
+            Err(String::from("Raiz dos recibos invalida"))
+        );
+        assert_eq!(main.height(), 1);
+        assert_eq!(main.last_block().hash, hash);
+        assert_eq!(
+            main.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            4.0
+        );
+        assert!(main.side_blocks().contains(&fork.chain[1].hash));
+        assert!(!main.side_blocks().contains(&tampered.hash));
+        assert!(main.pending_transactions.is_empty());
+        assert!(main.is_chain_valid());
+    }
+
     #[test]
     fn test_is_chain_not_valid() {
         let mut blockchain = Blockchain::new();
@@ -363,7 +3259,7 @@ mod tests {
         }
 
         //Alterar um bloco na blockchain
-        blockchain.corrupt_block(2, 6, 2.53722);
+        blockchain.corrupt_block(2, 6, 2.53722).unwrap();
 
         //resultado deve retorna falso, bloco de id 2 foi corrompido
         let result = blockchain.is_chain_valid();
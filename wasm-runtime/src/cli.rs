@@ -0,0 +1,1522 @@
+/* Mod Cli
+** - Interface de linha de comando do nó, operando sobre um diretório de dados
+**   (`--data-dir`, padrão `data`): a cadeia fica gravada em `chain.db` (FileStorage)
+**   e as chaves da carteira no keystore `keystore/` (mod wallet)
+** - Subcomandos:
+**    - init [--genesis arquivo]: cria a cadeia a partir do genesis
+**    - node run: inicia o nó (rede, RPC, WebSocket e o explorador de blocos
+**      `--explorer`), sincroniza com os peers e
+**      minera as transações pendentes a cada `--block-time` segundos. Na prova
+**      de autoridade ou de participação `--validator` desbloqueia a chave que
+**      sela os blocos da vez do nó
+**    - tx send: envia uma transferência ao nó em execução (`--rpc`) ou a inclui
+**      em um novo bloco da cadeia local. A transação é assinada com a conta do
+**      remetente no keystore e recebe o próximo nonce da
+**      conta. No modo UTXO a transação gasta as saídas do remetente, com troco
+**    - block show <id ou hash>
+**    - chain validate, chain export <arquivo> e chain import <arquivo>
+**    - wallet new e wallet list
+**    - wallet show <endereço>: exibe a chave pública da conta
+**    - wallet mnemonic: gera uma frase de recuperação (mod hd)
+**    - wallet recover --mnemonic [--count]: importa as contas
+**      derivadas da frase no keystore
+**    - multisig address e multisig register: endereço e registro da conta M-de-N
+**    - multisig propose, sign, combine e submit: a transação e as assinaturas
+**      parciais são trocadas em arquivos JSON, cada dono assina offline
+**    - validator list: validadores atuais da prova de autoridade
+**    - validator add e validator remove <chave pública>: voto de governança
+**      assinado pelo validador `--from` do keystore
+**    - stake bond e stake unbond --validator <chave pública>: vínculo e
+**      desvínculo da participação (prova de participação)
+**    - stake validators e stake delegations <endereço>
+** - A senha das contas do keystore vem do arquivo `--password-file`, da variável
+**   WASM_RUNTIME_PASSWORD ou do terminal, nunca de um argumento
+** - Códigos de saída: 0 sucesso (cadeia válida), 1 erro, 2 cadeia ou bloco inválido
+*/
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    consensus::GovernanceAction,
+    explorer::{ExplorerConfig, ExplorerServer},
+    genesis::{Genesis, LedgerMode},
+    hash::Hash,
+    hd::{self, HdWallet},
+    logger::{self, LevelFilter},
+    multisig::{self, MultisigAccount},
+    network::{Node, NodeConfig},
+    rpc::{RpcConfig, RpcServer},
+    stake::ValidatorStake,
+    storage::FileStorage,
+    transaction::{Transaction, TransactionKind, TransactionSignature},
+    utxo::{self, OutPoint, TxOutput},
+    wallet::{self, KdfParams, Keystore},
+    warn,
+    ws::{WsConfig, WsServer},
+};
+use clap::{Args, Parser, Subcommand};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_INVALID: i32 = 2;
+
+const CHAIN_FILE: &str = "chain.db";
+const KEYSTORE_DIR: &str = "keystore";
+// Variável de ambiente com a senha das contas do keystore
+const PASSWORD_ENV: &str = "WASM_RUNTIME_PASSWORD";
+
+// Custo do scrypt do keystore, reduzido nos testes
+#[cfg(not(test))]
+const KDF_PARAMS: KdfParams = KdfParams {
+    log_n: 15,
+    r: 8,
+    p: 1,
+};
+#[cfg(test)]
+const KDF_PARAMS: KdfParams = crate::wallet::tests::TEST_PARAMS;
+
+#[derive(Debug, Parser)]
+#[command(name = "wasm-runtime", about = "Nó e ferramentas da blockchain")]
+pub struct Cli {
+    /// Diretório de dados da cadeia e da carteira
+    #[arg(long, global = true, default_value = "data")]
+    data_dir: PathBuf,
+    /// Exibe os logs de nível info
+    #[arg(long, global = true)]
+    verbose: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Cria a cadeia no diretório de dados
+    Init {
+        /// Arquivo JSON com os parâmetros do genesis
+        #[arg(long)]
+        genesis: Option<PathBuf>,
+    },
+    /// Comandos do nó
+    #[command(subcommand)]
+    Node(NodeCommand),
+    /// Comandos de transações
+    #[command(subcommand)]
+    Tx(TxCommand),
+    /// Comandos de blocos
+    #[command(subcommand)]
+    Block(BlockCommand),
+    /// Comandos da cadeia
+    #[command(subcommand)]
+    Chain(ChainCommand),
+    /// Comandos da carteira
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Comandos de contas multisig
+    #[command(subcommand)]
+    Multisig(MultisigCommand),
+    /// Comandos dos validadores da prova de autoridade
+    #[command(subcommand)]
+    Validator(ValidatorCommand),
+    /// Comandos da prova de participação
+    #[command(subcommand)]
+    Stake(StakeCommand),
+}
+
+/* Senha das contas do keystore, lida do arquivo `--password-file`, da variável
+** WASM_RUNTIME_PASSWORD ou do terminal, nessa ordem. A senha não é aceita como
+** argumento, que fica visível na lista de processos e no histórico do shell
+*/
+#[derive(Debug, Args)]
+struct PasswordArgs {
+    /// Arquivo com a senha da conta do keystore
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+}
+
+impl PasswordArgs {
+    fn read(&self, prompt: &str) -> Result<String, String> {
+        if let Some(path) = &self.password_file {
+            let password = fs::read_to_string(path)
+                .map_err(|erro| format!("Erro ao ler {}: {}", path.display(), erro))?;
+            return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+        }
+        if let Ok(password) = env::var(PASSWORD_ENV) {
+            return Ok(password);
+        }
+        rpassword::prompt_password(prompt).map_err(|erro| format!("Erro ao ler a senha: {}", erro))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum NodeCommand {
+    /// Inicia o nó
+    Run {
+        /// Endereço de escuta da rede peer-to-peer
+        #[arg(long, default_value = "127.0.0.1:30333")]
+        listen: SocketAddr,
+        /// Peers para conectar ao iniciar
+        #[arg(long)]
+        peer: Vec<SocketAddr>,
+        /// Endereço do servidor JSON-RPC
+        #[arg(long, default_value = "127.0.0.1:8545")]
+        rpc: SocketAddr,
+        /// Endereço do servidor WebSocket
+        #[arg(long, default_value = "127.0.0.1:8546")]
+        ws: SocketAddr,
+        /// Endereço do explorador de blocos (HTML)
+        #[arg(long, default_value = "127.0.0.1:8547")]
+        explorer: SocketAddr,
+        /// Intervalo em segundos entre os blocos minerados
+        #[arg(long, default_value_t = 10)]
+        block_time: u64,
+        /// Conta do keystore que sela os blocos (prova de autoridade ou participação)
+        #[arg(long)]
+        validator: Option<String>,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TxCommand {
+    /// Envia uma transferência
+    Send {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        value: f64,
+        /// Envia ao nó em execução pelo JSON-RPC em vez da cadeia local
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum BlockCommand {
+    /// Exibe um bloco pelo id ou pela hash
+    Show { id: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ChainCommand {
+    /// Checa a integridade da cadeia
+    Validate,
+    /// Exporta o genesis e os blocos para um arquivo JSON
+    Export { file: PathBuf },
+    /// Importa os blocos de um arquivo exportado
+    Import { file: PathBuf },
+}
+
+#[derive(Debug, Subcommand)]
+enum WalletCommand {
+    /// Cria uma nova conta no keystore
+    New {
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+    /// Lista as contas do keystore
+    List,
+    /// Exibe a chave pública de uma conta do keystore
+    Show { address: String },
+    /// Gera uma nova frase mnemônica para recuperação das contas
+    Mnemonic {
+        /// Quantidade de palavras (12, 15, 18, 21 ou 24)
+        #[arg(long, default_value_t = 24)]
+        words: usize,
+    },
+    /// Recupera as contas derivadas de uma frase mnemônica
+    Recover {
+        /// Frase mnemônica (BIP-39)
+        #[arg(long)]
+        mnemonic: String,
+        /// Senha opcional da frase (BIP-39)
+        #[arg(long, default_value = "")]
+        passphrase: String,
+        /// Quantidade de contas derivadas
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum MultisigCommand {
+    /// Exibe o endereço da conta M-de-N
+    Address {
+        /// Quantidade mínima de assinaturas (M)
+        #[arg(long)]
+        threshold: u32,
+        /// Chaves públicas dos donos (N)
+        #[arg(long = "public-key", required = true)]
+        public_keys: Vec<String>,
+    },
+    /// Registra a conta M-de-N na cadeia, transferindo `value` de `from`
+    Register {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        threshold: u32,
+        #[arg(long = "public-key", required = true)]
+        public_keys: Vec<String>,
+        #[arg(long, default_value_t = 0.0)]
+        value: f64,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+    /// Cria o arquivo da transação da conta multisig, ainda sem assinaturas
+    Propose {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        value: f64,
+        /// Consulta o nonce da conta no nó em execução em vez da cadeia local
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Assina a transação do arquivo com uma conta do keystore (assinatura parcial)
+    Sign {
+        file: PathBuf,
+        #[arg(long)]
+        signer: String,
+        #[command(flatten)]
+        password: PasswordArgs,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Adiciona as assinaturas parciais à transação do arquivo
+    Combine {
+        file: PathBuf,
+        #[arg(long = "signature", required = true)]
+        signatures: Vec<PathBuf>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Envia a transação assinada do arquivo
+    Submit {
+        file: PathBuf,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ValidatorCommand {
+    /// Lista os validadores atuais, em ordem de vez
+    List {
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+    },
+    /// Vota na inclusão do validador com a chave pública informada
+    Add {
+        public_key: String,
+        /// Conta do keystore do validador que vota
+        #[arg(long)]
+        from: String,
+        #[command(flatten)]
+        password: PasswordArgs,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+    },
+    /// Vota na remoção do validador com a chave pública informada
+    Remove {
+        public_key: String,
+        #[arg(long)]
+        from: String,
+        #[command(flatten)]
+        password: PasswordArgs,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum StakeCommand {
+    /// Vincula `value` do saldo de `from` ao validador
+    Bond {
+        #[arg(long)]
+        from: String,
+        /// Chave pública do validador
+        #[arg(long)]
+        validator: String,
+        #[arg(long)]
+        value: f64,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+    /// Desvincula `value` do validador, devolvido depois do período de desvínculo
+    Unbond {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        validator: String,
+        #[arg(long)]
+        value: f64,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+        #[command(flatten)]
+        password: PasswordArgs,
+    },
+    /// Lista a participação atual dos validadores
+    Validators {
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+    },
+    /// Lista o valor vinculado pelo endereço a cada validador
+    Delegations {
+        address: String,
+        #[arg(long)]
+        rpc: Option<SocketAddr>,
+    },
+}
+
+// Arquivo gerado por `chain export`
+#[derive(Debug, Serialize, Deserialize)]
+struct ChainExport {
+    genesis: Genesis,
+    blocks: Vec<Block>,
+}
+
+// Executa a linha de comando e retorna o código de saída
+pub fn run<I, T>(args: I) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(erro) => {
+            let _ = erro.print();
+            return if erro.use_stderr() {
+                EXIT_ERROR
+            } else {
+                EXIT_OK
+            };
+        }
+    };
+    let level = if cli.verbose || matches!(cli.command, Command::Node(_)) {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Warn
+    };
+    logger::set_max_level(level);
+
+    match execute(&cli) {
+        Ok(code) => code,
+        Err(erro) => {
+            eprintln!("Erro: {}", erro);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn execute(cli: &Cli) -> Result<i32, String> {
+    let data_dir = cli.data_dir.as_path();
+    match &cli.command {
+        Command::Init { genesis } => {
+            let genesis = match genesis {
+                Some(path) => Genesis::from_file(path)?,
+                None => Genesis::default(),
+            };
+            let blockchain = init_chain(data_dir, &genesis)?;
+            println!(
+                "Cadeia {} criada em {}, genesis {}",
+                genesis.chain_id,
+                data_dir.display(),
+                blockchain.genesis_hash()
+            );
+            Ok(EXIT_OK)
+        }
+        Command::Node(NodeCommand::Run {
+            listen,
+            peer,
+            rpc,
+            ws,
+            explorer,
+            block_time,
+            validator,
+            password,
+        }) => {
+            let validator = match validator {
+                Some(address) => {
+                    let password = password.read("Senha do validador: ")?;
+                    Some(keystore(data_dir).unlock(address, &password)?)
+                }
+                None => None,
+            };
+            run_node(
+                data_dir,
+                *listen,
+                peer,
+                *rpc,
+                *ws,
+                *explorer,
+                *block_time,
+                validator,
+            )
+        }
+        Command::Tx(TxCommand::Send {
+            from,
+            to,
+            value,
+            rpc,
+            password,
+        }) => {
+            let mut transaction = build_transfer(data_dir, from, to, *value, *rpc)?;
+            set_nonce(data_dir, &mut transaction, *rpc)?;
+            sign_with_keystore(data_dir, &mut transaction, password)?;
+            let id = send_transaction(data_dir, transaction, *rpc)?;
+            println!("Transação {} enviada", id);
+            Ok(EXIT_OK)
+        }
+        Command::Block(BlockCommand::Show { id }) => {
+            let blockchain = open_chain(data_dir)?;
+            let block = match id.parse::<u64>() {
+                Ok(id) => blockchain.block_by_id(id),
+                Err(_) => id
+                    .parse::<Hash>()
+                    .ok()
+                    .and_then(|hash| blockchain.block_by_hash(&hash)),
+            }
+            .ok_or_else(|| format!("Bloco {} não encontrado", id))?;
+            println!("{}", to_json(block)?);
+            Ok(EXIT_OK)
+        }
+        Command::Chain(ChainCommand::Validate) => {
+            let blockchain = open_chain(data_dir)?;
+            if blockchain.is_chain_valid() {
+                println!("Cadeia válida, altura {}", blockchain.height());
+                Ok(EXIT_OK)
+            } else {
+                println!("Cadeia inválida");
+                Ok(EXIT_INVALID)
+            }
+        }
+        Command::Chain(ChainCommand::Export { file }) => {
+            let blockchain = open_chain(data_dir)?;
+            let export = ChainExport {
+                genesis: blockchain.genesis(),
+                blocks: blockchain.chain[1..].to_vec(),
+            };
+            fs::write(file, to_json(&export)?).map_err(|erro| erro.to_string())?;
+            println!("{} blocos exportados", export.blocks.len());
+            Ok(EXIT_OK)
+        }
+        Command::Chain(ChainCommand::Import { file }) => import_chain(data_dir, file),
+        Command::Wallet(WalletCommand::New { password }) => {
+            let address = keystore(data_dir).create(&password.read("Senha da nova conta: ")?)?;
+            println!("{}", address);
+            Ok(EXIT_OK)
+        }
+        Command::Wallet(WalletCommand::List) => {
+            for address in keystore(data_dir).list()? {
+                println!("{}", address);
+            }
+            Ok(EXIT_OK)
+        }
+        Command::Wallet(WalletCommand::Show { address }) => {
+            println!("{}", keystore(data_dir).public_key(address)?);
+            Ok(EXIT_OK)
+        }
+        Command::Wallet(WalletCommand::Mnemonic { words }) => {
+            println!("{}", hd::generate_mnemonic(*words)?);
+            Ok(EXIT_OK)
+        }
+        Command::Wallet(WalletCommand::Recover {
+            mnemonic,
+            passphrase,
+            count,
+            password,
+        }) => {
+            let wallet = HdWallet::from_mnemonic(mnemonic, passphrase)?;
+            let password = password.read("Senha das contas: ")?;
+            let keystore = keystore(data_dir);
+            for account in 0..*count {
+                let address = keystore.import(&wallet.account(account), &password)?;
+                println!("{}", address);
+            }
+            Ok(EXIT_OK)
+        }
+        Command::Multisig(command) => execute_multisig(data_dir, command),
+        Command::Validator(command) => execute_validator(data_dir, command),
+        Command::Stake(command) => execute_stake(data_dir, command),
+    }
+}
+
+fn execute_stake(data_dir: &Path, command: &StakeCommand) -> Result<i32, String> {
+    let (mut transaction, password, rpc) = match command {
+        StakeCommand::Bond {
+            from,
+            validator,
+            value,
+            rpc,
+            password,
+        } => {
+            let mut transaction = Transaction::new(from, "", *value);
+            transaction.kind = TransactionKind::Bond {
+                validator: validator.to_lowercase(),
+            };
+            (transaction, password, rpc)
+        }
+        StakeCommand::Unbond {
+            from,
+            validator,
+            value,
+            rpc,
+            password,
+        } => {
+            let mut transaction = Transaction::new(from, "", 0.0);
+            transaction.kind = TransactionKind::Unbond {
+                validator: validator.to_lowercase(),
+                amount: *value,
+            };
+            (transaction, password, rpc)
+        }
+        StakeCommand::Validators { rpc } => {
+            let validators = match rpc {
+                Some(addr) => serde_json::from_value::<Vec<ValidatorStake>>(rpc_call(
+                    *addr,
+                    "stake_getValidators",
+                    json!([]),
+                )?)
+                .map_err(|erro| erro.to_string())?,
+                None => open_chain(data_dir)?.stake_validators(),
+            };
+            for validator in validators {
+                println!("{} {}", validator.public_key, validator.stake);
+            }
+            return Ok(EXIT_OK);
+        }
+        StakeCommand::Delegations { address, rpc } => {
+            let delegations = match rpc {
+                Some(addr) => serde_json::from_value::<BTreeMap<String, f64>>(rpc_call(
+                    *addr,
+                    "stake_getDelegations",
+                    json!([address]),
+                )?)
+                .map_err(|erro| erro.to_string())?,
+                None => open_chain(data_dir)?.delegations(address),
+            };
+            for (validator, value) in delegations {
+                println!("{} {}", validator, value);
+            }
+            return Ok(EXIT_OK);
+        }
+    };
+    set_nonce(data_dir, &mut transaction, *rpc)?;
+    sign_with_keystore(data_dir, &mut transaction, password)?;
+    let id = send_transaction(data_dir, transaction, *rpc)?;
+    println!("Transação {} enviada", id);
+    Ok(EXIT_OK)
+}
+
+fn execute_validator(data_dir: &Path, command: &ValidatorCommand) -> Result<i32, String> {
+    let (action, from, password, rpc) = match command {
+        ValidatorCommand::List { rpc } => {
+            let validators = match rpc {
+                Some(addr) => serde_json::from_value::<Vec<String>>(rpc_call(
+                    *addr,
+                    "consensus_validators",
+                    json!([]),
+                )?)
+                .map_err(|erro| erro.to_string())?,
+                None => open_chain(data_dir)?.validators(),
+            };
+            for validator in validators {
+                println!("{}", validator);
+            }
+            return Ok(EXIT_OK);
+        }
+        ValidatorCommand::Add {
+            public_key,
+            from,
+            password,
+            rpc,
+        } => (
+            GovernanceAction::AddValidator {
+                public_key: public_key.to_lowercase(),
+            },
+            from,
+            password,
+            rpc,
+        ),
+        ValidatorCommand::Remove {
+            public_key,
+            from,
+            password,
+            rpc,
+        } => (
+            GovernanceAction::RemoveValidator {
+                public_key: public_key.to_lowercase(),
+            },
+            from,
+            password,
+            rpc,
+        ),
+    };
+    // O voto é da chave que assina a transação
+    let key = keystore(data_dir).unlock(from, &password.read("Senha do validador: ")?)?;
+    let mut transaction = Transaction::new(from, "", 0.0);
+    transaction.kind = TransactionKind::Governance { action };
+    set_nonce(data_dir, &mut transaction, *rpc)?;
+    wallet::sign_transaction(&key, &mut transaction)?;
+    let id = match rpc {
+        Some(_) => send_transaction(data_dir, transaction, *rpc)?,
+        // Na cadeia local o próprio validador sela o bloco, se for a sua vez
+        None => {
+            let mut blockchain = open_chain(data_dir)?;
+            blockchain.set_validator_key(key);
+            let id = blockchain.submit_transaction(transaction);
+            if blockchain.pending_transactions.contains_key(&id) {
+                blockchain.try_mine_block()?;
+            }
+            id
+        }
+    };
+    println!("Voto enviado na transação {}", id);
+    Ok(EXIT_OK)
+}
+
+fn execute_multisig(data_dir: &Path, command: &MultisigCommand) -> Result<i32, String> {
+    match command {
+        MultisigCommand::Address {
+            threshold,
+            public_keys,
+        } => {
+            println!(
+                "{}",
+                MultisigAccount::new(*threshold, public_keys)?.address()
+            );
+        }
+        MultisigCommand::Register {
+            from,
+            threshold,
+            public_keys,
+            value,
+            rpc,
+            password,
+        } => {
+            let account = MultisigAccount::new(*threshold, public_keys)?;
+            let mut transaction = account.register_transaction(from, *value);
+            set_nonce(data_dir, &mut transaction, *rpc)?;
+            sign_with_keystore(data_dir, &mut transaction, password)?;
+            let id = send_transaction(data_dir, transaction, *rpc)?;
+            println!("Conta {} registrada na transação {}", account.address(), id);
+        }
+        MultisigCommand::Propose {
+            from,
+            to,
+            value,
+            rpc,
+            out,
+        } => {
+            let mut transaction = Transaction::new(from, to, *value);
+            set_nonce(data_dir, &mut transaction, *rpc)?;
+            write_json(out, &transaction)?;
+            println!("Transação gravada em {}", out.display());
+        }
+        MultisigCommand::Sign {
+            file,
+            signer,
+            password,
+            out,
+        } => {
+            let transaction = read_json::<Transaction>(file)?;
+            let key =
+                keystore(data_dir).unlock(signer, &password.read("Senha do signatário: ")?)?;
+            write_json(out, &multisig::sign_partial(&key, &transaction))?;
+            println!("Assinatura gravada em {}", out.display());
+        }
+        MultisigCommand::Combine {
+            file,
+            signatures,
+            out,
+        } => {
+            let mut transaction = read_json::<Transaction>(file)?;
+            let signatures = signatures
+                .iter()
+                .map(|path| read_json::<TransactionSignature>(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            multisig::combine(&mut transaction, &signatures)?;
+            write_json(out, &transaction)?;
+            println!(
+                "{} assinaturas gravadas em {}",
+                transaction.signatures.len(),
+                out.display()
+            );
+        }
+        MultisigCommand::Submit { file, rpc } => {
+            let transaction = read_json::<Transaction>(file)?;
+            let id = send_transaction(data_dir, transaction, *rpc)?;
+            println!("Transação {} enviada", id);
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+// Assina a transação quando o remetente está no keystore
+fn sign_with_keystore(
+    data_dir: &Path,
+    transaction: &mut Transaction,
+    password: &PasswordArgs,
+) -> Result<(), String> {
+    let keystore = keystore(data_dir);
+    if keystore.contains(&transaction.from) {
+        keystore.sign_transaction(transaction, &password.read("Senha do remetente: ")?)?;
+    }
+    Ok(())
+}
+
+/* Preenche o nonce da transação com o próximo nonce do remetente, consultado
+** no nó em execução (`rpc`) ou na cadeia local
+*/
+fn set_nonce(
+    data_dir: &Path,
+    transaction: &mut Transaction,
+    rpc: Option<SocketAddr>,
+) -> Result<(), String> {
+    if !transaction.uses_nonce() {
+        return Ok(());
+    }
+    transaction.nonce = match rpc {
+        Some(addr) => rpc_call(addr, "account_getNonce", json!([transaction.from]))?
+            .as_u64()
+            .ok_or_else(|| String::from("Resposta inválida do nó"))?,
+        None => open_chain(data_dir)?.next_nonce(&transaction.from),
+    };
+    Ok(())
+}
+
+/* Monta a transferência no modelo da cadeia do nó em execução (`rpc`) ou da
+** cadeia local. No modo UTXO as saídas do remetente são escolhidas, com troco
+*/
+fn build_transfer(
+    data_dir: &Path,
+    from: &str,
+    to: &str,
+    value: f64,
+    rpc: Option<SocketAddr>,
+) -> Result<Transaction, String> {
+    let Some(addr) = rpc else {
+        return open_chain(data_dir)?.build_transfer(from, to, value);
+    };
+    let genesis = serde_json::from_value::<Genesis>(rpc_call(addr, "chain_getGenesis", json!([]))?)
+        .map_err(|erro| erro.to_string())?;
+    match genesis.ledger {
+        LedgerMode::Account => Ok(Transaction::new(from, to, value)),
+        LedgerMode::Utxo => {
+            let available = rpc_call(addr, "account_getUtxos", json!([from]))?;
+            let available = serde_json::from_value::<Vec<(OutPoint, TxOutput)>>(available)
+                .map_err(|erro| erro.to_string())?;
+            utxo::build_transfer(&available, from, to, value)
+        }
+    }
+}
+
+/* Envia a transação ao nó em execução pelo JSON-RPC, ou a inclui em um novo
+** bloco da cadeia local. Retorna o id da transação
+*/
+fn send_transaction(
+    data_dir: &Path,
+    transaction: Transaction,
+    rpc: Option<SocketAddr>,
+) -> Result<u64, String> {
+    match rpc {
+        Some(addr) => {
+            let result = rpc_call(addr, "tx_submit", json!([transaction]))?;
+            result
+                .as_u64()
+                .ok_or_else(|| String::from("Resposta inválida do nó"))
+        }
+        None => {
+            let mut blockchain = open_chain(data_dir)?;
+            blockchain.check_transaction(&transaction)?;
+            let id = blockchain.submit_transaction(transaction);
+            if blockchain.pending_transactions.contains_key(&id) {
+                blockchain.try_mine_block()?;
+            }
+            Ok(id)
+        }
+    }
+}
+
+fn chain_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(CHAIN_FILE)
+}
+
+fn init_chain(data_dir: &Path, genesis: &Genesis) -> Result<Blockchain, String> {
+    if chain_path(data_dir).exists() {
+        return Err(format!(
+            "Diretório de dados {} já inicializado",
+            data_dir.display()
+        ));
+    }
+    fs::create_dir_all(data_dir).map_err(|erro| erro.to_string())?;
+    let storage = FileStorage::open(&chain_path(data_dir))?;
+    // Genesis inválido não deixa o diretório de dados inicializado
+    Blockchain::with_genesis(Box::new(storage), genesis).inspect_err(|_| {
+        let _ = fs::remove_file(chain_path(data_dir));
+    })
+}
+
+fn open_chain(data_dir: &Path) -> Result<Blockchain, String> {
+    if !chain_path(data_dir).exists() {
+        return Err(format!(
+            "Diretório de dados {} não inicializado, utilize init",
+            data_dir.display()
+        ));
+    }
+    let storage = FileStorage::open(&chain_path(data_dir))?;
+    // A cadeia gravada é carregada, o genesis só é utilizado em um arquivo vazio
+    Blockchain::with_genesis(Box::new(storage), &Genesis::default())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_node(
+    data_dir: &Path,
+    listen: SocketAddr,
+    peers: &[SocketAddr],
+    rpc: SocketAddr,
+    ws: SocketAddr,
+    explorer: SocketAddr,
+    block_time: u64,
+    validator: Option<SigningKey>,
+) -> Result<i32, String> {
+    let mut blockchain = open_chain(data_dir)?;
+    if let Some(key) = validator {
+        blockchain.set_validator_key(key);
+    }
+    let config = NodeConfig {
+        chain_id: blockchain.genesis().chain_id,
+        listen_addr: listen,
+    };
+    let blockchain = Arc::new(Mutex::new(blockchain));
+    let node = Node::start(config, blockchain.clone())?;
+    for peer in peers {
+        if let Err(erro) = node.connect(*peer) {
+            warn!("Erro ao conectar ao peer"; peer = peer, erro = erro);
+        }
+    }
+    if let Err(erro) = node.sync() {
+        warn!("Erro na sincronização"; erro = erro);
+    }
+    // Transações do RPC passam pelo nó, que as propaga e anuncia os blocos completados
+    let submit_node = node.clone();
+    RpcServer::with_submit(
+        RpcConfig { listen_addr: rpc },
+        blockchain.clone(),
+        Arc::new(move |transaction| submit_node.submit_checked(transaction)),
+    )?;
+    WsServer::start(WsConfig { listen_addr: ws }, blockchain.clone())?;
+    ExplorerServer::start(
+        ExplorerConfig {
+            listen_addr: explorer,
+        },
+        blockchain.clone(),
+    )?;
+
+    loop {
+        thread::sleep(Duration::from_secs(block_time));
+        if !blockchain.lock().unwrap().pending_transactions.is_empty() {
+            node.mine_block();
+        }
+    }
+}
+
+/* Importa os blocos exportados. Um diretório não inicializado é criado com o
+** genesis do arquivo, blocos já presentes na cadeia são ignorados
+*/
+fn import_chain(data_dir: &Path, file: &Path) -> Result<i32, String> {
+    let data = fs::read(file).map_err(|erro| erro.to_string())?;
+    let export = serde_json::from_slice::<ChainExport>(&data)
+        .map_err(|erro| format!("Arquivo de exportação inválido: {}", erro))?;
+
+    let mut blockchain = if chain_path(data_dir).exists() {
+        open_chain(data_dir)?
+    } else {
+        init_chain(data_dir, &export.genesis)?
+    };
+    if blockchain.genesis().chain_id != export.genesis.chain_id {
+        return Err(String::from("Id da cadeia incompatível"));
+    }
+
+    let mut imported = 0;
+    for block in export.blocks {
+        let known = blockchain
+            .chain
+            .get(block.id as usize)
+            .is_some_and(|local| local.hash == block.hash);
+        if known {
+            continue;
+        }
+        let id = block.id;
+        if let Err(erro) = blockchain.import_block(block) {
+            println!("Bloco {} inválido: {}", id, erro);
+            return Ok(EXIT_INVALID);
+        }
+        imported += 1;
+    }
+    println!(
+        "{} blocos importados, altura {}",
+        imported,
+        blockchain.height()
+    );
+    Ok(EXIT_OK)
+}
+
+fn keystore(data_dir: &Path) -> Keystore {
+    Keystore::with_params(&data_dir.join(KEYSTORE_DIR), KDF_PARAMS)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|erro| erro.to_string())
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    fs::write(path, to_json(value)?).map_err(|erro| erro.to_string())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let data = fs::read(path).map_err(|erro| format!("{}: {}", path.display(), erro))?;
+    serde_json::from_slice(&data).map_err(|erro| format!("{}: {}", path.display(), erro))
+}
+
+// Chamada JSON-RPC ao nó em execução
+fn rpc_call(addr: SocketAddr, method: &str, params: Value) -> Result<Value, String> {
+    let body = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 }).to_string();
+    let mut stream = TcpStream::connect(addr).map_err(|erro| erro.to_string())?;
+    write!(
+        stream,
+        "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        addr,
+        body.len(),
+        body
+    )
+    .map_err(|erro| erro.to_string())?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|erro| erro.to_string())?;
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body)
+        .ok_or_else(|| String::from("Resposta HTTP inválida"))?;
+    let response = serde_json::from_str::<Value>(body).map_err(|erro| erro.to_string())?;
+    match response.get("error") {
+        Some(erro) => Err(format!("Erro do nó: {}", erro["message"])),
+        None => Ok(response["result"].clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(data_dir: &Path, args: &[&str]) -> i32 {
+        let data_dir = data_dir.to_str().unwrap();
+        let args = ["wasm-runtime", "--data-dir", data_dir]
+            .iter()
+            .chain(args)
+            .copied()
+            .collect::<Vec<_>>();
+        run(args)
+    }
+
+    // Grava a senha em um arquivo do diretório, para `--password-file`
+    fn password_file(dir: &Path, password: &str) -> String {
+        let path = dir.join(format!("{}.txt", password));
+        fs::write(&path, format!("{}\n", password)).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_cli() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let data = dir.path().join("data");
+        let from = keystore(&data).create("senha").unwrap();
+        let genesis = dir.path().join("genesis.json");
+        fs::write(
+            &genesis,
+            json!({ "chain_id": "rede-teste", "alloc": { from.clone(): 100.0 } }).to_string(),
+        )
+        .unwrap();
+
+        // Comandos da cadeia exigem o diretório inicializado
+        assert_eq!(cli(&data, &["chain", "validate"]), EXIT_ERROR);
+
+        // Genesis com consenso inválido é reportado e não inicializa o diretório
+        let invalid = dir.path().join("invalid.json");
+        fs::write(
+            &invalid,
+            json!({ "chain_id": "rede-teste", "consensus": { "type": "authority", "validators": [] } })
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            cli(&data, &["init", "--genesis", invalid.to_str().unwrap()]),
+            EXIT_ERROR
+        );
+        assert_eq!(cli(&data, &["chain", "validate"]), EXIT_ERROR);
+        assert_eq!(
+            cli(&data, &["init", "--genesis", genesis.to_str().unwrap()]),
+            EXIT_OK
+        );
+        assert_eq!(cli(&data, &["init"]), EXIT_ERROR);
+
+        let send = [
+            "tx",
+            "send",
+            "--from",
+            &from,
+            "--to",
+            "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            "--value",
+            "4",
+            "--password-file",
+            &senha,
+        ];
+        // Cada envio recebe o próximo nonce do remetente
+        assert_eq!(cli(&data, &send), EXIT_OK);
+        assert_eq!(cli(&data, &send), EXIT_OK);
+        assert_eq!(cli(&data, &["block", "show", "2"]), EXIT_OK);
+        assert_eq!(cli(&data, &["block", "show", "3"]), EXIT_ERROR);
+        assert_eq!(cli(&data, &["chain", "validate"]), EXIT_OK);
+
+        let blockchain = open_chain(&data).unwrap();
+        assert_eq!(blockchain.height(), 2);
+        assert_eq!(blockchain.balance(&from), 92.0);
+        assert_eq!(blockchain.account(&from).nonce, 2);
+        let hash = blockchain.chain[1].hash.to_string();
+        drop(blockchain);
+        assert_eq!(cli(&data, &["block", "show", &hash]), EXIT_OK);
+
+        // Subcomando inexistente
+        assert_eq!(cli(&data, &["chain", "repair"]), EXIT_ERROR);
+    }
+
+    #[test]
+    fn test_cli_export_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let data = dir.path().join("data");
+        let copy = dir.path().join("copy");
+        let file = dir.path().join("chain.json");
+        let file = file.to_str().unwrap();
+
+        assert_eq!(cli(&data, &["init"]), EXIT_OK);
+        let from = keystore(&data).create("senha").unwrap();
+        let send = [
+            "tx",
+            "send",
+            "--from",
+            &from,
+            "--to",
+            "0x2",
+            "--value",
+            "1",
+            "--password-file",
+            &senha,
+        ];
+        for _ in 0..3 {
+            assert_eq!(cli(&data, &send), EXIT_OK);
+        }
+        assert_eq!(cli(&data, &["chain", "export", file]), EXIT_OK);
+        assert_eq!(cli(&copy, &["chain", "import", file]), EXIT_OK);
+        assert_eq!(cli(&copy, &["chain", "validate"]), EXIT_OK);
+        assert_eq!(open_chain(&copy).unwrap().balance("0x2"), 3.0);
+
+        // Importar novamente não altera a cadeia
+        assert_eq!(cli(&copy, &["chain", "import", file]), EXIT_OK);
+        assert_eq!(open_chain(&copy).unwrap().height(), 3);
+
+        // Bloco alterado no arquivo é recusado
+        let mut export = serde_json::from_slice::<ChainExport>(&fs::read(file).unwrap()).unwrap();
+        export.blocks[2].transactions.get_mut(&3).unwrap().value = 100.0;
+        fs::write(file, to_json(&export).unwrap()).unwrap();
+        let other = dir.path().join("other");
+        assert_eq!(cli(&other, &["chain", "import", file]), EXIT_INVALID);
+    }
+
+    #[test]
+    fn test_cli_corrupted_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let data = dir.path().join("data");
+        assert_eq!(cli(&data, &["init"]), EXIT_OK);
+        let from = keystore(&data).create("senha").unwrap();
+        let send = [
+            "tx",
+            "send",
+            "--from",
+            &from,
+            "--to",
+            "0x2",
+            "--value",
+            "1",
+            "--password-file",
+            &senha,
+        ];
+        assert_eq!(cli(&data, &send), EXIT_OK);
+
+        // A corrupção é gravada no diretório de dados
+        open_chain(&data)
+            .unwrap()
+            .corrupt_block(1, 1, 50.0)
+            .unwrap();
+        assert_eq!(cli(&data, &["chain", "validate"]), EXIT_INVALID);
+    }
+
+    #[test]
+    fn test_cli_wallet() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let errada = password_file(dir.path(), "errada");
+        let data = dir.path().join("data");
+        assert_eq!(cli(&data, &["init"]), EXIT_OK);
+        assert_eq!(
+            cli(&data, &["wallet", "new", "--password-file", &senha]),
+            EXIT_OK
+        );
+        assert_eq!(cli(&data, &["wallet", "list"]), EXIT_OK);
+        let from = keystore(&data).list().unwrap().remove(0);
+
+        // Remetente do keystore exige a senha correta, a transação é assinada
+        let send = ["tx", "send", "--from", &from, "--to", "0x2", "--value", "1"];
+        let missing = dir.path().join("ausente.txt");
+        let missing = [&send[..], &["--password-file", missing.to_str().unwrap()]].concat();
+        assert_eq!(cli(&data, &missing), EXIT_ERROR);
+        let wrong = [&send[..], &["--password-file", &errada]].concat();
+        assert_eq!(cli(&data, &wrong), EXIT_ERROR);
+        let signed = [&send[..], &["--password-file", &senha]].concat();
+        assert_eq!(cli(&data, &signed), EXIT_OK);
+        // Sem o arquivo a senha vem da variável de ambiente
+        env::set_var(PASSWORD_ENV, "senha");
+        let code = cli(&data, &send);
+        env::remove_var(PASSWORD_ENV);
+        assert_eq!(code, EXIT_OK);
+
+        let blockchain = open_chain(&data).unwrap();
+        let transaction = &blockchain.chain[1].transactions[&1];
+        assert!(crate::wallet::verify_transaction(transaction).is_ok());
+        assert_eq!(blockchain.balance("0x2"), 2.0);
+    }
+
+    #[test]
+    fn test_cli_wallet_recover() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let data = dir.path().join("data");
+        assert_eq!(
+            cli(&data, &["wallet", "mnemonic", "--words", "12"]),
+            EXIT_OK
+        );
+        assert_eq!(
+            cli(&data, &["wallet", "mnemonic", "--words", "10"]),
+            EXIT_ERROR
+        );
+
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let recover = ["wallet", "recover", "--mnemonic", phrase, "--count", "2"];
+        assert_eq!(
+            cli(
+                &data,
+                &[&recover[..], &["--password-file", &senha]].concat()
+            ),
+            EXIT_OK
+        );
+        let keystore = keystore(&data);
+        assert!(keystore.contains("0x2243ac3a24188b27e7fda3e382f960adf193dcf9"));
+        assert!(keystore.contains("0xd8885978876512c8dce0d4ff333588492404406e"));
+        assert_eq!(keystore.list().unwrap().len(), 2);
+
+        // Frase com checksum inválido
+        let invalid = phrase.replace("about", "abandon");
+        let recover = [
+            "wallet",
+            "recover",
+            "--mnemonic",
+            &invalid,
+            "--password-file",
+            &senha,
+        ];
+        assert_eq!(cli(&data, &recover), EXIT_ERROR);
+    }
+
+    #[test]
+    fn test_cli_multisig() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let errada = password_file(dir.path(), "errada");
+        let data = dir.path().join("data");
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        assert_eq!(cli(&data, &["init"]), EXIT_OK);
+
+        // Três donos no keystore, conta 2-de-3
+        let keystore = keystore(&data);
+        let owners = (0..3)
+            .map(|_| keystore.create("senha").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(cli(&data, &["wallet", "show", &owners[0]]), EXIT_OK);
+        let mut args = vec!["--threshold", "2"];
+        let public_keys = owners
+            .iter()
+            .map(|owner| keystore.public_key(owner).unwrap())
+            .collect::<Vec<_>>();
+        for public_key in &public_keys {
+            args.extend(["--public-key", public_key]);
+        }
+        let account = MultisigAccount::new(2, &public_keys).unwrap().address();
+        assert_eq!(
+            cli(&data, &[&["multisig", "address"], &args[..]].concat()),
+            EXIT_OK
+        );
+        let register = [
+            &[
+                "multisig", "register", "--from", &owners[0], "--value", "10",
+            ][..],
+            &["--password-file", &senha],
+            &args[..],
+        ]
+        .concat();
+        assert_eq!(cli(&data, &register), EXIT_OK);
+
+        let propose = [
+            "multisig", "propose", "--from", &account, "--to", "0x2", "--value", "4",
+        ];
+        let (tx, signed) = (path("tx.json"), path("signed.json"));
+        assert_eq!(
+            cli(&data, &[&propose[..], &["--out", &tx]].concat()),
+            EXIT_OK
+        );
+
+        // Cada dono assina o arquivo separadamente
+        let mut combine = vec!["multisig", "combine", &tx, "--out", &signed];
+        let signatures = (0..2)
+            .map(|i| path(&format!("sig{}.json", i)))
+            .collect::<Vec<_>>();
+        for (owner, signature) in owners.iter().zip(&signatures) {
+            let sign = [
+                "multisig",
+                "sign",
+                &tx,
+                "--signer",
+                owner,
+                "--password-file",
+                &senha,
+                "--out",
+                signature,
+            ];
+            assert_eq!(cli(&data, &sign), EXIT_OK);
+            combine.extend(["--signature", signature]);
+        }
+        let wrong = [
+            "multisig",
+            "sign",
+            &tx,
+            "--signer",
+            &owners[2],
+            "--password-file",
+            &errada,
+            "--out",
+            &signed,
+        ];
+        assert_eq!(cli(&data, &wrong), EXIT_ERROR);
+
+        // Apenas uma assinatura: a transação é recusada
+        let partial = path("partial.json");
+        let single = [
+            "multisig",
+            "combine",
+            &tx,
+            "--signature",
+            &signatures[0],
+            "--out",
+            &partial,
+        ];
+        assert_eq!(cli(&data, &single), EXIT_OK);
+        assert_eq!(cli(&data, &["multisig", "submit", &partial]), EXIT_ERROR);
+        assert_eq!(open_chain(&data).unwrap().balance(&account), 10.0);
+
+        assert_eq!(cli(&data, &combine), EXIT_OK);
+        assert_eq!(cli(&data, &["multisig", "submit", &signed]), EXIT_OK);
+        let blockchain = open_chain(&data).unwrap();
+        assert_eq!(blockchain.balance(&account), 6.0);
+        assert_eq!(blockchain.balance("0x2"), 4.0);
+    }
+
+    #[test]
+    fn test_cli_utxo() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let data = dir.path().join("data");
+        let genesis = dir.path().join("genesis.json");
+        let from = keystore(&data).create("senha").unwrap();
+        fs::write(
+            &genesis,
+            json!({ "chain_id": "utxo", "ledger": "utxo", "alloc": { from.clone(): 10.0 } })
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            cli(&data, &["init", "--genesis", genesis.to_str().unwrap()]),
+            EXIT_OK
+        );
+
+        let send = [
+            "tx",
+            "send",
+            "--from",
+            &from,
+            "--to",
+            "0x2",
+            "--value",
+            "4",
+            "--password-file",
+            &senha,
+        ];
+        assert_eq!(cli(&data, &send), EXIT_OK);
+        assert_eq!(cli(&data, &send), EXIT_OK);
+        // Saldo insuficiente: restam 2 em uma saída de troco
+        assert_eq!(cli(&data, &send), EXIT_ERROR);
+
+        let blockchain = open_chain(&data).unwrap();
+        assert_eq!(blockchain.balance(&from), 2.0);
+        assert_eq!(blockchain.balance("0x2"), 8.0);
+        assert_eq!(blockchain.unspent_outputs("0x2").len(), 2);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_cli_validator() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let errada = password_file(dir.path(), "errada");
+        let data = dir.path().join("data");
+        let address = keystore(&data).create("senha").unwrap();
+        let public_key = keystore(&data).public_key(&address).unwrap();
+        let genesis = dir.path().join("genesis.json");
+        fs::write(
+            &genesis,
+            json!({ "chain_id": "poa", "consensus": { "type": "authority", "validators": [public_key] } })
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            cli(&data, &["init", "--genesis", genesis.to_str().unwrap()]),
+            EXIT_OK
+        );
+
+        // Sem a chave do validador a cadeia local não produz blocos
+        let send = [
+            "tx",
+            "send",
+            "--from",
+            &address,
+            "--to",
+            "0x2",
+            "--value",
+            "4",
+            "--password-file",
+            &senha,
+        ];
+        assert_eq!(cli(&data, &send), EXIT_ERROR);
+
+        let other = crate::storage::encode_hex(
+            crate::wallet::keypair_from_seed(&[7u8; 32])
+                .verifying_key()
+                .as_bytes(),
+        );
+        let add = ["validator", "add", &other, "--from", &address];
+        assert_eq!(
+            cli(&data, &[&add[..], &["--password-file", &errada]].concat()),
+            EXIT_ERROR
+        );
+        assert_eq!(
+            cli(&data, &[&add[..], &["--password-file", &senha]].concat()),
+            EXIT_OK
+        );
+
+        let blockchain = open_chain(&data).unwrap();
+        assert_eq!(blockchain.validators(), vec![public_key, other]);
+        assert_eq!(blockchain.height(), 1);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_cli_stake() {
+        let dir = tempfile::tempdir().unwrap();
+        let senha = password_file(dir.path(), "senha");
+        let data = dir.path().join("data");
+        let public_key = crate::storage::encode_hex(
+            crate::wallet::keypair_from_seed(&[1u8; 32])
+                .verifying_key()
+                .as_bytes(),
+        );
+        let from = keystore(&data).create("senha").unwrap();
+        let genesis = dir.path().join("genesis.json");
+        fs::write(
+            &genesis,
+            json!({
+                "chain_id": "pos",
+                "alloc": { from.clone(): 5.0 },
+                "consensus": { "type": "stake", "validators": { public_key.clone(): 10.0 } }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            cli(&data, &["init", "--genesis", genesis.to_str().unwrap()]),
+            EXIT_OK
+        );
+        assert_eq!(cli(&data, &["stake", "validators"]), EXIT_OK);
+        assert_eq!(cli(&data, &["stake", "delegations", "0x1"]), EXIT_OK);
+
+        // Sem a chave do validador a cadeia local não produz o bloco do vínculo
+        let bond = [
+            "stake",
+            "bond",
+            "--from",
+            &from,
+            "--validator",
+            &public_key,
+            "--password-file",
+            &senha,
+        ];
+        assert_eq!(
+            cli(&data, &[&bond[..], &["--value", "2"]].concat()),
+            EXIT_ERROR
+        );
+
+        let blockchain = open_chain(&data).unwrap();
+        assert_eq!(blockchain.stake_validators()[0].stake, 10.0);
+        assert!(blockchain.delegations(&from).is_empty());
+    }
+}
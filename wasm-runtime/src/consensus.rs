@@ -0,0 +1,382 @@
+/* Mod Consensus
+** - Trait `Consensus`: regras de produção e validação dos blocos, a
+**   implementação é escolhida pelos parâmetros do genesis (`consensus`)
+**    - prepare: checa se o nó local pode produzir o próximo bloco
+**    - seal: assina o bloco montado pelo nó local
+**    - verify: checa o produtor e a assinatura de um bloco recebido, com o
+**      estado do bloco anterior
+**    - finalize_block: alterações do consenso no estado ao fim de cada bloco
+** - OpenConsensus: qualquer nó produz blocos, sem selo (padrão)
+** - ProofOfAuthority: conjunto de validadores (chaves públicas) gravado no
+**   estado em `consensus/validators`. O bloco `id` pertence ao validador
+**   `validators[id % N]` (vez em rodízio), que assina a hash do bloco
+** - Governança: transações `Governance` assinadas por validadores votam na
+**   inclusão ou remoção de um validador, a alteração é aplicada quando a
+**   maioria dos validadores (mais da metade) vota na mesma proposta
+**    - voting_power: peso dos validadores nos votos de finalidade (mod finality)
+** - ProofOfStake: prova de participação (mod stake)
+*/
+use crate::{
+    block::Block,
+    genesis::ConsensusParams,
+    stake::{self, ProofOfStake},
+    state,
+    storage::{encode_hex, load, save, Storage},
+    wallet::{self, parse_public_key},
+};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+
+const VALIDATORS_KEY: &str = "consensus/validators";
+const PROPOSALS_KEY: &str = "consensus/proposals";
+
+pub trait Consensus: Send + std::fmt::Debug {
+    /* Checa se o nó local pode produzir o bloco `block_id` com o estado atual.
+     ** Retorna a chave pública do produtor, vazia para blocos sem selo
+     */
+    fn prepare(&self, storage: &dyn Storage, block_id: u64) -> Result<String, String>;
+
+    // Assina a hash do bloco montado com o produtor retornado por `prepare`
+    fn seal(&self, block: &mut Block) -> Result<(), String>;
+
+    // Checa o selo do bloco com o estado do bloco anterior
+    fn verify(&self, storage: &dyn Storage, block: &Block) -> Result<(), String>;
+
+    // Aplicado depois das transações do bloco, antes do cálculo da raiz do estado
+    fn finalize_block(&self, _storage: &mut dyn Storage, _block_id: u64, _producer: &str) {}
+
+    /* Peso de cada validador (chave pública) nos votos de finalidade do bloco
+     ** (mod finality), vazio sem validadores
+     */
+    fn voting_power(&self, _storage: &dyn Storage, _block_id: u64) -> Vec<(String, f64)> {
+        vec![]
+    }
+
+    // Chave do nó local utilizada para selar os blocos
+    fn set_key(&mut self, _key: SigningKey) {}
+}
+
+// Consenso a partir dos parâmetros do genesis, os selos incluem o id da cadeia
+pub fn from_params(params: &ConsensusParams, chain_id: &str) -> Box<dyn Consensus> {
+    match params {
+        ConsensusParams::Open => Box::new(OpenConsensus),
+        ConsensusParams::Authority { .. } => Box::new(ProofOfAuthority::new(chain_id)),
+        ConsensusParams::Stake(_) => Box::new(ProofOfStake::new(chain_id)),
+    }
+}
+
+// Estado inicial do consenso, gravado junto com o genesis
+pub fn init_state(storage: &mut dyn Storage, params: &ConsensusParams) -> Result<(), String> {
+    if let ConsensusParams::Authority { validators } = params {
+        if validators.is_empty() {
+            return Err(String::from("Prova de autoridade sem validadores"));
+        }
+        for validator in validators {
+            parse_public_key(validator)?;
+        }
+        set_validators(storage, validators);
+    }
+    if let ConsensusParams::Stake(params) = params {
+        stake::init_state(storage, params)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn validator_key(key: Option<&SigningKey>) -> Result<&SigningKey, String> {
+    key.ok_or_else(|| String::from("Chave do validador não configurada"))
+}
+
+// Assina o id da cadeia, a altura e a hash do bloco com a chave do produtor
+pub(crate) fn seal_block(
+    key: &SigningKey,
+    chain_id: &str,
+    block: &mut Block,
+) -> Result<(), String> {
+    block.signature = wallet::sign_hash(key, &block.signing_hash(chain_id)).signature;
+    Ok(())
+}
+
+// Blocos dos validadores precisam da assinatura do produtor
+pub(crate) fn check_seal(block: &Block, chain_id: &str) -> Result<(), String> {
+    if block.signature.is_empty() {
+        return Err(String::from("Bloco sem assinatura do produtor"));
+    }
+    block.check_signature(chain_id)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OpenConsensus;
+
+impl Consensus for OpenConsensus {
+    fn prepare(&self, _storage: &dyn Storage, _block_id: u64) -> Result<String, String> {
+        Ok(String::new())
+    }
+
+    fn seal(&self, _block: &mut Block) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn verify(&self, _storage: &dyn Storage, _block: &Block) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProofOfAuthority {
+    key: Option<SigningKey>,
+    chain_id: String,
+}
+
+impl ProofOfAuthority {
+    pub fn new(chain_id: &str) -> Self {
+        ProofOfAuthority {
+            key: None,
+            chain_id: chain_id.to_string(),
+        }
+    }
+
+    pub fn with_key(chain_id: &str, key: SigningKey) -> Self {
+        ProofOfAuthority {
+            key: Some(key),
+            chain_id: chain_id.to_string(),
+        }
+    }
+}
+
+impl Consensus for ProofOfAuthority {
+    fn prepare(&self, storage: &dyn Storage, block_id: u64) -> Result<String, String> {
+        let key = validator_key(self.key.as_ref())?;
+        let expected = slot_validator(storage, block_id)?;
+        let public_key = encode_hex(key.verifying_key().as_bytes());
+        if public_key != expected {
+            return Err(format!(
+                "Bloco {} pertence ao validador {}",
+                block_id, expected
+            ));
+        }
+        Ok(public_key)
+    }
+
+    fn seal(&self, block: &mut Block) -> Result<(), String> {
+        seal_block(validator_key(self.key.as_ref())?, &self.chain_id, block)
+    }
+
+    fn verify(&self, storage: &dyn Storage, block: &Block) -> Result<(), String> {
+        if block.producer != slot_validator(storage, block.id)? {
+            return Err(String::from("Produtor fora da sua vez"));
+        }
+        check_seal(block, &self.chain_id)
+    }
+
+    fn voting_power(&self, storage: &dyn Storage, _block_id: u64) -> Vec<(String, f64)> {
+        validators(storage)
+            .into_iter()
+            .map(|validator| (validator, 1.0))
+            .collect()
+    }
+
+    fn set_key(&mut self, key: SigningKey) {
+        self.key = Some(key);
+    }
+}
+
+// Validadores atuais em ordem de vez, vazio fora da prova de autoridade
+pub fn validators(storage: &dyn Storage) -> Vec<String> {
+    load(storage, VALIDATORS_KEY).unwrap_or_default()
+}
+
+fn set_validators(storage: &mut dyn Storage, validators: &[String]) {
+    save(storage, VALIDATORS_KEY, &validators);
+    let value = serde_json::to_vec(validators).expect("Erro ao serializar validadores");
+    state::record_change(storage, VALIDATORS_KEY.as_bytes(), Some(&value));
+}
+
+// Validador da vez do bloco `block_id`
+fn slot_validator(storage: &dyn Storage, block_id: u64) -> Result<String, String> {
+    let validators = validators(storage);
+    if validators.is_empty() {
+        return Err(String::from("Conjunto de validadores vazio"));
+    }
+    Ok(validators[(block_id % validators.len() as u64) as usize].clone())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GovernanceAction {
+    AddValidator { public_key: String },
+    RemoveValidator { public_key: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Proposal {
+    action: GovernanceAction,
+    votes: Vec<String>,
+}
+
+/* Registra o voto do validador `voter` (chave pública) na proposta. Retorna
+** true quando a proposta alcança a maioria e o conjunto de validadores é alterado
+*/
+pub fn vote(
+    storage: &mut dyn Storage,
+    voter: &str,
+    action: &GovernanceAction,
+) -> Result<bool, String> {
+    let mut validators = validators(storage);
+    if validators.is_empty() {
+        return Err(String::from("Governança exige a prova de autoridade"));
+    }
+    if !validators.iter().any(|validator| validator == voter) {
+        return Err(String::from("Apenas validadores votam na governança"));
+    }
+    match action {
+        GovernanceAction::AddValidator { public_key } => {
+            parse_public_key(public_key)?;
+            if validators.contains(public_key) {
+                return Err(String::from("Validador já faz parte do conjunto"));
+            }
+        }
+        GovernanceAction::RemoveValidator { public_key } => {
+            if !validators.contains(public_key) {
+                return Err(String::from("Validador não faz parte do conjunto"));
+            }
+            if validators.len() == 1 {
+                return Err(String::from("O último validador não pode ser removido"));
+            }
+        }
+    }
+
+    let mut proposals = load::<Vec<Proposal>>(storage, PROPOSALS_KEY).unwrap_or_default();
+    let position = match proposals
+        .iter()
+        .position(|proposal| proposal.action == *action)
+    {
+        Some(position) => position,
+        None => {
+            proposals.push(Proposal {
+                action: action.clone(),
+                votes: vec![],
+            });
+            proposals.len() - 1
+        }
+    };
+    let proposal = &mut proposals[position];
+    if !proposal.votes.iter().any(|other| other == voter) {
+        proposal.votes.push(voter.to_string());
+    }
+    // Votos de validadores removidos não contam
+    let votes = proposal
+        .votes
+        .iter()
+        .filter(|vote| validators.contains(vote))
+        .count();
+    let approved = votes * 2 > validators.len();
+    if approved {
+        proposals.remove(position);
+        match action {
+            GovernanceAction::AddValidator { public_key } => validators.push(public_key.clone()),
+            GovernanceAction::RemoveValidator { public_key } => {
+                validators.retain(|validator| validator != public_key)
+            }
+        }
+        set_validators(storage, &validators);
+    }
+    save(storage, PROPOSALS_KEY, &proposals);
+    let value = serde_json::to_vec(&proposals).expect("Erro ao serializar propostas");
+    state::record_change(storage, PROPOSALS_KEY.as_bytes(), Some(&value));
+    Ok(approved)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hash::{Hash, Sha256Hasher};
+    use crate::{storage::MemoryStorage, wallet::keypair_from_seed};
+
+    pub(crate) fn validator_keys(count: u8) -> Vec<SigningKey> {
+        (1..=count)
+            .map(|seed| keypair_from_seed(&[seed; 32]))
+            .collect()
+    }
+
+    pub(crate) fn public_key(key: &SigningKey) -> String {
+        encode_hex(key.verifying_key().as_bytes())
+    }
+
+    #[test]
+    fn test_governance_votes() {
+        let keys = validator_keys(4);
+        let mut storage = MemoryStorage::new();
+        let initial = keys[..3].iter().map(public_key).collect::<Vec<_>>();
+        init_state(
+            &mut storage,
+            &ConsensusParams::Authority {
+                validators: initial.clone(),
+            },
+        )
+        .unwrap();
+
+        // Inclusão exige 2 de 3 votos
+        let add = GovernanceAction::AddValidator {
+            public_key: public_key(&keys[3]),
+        };
+        assert_eq!(vote(&mut storage, &initial[0], &add), Ok(false));
+        assert_eq!(vote(&mut storage, &initial[0], &add), Ok(false));
+        assert!(vote(&mut storage, &public_key(&keys[3]), &add).is_err());
+        assert_eq!(vote(&mut storage, &initial[1], &add), Ok(true));
+        assert_eq!(validators(&storage).len(), 4);
+        assert!(vote(&mut storage, &initial[2], &add).is_err());
+
+        // Remoção exige 3 de 4 votos
+        let remove = GovernanceAction::RemoveValidator {
+            public_key: initial[0].clone(),
+        };
+        for voter in &initial[..2] {
+            assert_eq!(vote(&mut storage, voter, &remove), Ok(false));
+        }
+        assert_eq!(vote(&mut storage, &public_key(&keys[3]), &remove), Ok(true));
+        assert_eq!(
+            validators(&storage),
+            vec![initial[1].clone(), initial[2].clone(), public_key(&keys[3])]
+        );
+
+        assert!(init_state(
+            &mut storage,
+            &ConsensusParams::Authority { validators: vec![] }
+        )
+        .is_err());
+        assert!(vote(&mut MemoryStorage::new(), &initial[0], &add).is_err());
+    }
+
+    #[test]
+    fn test_authority_slots() {
+        let keys = validator_keys(2);
+        let mut storage = MemoryStorage::new();
+        let validators = keys.iter().map(public_key).collect::<Vec<_>>();
+        init_state(&mut storage, &ConsensusParams::Authority { validators }).unwrap();
+
+        // O bloco 1 pertence ao segundo validador
+        let first = ProofOfAuthority::with_key("local", keys[0].clone());
+        let second = ProofOfAuthority::with_key("local", keys[1].clone());
+        assert!(first.prepare(&storage, 1).is_err());
+        assert!(ProofOfAuthority::default().prepare(&storage, 1).is_err());
+        let producer = second.prepare(&storage, 1).unwrap();
+
+        let mut block = Block::new(1, Hash::ZERO, "", "", Default::default(), &Sha256Hasher);
+        block.set_producer(&producer, &Sha256Hasher);
+        second.seal(&mut block).unwrap();
+        assert_eq!(first.verify(&storage, &block), Ok(()));
+
+        // Assinatura de outro validador
+        let mut forged = block.clone();
+        first.seal(&mut forged).unwrap();
+        assert!(second.verify(&storage, &forged).is_err());
+
+        // Produtor fora da vez
+        let mut early = Block::new(2, Hash::ZERO, "", "", Default::default(), &Sha256Hasher);
+        early.set_producer(&producer, &Sha256Hasher);
+        second.seal(&mut early).unwrap();
+        assert_eq!(
+            first.verify(&storage, &early),
+            Err(String::from("Produtor fora da sua vez"))
+        );
+    }
+}
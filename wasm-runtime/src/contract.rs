@@ -0,0 +1,692 @@
+/* Mod Contract
+** - Execução de contratos inteligentes wasm com o interpretador wasmi
+** - O endereço do contrato é derivado do remetente e do nonce da transação de
+**   publicação: conhecido pelo remetente antes do envio, igual em todos os nós
+** - Cada contrato possui seu próprio armazenamento chave-valor, com as chaves
+**   prefixadas pelo endereço do contrato: `contract/<endereço>/storage/<chave>`
+** - Funções do host disponíveis para o contrato (módulo "env"):
+**    - caller(ptr, cap) -> len: copia o endereço de quem chamou o contrato
+**    - value() -> f64: valor enviado na transação
+**    - log(ptr, len): registra uma mensagem no log
+**    - storage_get(key_ptr, key_len, value_ptr, value_cap) -> len (-1 se não existe)
+**    - storage_set(key_ptr, key_len, value_ptr, value_len)
+**    - storage_remove(key_ptr, key_len)
+** - As alterações no armazenamento só são gravadas se a execução terminar sem erro
+** - A execução é medida em gas (fuel do wasmi, consumido por instrução executada).
+**   `log` e `storage_set` também cobram `GAS_PER_BYTE` por byte gravado.
+**   Ao esgotar o gas a execução falha de forma determinística
+** - As funções do host checam os limites da memória do contrato antes de copiar
+**   os dados
+** - Limites da execução (StoreLimits do wasmi): memória de até MAX_MEMORY_SIZE,
+**   tabelas de até MAX_TABLE_ELEMENTS e uma instância com uma memória e uma
+**   tabela. Memórias declaradas acima do limite falham na instanciação e
+**   `memory.grow` acima do limite retorna -1, sem alocar
+** - Publicação: bytecode de até MAX_CODE_SIZE bytes, com gas de
+**   DEPLOY_GAS_PER_BYTE por byte do bytecode (`deploy_gas`)
+*/
+use crate::{info, state, storage::Storage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use wasmi::{
+    core::TrapCode, Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, Val,
+};
+
+// Gas cobrado por byte das mensagens do log e das chaves e valores gravados
+pub const GAS_PER_BYTE: u64 = 10;
+// Gas cobrado por byte do bytecode publicado
+pub const DEPLOY_GAS_PER_BYTE: u64 = 200;
+// Tamanho máximo do bytecode de um contrato
+pub const MAX_CODE_SIZE: usize = 64 * 1024;
+// Tamanho máximo da memória do contrato (256 páginas de 64 KiB)
+pub const MAX_MEMORY_SIZE: usize = 16 * 1024 * 1024;
+// Quantidade máxima de elementos de uma tabela do contrato
+pub const MAX_TABLE_ELEMENTS: u32 = 10_000;
+
+// Contexto da execução, acessível pelas funções do host
+struct ContractContext<'a> {
+    storage: &'a dyn Storage,
+    contract: String,
+    caller: String,
+    value: f64,
+    // Alterações pendentes no armazenamento do contrato, None representa remoção
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    logs: Vec<String>,
+    // Limites de memória, tabelas e instâncias da execução
+    limits: StoreLimits,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ExecutionStatus {
+    #[default]
+    Success,
+    // Gas esgotado antes do fim da execução
+    OutOfGas,
+    // Erro na chamada ou na execução do contrato
+    Failed(String),
+}
+
+// Resultado de uma execução
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Execution {
+    pub status: ExecutionStatus,
+    // Valor retornado pela função chamada
+    pub result: Option<i64>,
+    // Gas consumido pela execução
+    pub gas_used: u64,
+    // Mensagens registradas pelo contrato com a função `log`
+    pub logs: Vec<String>,
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+// Endereço do contrato: 20 primeiros bytes da sha-256 do remetente e do nonce da publicação
+pub fn contract_address(from: &str, nonce: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("contract/{}/{}", from, nonce));
+    let hash = hasher.finalize();
+    let hex = hash[..20]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("0x{}", hex)
+}
+
+fn code_key(contract: &str) -> String {
+    format!("contract/{}/code", contract)
+}
+
+fn storage_key(contract: &str, key: &[u8]) -> Vec<u8> {
+    let mut full_key = format!("contract/{}/storage/", contract).into_bytes();
+    full_key.extend_from_slice(key);
+    full_key
+}
+
+pub fn get_code(storage: &dyn Storage, contract: &str) -> Option<Vec<u8>> {
+    storage.get(code_key(contract).as_bytes())
+}
+
+// Lê um valor do armazenamento do contrato
+pub fn get_storage(storage: &dyn Storage, contract: &str, key: &[u8]) -> Option<Vec<u8>> {
+    storage.get(&storage_key(contract, key))
+}
+
+// Gas da publicação do bytecode
+pub fn deploy_gas(code: &[u8]) -> u64 {
+    (code.len() as u64).saturating_mul(DEPLOY_GAS_PER_BYTE)
+}
+
+// Bytecode acima de MAX_CODE_SIZE é recusado antes de qualquer validação
+pub fn check_code_size(code: &[u8]) -> Result<(), String> {
+    if code.len() > MAX_CODE_SIZE {
+        Err(format!(
+            "Bytecode do contrato com {} bytes, máximo {}",
+            code.len(),
+            MAX_CODE_SIZE
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// Checa se o bytecode é um módulo wasm válido antes de publicá-lo
+pub fn validate_code(code: &[u8]) -> Result<(), String> {
+    check_code_size(code)?;
+    let engine = Engine::default();
+    Module::new(&engine, code)
+        .map(|_| ())
+        .map_err(|erro| format!("Bytecode do contrato inválido: {}", erro))
+}
+
+pub fn deploy(storage: &mut dyn Storage, contract: &str, code: &[u8]) -> Result<(), String> {
+    if get_code(storage, contract).is_some() {
+        return Err(String::from("Contrato já existe"));
+    }
+    validate_code(code)?;
+    storage.set(code_key(contract).as_bytes(), code);
+    Ok(())
+}
+
+// Preço do gas precisa ser finito e não negativo: um preço negativo creditaria o remetente
+pub fn check_gas_price(gas_price: f64) -> Result<(), String> {
+    if gas_price.is_finite() && gas_price >= 0.0 {
+        Ok(())
+    } else {
+        Err(format!("Preço do gas inválido: {}", gas_price))
+    }
+}
+
+impl Execution {
+    pub fn is_success(&self) -> bool {
+        self.status == ExecutionStatus::Success
+    }
+
+    // Execução com sucesso sem contrato executado, apenas com o gas consumido
+    pub fn succeeded(gas_used: u64) -> Self {
+        Execution {
+            gas_used,
+            ..Default::default()
+        }
+    }
+
+    pub fn failed(erro: String, gas_used: u64) -> Self {
+        Execution {
+            status: ExecutionStatus::Failed(erro),
+            gas_used,
+            ..Default::default()
+        }
+    }
+}
+
+/* Executa a função `function` do contrato com no máximo `gas_limit` de gas,
+** sem alterar o storage. As alterações são gravadas com `commit`
+*/
+pub fn execute(
+    storage: &dyn Storage,
+    contract: &str,
+    caller: &str,
+    value: f64,
+    function: &str,
+    args: &[i64],
+    gas_limit: u64,
+) -> Execution {
+    let mut gas_left = gas_limit;
+    match run(
+        storage,
+        contract,
+        caller,
+        value,
+        function,
+        args,
+        &mut gas_left,
+    ) {
+        Ok(execution) => execution,
+        Err(erro) => Execution::failed(erro, gas_limit - gas_left),
+    }
+}
+
+fn run(
+    storage: &dyn Storage,
+    contract: &str,
+    caller: &str,
+    value: f64,
+    function: &str,
+    args: &[i64],
+    gas_left: &mut u64,
+) -> Result<Execution, String> {
+    let gas_limit = *gas_left;
+    let code = get_code(storage, contract).ok_or(String::from("Contrato não encontrado"))?;
+
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, &code[..])
+        .map_err(|erro| format!("Bytecode do contrato inválido: {}", erro))?;
+    let context = ContractContext {
+        storage,
+        contract: contract.to_string(),
+        caller: caller.to_string(),
+        value,
+        changes: BTreeMap::new(),
+        logs: Vec::new(),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_SIZE)
+            .table_elements(MAX_TABLE_ELEMENTS)
+            .instances(1)
+            .memories(1)
+            .tables(1)
+            .build(),
+    };
+    let mut store = Store::new(&engine, context);
+    store.limiter(|context| &mut context.limits);
+    store.set_fuel(gas_limit).map_err(|erro| erro.to_string())?;
+    let linker = host_functions(&engine)?;
+
+    let instantiated = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store));
+    *gas_left = store.get_fuel().unwrap_or(0);
+    let instance = match instantiated {
+        Ok(instance) => instance,
+        Err(erro) if erro.as_trap_code() == Some(TrapCode::OutOfFuel) => {
+            return Ok(out_of_gas(gas_limit))
+        }
+        Err(erro) => return Err(format!("Erro ao instanciar contrato: {}", erro)),
+    };
+    let func = instance
+        .get_func(&store, function)
+        .ok_or(format!("Função {} não exportada pelo contrato", function))?;
+
+    // Converte os argumentos para os tipos dos parâmetros da função
+    let ty = func.ty(&store);
+    if ty.params().len() != args.len() {
+        return Err(format!(
+            "Função {} espera {} argumentos",
+            function,
+            ty.params().len()
+        ));
+    }
+    let inputs = ty
+        .params()
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| match param {
+            wasmi::core::ValType::I32 => Ok(Val::I32(*arg as i32)),
+            wasmi::core::ValType::I64 => Ok(Val::I64(*arg)),
+            _ => Err(format!("Tipo de parâmetro não suportado: {:?}", param)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut outputs = ty
+        .results()
+        .iter()
+        .map(|result| Val::default(*result))
+        .collect::<Vec<_>>();
+
+    let called = func.call(&mut store, &inputs, &mut outputs);
+    *gas_left = store.get_fuel().unwrap_or(0);
+    match called {
+        Ok(()) => {}
+        Err(erro) if erro.as_trap_code() == Some(TrapCode::OutOfFuel) => {
+            return Ok(out_of_gas(gas_limit))
+        }
+        Err(erro) => return Err(format!("Erro na execução do contrato: {}", erro)),
+    }
+
+    let result = match outputs.first() {
+        Some(Val::I32(value)) => Some(*value as i64),
+        Some(Val::I64(value)) => Some(*value),
+        _ => None,
+    };
+    let context = store.into_data();
+    Ok(Execution {
+        status: ExecutionStatus::Success,
+        result,
+        gas_used: gas_limit - *gas_left,
+        logs: context.logs,
+        changes: context.changes,
+    })
+}
+
+// Gas esgotado: todo o gas é consumido e nenhuma alteração é mantida
+fn out_of_gas(gas_limit: u64) -> Execution {
+    Execution {
+        status: ExecutionStatus::OutOfGas,
+        gas_used: gas_limit,
+        ..Default::default()
+    }
+}
+
+// Grava no storage as alterações feitas por uma execução com sucesso
+pub fn commit(storage: &mut dyn Storage, contract: &str, execution: &Execution) {
+    if !execution.is_success() {
+        return;
+    }
+    for (key, value) in &execution.changes {
+        let full_key = storage_key(contract, key);
+        match value {
+            Some(value) => storage.set(&full_key, value),
+            None => storage.remove(&full_key),
+        }
+        state::record_change(storage, &full_key, value.as_deref());
+    }
+}
+
+fn memory(caller: &Caller<'_, ContractContext<'_>>) -> Result<Memory, wasmi::Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or(wasmi::Error::new("Contrato não exporta memória"))
+}
+
+fn read_bytes(
+    caller: &Caller<'_, ContractContext<'_>>,
+    ptr: i32,
+    len: i32,
+) -> Result<Vec<u8>, wasmi::Error> {
+    // O tamanho é controlado pelo contrato, nada é alocado antes da checagem dos limites
+    let start = ptr as u32 as usize;
+    let end = start + len as u32 as usize;
+    memory(caller)?
+        .data(caller)
+        .get(start..end)
+        .map(<[u8]>::to_vec)
+        .ok_or(wasmi::Error::new("Acesso fora da memória do contrato"))
+}
+
+// Cobra o gas dos bytes gravados pela função do host, sem gas a execução é interrompida
+fn charge_bytes(
+    caller: &mut Caller<'_, ContractContext<'_>>,
+    bytes: usize,
+) -> Result<(), wasmi::Error> {
+    let fuel = caller
+        .get_fuel()
+        .map_err(|erro| wasmi::Error::new(erro.to_string()))?;
+    let cost = (bytes as u64).saturating_mul(GAS_PER_BYTE);
+    let left = fuel.checked_sub(cost);
+    caller
+        .set_fuel(left.unwrap_or(0))
+        .map_err(|erro| wasmi::Error::new(erro.to_string()))?;
+    match left {
+        Some(_) => Ok(()),
+        None => Err(TrapCode::OutOfFuel.into()),
+    }
+}
+
+fn write_bytes(
+    caller: &mut Caller<'_, ContractContext<'_>>,
+    ptr: i32,
+    data: &[u8],
+) -> Result<(), wasmi::Error> {
+    memory(caller)?
+        .write(caller, ptr as u32 as usize, data)
+        .map_err(|erro| wasmi::Error::new(erro.to_string()))
+}
+
+fn host_caller(
+    mut caller: Caller<'_, ContractContext<'_>>,
+    ptr: i32,
+    cap: i32,
+) -> Result<i32, wasmi::Error> {
+    let address = caller.data().caller.clone().into_bytes();
+    if address.len() <= cap as u32 as usize {
+        write_bytes(&mut caller, ptr, &address)?;
+    }
+    Ok(address.len() as i32)
+}
+
+fn host_value(caller: Caller<'_, ContractContext<'_>>) -> f64 {
+    caller.data().value
+}
+
+fn host_log(
+    mut caller: Caller<'_, ContractContext<'_>>,
+    ptr: i32,
+    len: i32,
+) -> Result<(), wasmi::Error> {
+    let bytes = read_bytes(&caller, ptr, len)?;
+    charge_bytes(&mut caller, bytes.len())?;
+    let message = String::from_utf8_lossy(&bytes).to_string();
+    info!("Log do contrato"; contract = caller.data().contract, message = message);
+    caller.data_mut().logs.push(message);
+    Ok(())
+}
+
+fn host_storage_get(
+    mut caller: Caller<'_, ContractContext<'_>>,
+    key_ptr: i32,
+    key_len: i32,
+    value_ptr: i32,
+    value_cap: i32,
+) -> Result<i32, wasmi::Error> {
+    let key = read_bytes(&caller, key_ptr, key_len)?;
+    let context = caller.data();
+    // Alterações pendentes têm prioridade sobre o valor gravado
+    let value = match context.changes.get(&key) {
+        Some(change) => change.clone(),
+        None => get_storage(context.storage, &context.contract, &key),
+    };
+    match value {
+        Some(value) => {
+            if value.len() <= value_cap as u32 as usize {
+                write_bytes(&mut caller, value_ptr, &value)?;
+            }
+            Ok(value.len() as i32)
+        }
+        None => Ok(-1),
+    }
+}
+
+fn host_storage_set(
+    mut caller: Caller<'_, ContractContext<'_>>,
+    key_ptr: i32,
+    key_len: i32,
+    value_ptr: i32,
+    value_len: i32,
+) -> Result<(), wasmi::Error> {
+    let key = read_bytes(&caller, key_ptr, key_len)?;
+    let value = read_bytes(&caller, value_ptr, value_len)?;
+    charge_bytes(&mut caller, key.len() + value.len())?;
+    caller.data_mut().changes.insert(key, Some(value));
+    Ok(())
+}
+
+fn host_storage_remove(
+    mut caller: Caller<'_, ContractContext<'_>>,
+    key_ptr: i32,
+    key_len: i32,
+) -> Result<(), wasmi::Error> {
+    let key = read_bytes(&caller, key_ptr, key_len)?;
+    caller.data_mut().changes.insert(key, None);
+    Ok(())
+}
+
+fn host_functions<'a>(engine: &Engine) -> Result<Linker<ContractContext<'a>>, String> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap("env", "caller", host_caller)
+        .and_then(|linker| linker.func_wrap("env", "value", host_value))
+        .and_then(|linker| linker.func_wrap("env", "log", host_log))
+        .and_then(|linker| linker.func_wrap("env", "storage_get", host_storage_get))
+        .and_then(|linker| linker.func_wrap("env", "storage_set", host_storage_set))
+        .and_then(|linker| linker.func_wrap("env", "storage_remove", host_storage_remove))
+        .map_err(|erro| format!("Erro ao registrar funções do host: {}", erro))?;
+    Ok(linker)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    // Contador que guarda o total na chave "total" e registra o chamador no log
+    pub(crate) const COUNTER_WAT: &str = r#"
+        (module
+            (import "env" "caller" (func $caller (param i32 i32) (result i32)))
+            (import "env" "log" (func $log (param i32 i32)))
+            (import "env" "storage_get" (func $get (param i32 i32 i32 i32) (result i32)))
+            (import "env" "storage_set" (func $set (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "total")
+            (func (export "add") (param $amount i64) (result i64)
+                (local $total i64)
+                (if (i32.ge_s (call $get (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 8)) (i32.const 0))
+                    (then (local.set $total (i64.load (i32.const 16)))))
+                (local.set $total (i64.add (local.get $total) (local.get $amount)))
+                (i64.store (i32.const 16) (local.get $total))
+                (call $set (i32.const 0) (i32.const 5) (i32.const 16) (i32.const 8))
+                (call $log (i32.const 64) (call $caller (i32.const 64) (i32.const 64)))
+                (local.get $total))
+            (func (export "write") (param $len i32)
+                (call $set (i32.const 0) (i32.const 5) (i32.const 0) (local.get $len)))
+            (func (export "huge_log") (call $log (i32.const 0) (i32.const -1)))
+            (func (export "fail") (unreachable))
+            (func (export "loop") (loop $l (br $l))))
+    "#;
+
+    pub(crate) const GAS_LIMIT: u64 = 100_000;
+
+    pub(crate) fn counter_code() -> Vec<u8> {
+        wat::parse_str(COUNTER_WAT).unwrap()
+    }
+
+    #[test]
+    fn test_contract_address() {
+        let address = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1);
+        assert_eq!(address.len(), 42);
+        assert_eq!(
+            address,
+            contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1)
+        );
+        assert_ne!(
+            address,
+            contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 2)
+        );
+    }
+
+    #[test]
+    fn test_deploy_and_execute() {
+        let mut storage = MemoryStorage::new();
+        let contract = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1);
+        deploy(&mut storage, &contract, &counter_code()).unwrap();
+
+        let execution = execute(&storage, &contract, "0xabc", 0.0, "add", &[5], GAS_LIMIT);
+        assert!(execution.is_success());
+        assert_eq!(execution.result, Some(5));
+        assert!(execution.gas_used > 0 && execution.gas_used < GAS_LIMIT);
+        assert_eq!(execution.logs, vec!["0xabc".to_string()]);
+
+        // Sem commit o armazenamento do contrato não é alterado
+        assert_eq!(get_storage(&storage, &contract, b"total"), None);
+        commit(&mut storage, &contract, &execution);
+        assert_eq!(
+            get_storage(&storage, &contract, b"total"),
+            Some(5i64.to_le_bytes().to_vec())
+        );
+
+        let execution = execute(&storage, &contract, "0xabc", 0.0, "add", &[7], GAS_LIMIT);
+        assert_eq!(execution.result, Some(12));
+    }
+
+    #[test]
+    fn test_execute_errors() {
+        let mut storage = MemoryStorage::new();
+        let contract = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1);
+
+        assert!(deploy(&mut storage, &contract, b"codigo invalido").is_err());
+        assert!(!execute(&storage, &contract, "0xabc", 0.0, "add", &[1], GAS_LIMIT).is_success());
+
+        deploy(&mut storage, &contract, &counter_code()).unwrap();
+        assert!(deploy(&mut storage, &contract, &counter_code()).is_err());
+        for (function, args) in [("fail", vec![]), ("inexistente", vec![]), ("add", vec![])] {
+            let execution = execute(
+                &storage, &contract, "0xabc", 0.0, function, &args, GAS_LIMIT,
+            );
+            assert!(matches!(execution.status, ExecutionStatus::Failed(_)));
+        }
+    }
+
+    #[test]
+    fn test_out_of_gas() {
+        let mut storage = MemoryStorage::new();
+        let contract = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1);
+        deploy(&mut storage, &contract, &counter_code()).unwrap();
+
+        // Loop infinito consome todo o gas
+        let execution = execute(&storage, &contract, "0xabc", 0.0, "loop", &[], GAS_LIMIT);
+        assert_eq!(execution.status, ExecutionStatus::OutOfGas);
+        assert_eq!(execution.gas_used, GAS_LIMIT);
+
+        // Gas insuficiente para terminar a função, as alterações são descartadas
+        let execution = execute(&storage, &contract, "0xabc", 0.0, "add", &[5], 5);
+        assert_eq!(execution.status, ExecutionStatus::OutOfGas);
+        commit(&mut storage, &contract, &execution);
+        assert_eq!(get_storage(&storage, &contract, b"total"), None);
+
+        // A mesma execução consome sempre o mesmo gas
+        let first = execute(&storage, &contract, "0xabc", 0.0, "add", &[5], GAS_LIMIT);
+        let second = execute(&storage, &contract, "0xabc", 0.0, "add", &[5], GAS_LIMIT);
+        assert_eq!(first.gas_used, second.gas_used);
+    }
+
+    #[test]
+    fn test_host_bytes() {
+        let mut storage = MemoryStorage::new();
+        let contract = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1);
+        deploy(&mut storage, &contract, &counter_code()).unwrap();
+
+        // Cada byte gravado cobra GAS_PER_BYTE
+        let small = execute(&storage, &contract, "0xabc", 0.0, "write", &[0], GAS_LIMIT);
+        let large = execute(
+            &storage,
+            &contract,
+            "0xabc",
+            0.0,
+            "write",
+            &[1000],
+            GAS_LIMIT,
+        );
+        assert!(small.is_success() && large.is_success());
+        assert_eq!(large.gas_used - small.gas_used, 1000 * GAS_PER_BYTE);
+        let execution = execute(&storage, &contract, "0xabc", 0.0, "write", &[1000], 5000);
+        assert_eq!(execution.status, ExecutionStatus::OutOfGas);
+
+        // Tamanho maior que a memória falha sem alocar o buffer
+        let execution = execute(
+            &storage,
+            &contract,
+            "0xabc",
+            0.0,
+            "huge_log",
+            &[],
+            GAS_LIMIT,
+        );
+        assert!(matches!(
+            execution.status,
+            ExecutionStatus::Failed(erro) if erro.contains("Acesso fora da memória do contrato")
+        ));
+        let execution = execute(&storage, &contract, "0xabc", 0.0, "write", &[-1], GAS_LIMIT);
+        assert!(matches!(execution.status, ExecutionStatus::Failed(_)));
+    }
+
+    #[test]
+    fn test_execution_limits() {
+        let mut storage = MemoryStorage::new();
+
+        // Memória declarada acima do limite falha na instanciação, sem alocar 4 GiB
+        let huge = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 1);
+        let code = wat::parse_str(r#"(module (memory 65536) (func (export "run")))"#).unwrap();
+        deploy(&mut storage, &huge, &code).unwrap();
+        let execution = execute(&storage, &huge, "0xabc", 0.0, "run", &[], GAS_LIMIT);
+        assert!(matches!(
+            execution.status,
+            ExecutionStatus::Failed(erro) if erro.starts_with("Erro ao instanciar contrato")
+        ));
+
+        // memory.grow acima do limite retorna -1, dentro do limite retorna o tamanho anterior
+        let growing = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 2);
+        let code = wat::parse_str(
+            r#"(module (memory 1)
+                (func (export "grow") (param $pages i32) (result i32)
+                    (memory.grow (local.get $pages))))"#,
+        )
+        .unwrap();
+        deploy(&mut storage, &growing, &code).unwrap();
+        let pages = (MAX_MEMORY_SIZE / (64 * 1024)) as i64;
+        let execution = execute(
+            &storage,
+            &growing,
+            "0xabc",
+            0.0,
+            "grow",
+            &[pages],
+            GAS_LIMIT,
+        );
+        assert_eq!(execution.result, Some(-1));
+        let execution = execute(&storage, &growing, "0xabc", 0.0, "grow", &[15], GAS_LIMIT);
+        assert_eq!(execution.result, Some(1));
+
+        // Tabela acima do limite
+        let table = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 3);
+        let code =
+            wat::parse_str(r#"(module (table 100000 funcref) (func (export "run")))"#).unwrap();
+        deploy(&mut storage, &table, &code).unwrap();
+        let execution = execute(&storage, &table, "0xabc", 0.0, "run", &[], GAS_LIMIT);
+        assert!(!execution.is_success());
+
+        // Bytecode acima do tamanho máximo é recusado
+        let large = vec![0u8; MAX_CODE_SIZE + 1];
+        let other = contract_address("0xEf8801eaf234ff82801821FFe2d780237F9967", 4);
+        assert_eq!(
+            deploy(&mut storage, &other, &large),
+            Err(format!(
+                "Bytecode do contrato com {} bytes, máximo {}",
+                MAX_CODE_SIZE + 1,
+                MAX_CODE_SIZE
+            ))
+        );
+        assert_eq!(
+            deploy_gas(&counter_code()),
+            counter_code().len() as u64 * DEPLOY_GAS_PER_BYTE
+        );
+    }
+}
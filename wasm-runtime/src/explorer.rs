@@ -0,0 +1,550 @@
+/* Mod Explorer
+** - Explorador de blocos em HTML servido pelo próprio nó, escuta apenas em
+**   localhost, sem arquivos nem serviços externos
+** - Páginas (GET):
+**    - /: blocos mais recentes
+**    - /block/<id ou hash>: cabeçalho e transações do bloco
+**    - /tx/<id>: transação pendente ou incluída em um bloco, com o recibo
+**    - /account/<endereço>?cursor=<posição>: saldo e histórico paginado de
+**      transferências do endereço (mod index)
+** - Todas as páginas exibem no topo o resultado da validação da cadeia, guardado
+**   e calculado novamente apenas quando a altura ou o último bloco mudam
+** - No máximo MAX_CONNECTIONS conexões atendidas ao mesmo tempo, as demais
+**   recebem 503
+** - Os valores vindos da cadeia são escapados antes de entrar no HTML
+*/
+use crate::{
+    block::Block,
+    blockchain::Blockchain,
+    hash::Hash,
+    http::{self, ConnectionSlot},
+    index::Direction,
+    info,
+    transaction::{Transaction, TransactionKind},
+    warn,
+};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    io::BufReader,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{atomic::AtomicUsize, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+// Quantidade de blocos da página inicial
+const RECENT_BLOCKS: usize = 20;
+// Quantidade de transferências por página do histórico
+const HISTORY_PAGE_SIZE: usize = 20;
+// Conexões atendidas ao mesmo tempo, uma thread por conexão
+const MAX_CONNECTIONS: usize = 16;
+// Tempo máximo de leitura e escrita de uma conexão
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct ExplorerConfig {
+    // Endereço de escuta, precisa ser um endereço de loopback
+    pub listen_addr: SocketAddr,
+}
+
+impl Default for ExplorerConfig {
+    fn default() -> Self {
+        ExplorerConfig {
+            listen_addr: "127.0.0.1:8547".parse().unwrap(),
+        }
+    }
+}
+
+pub struct ExplorerServer {
+    local_addr: SocketAddr,
+}
+
+impl ExplorerServer {
+    // Inicia o servidor: aceita conexões em uma thread, uma thread por conexão até MAX_CONNECTIONS
+    pub fn start(
+        config: ExplorerConfig,
+        blockchain: Arc<Mutex<Blockchain>>,
+    ) -> Result<ExplorerServer, String> {
+        if !config.listen_addr.ip().is_loopback() {
+            return Err(String::from(
+                "O explorador de blocos só pode escutar em localhost",
+            ));
+        }
+        let listener = TcpListener::bind(config.listen_addr).map_err(|erro| erro.to_string())?;
+        let local_addr = listener.local_addr().map_err(|erro| erro.to_string())?;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let validity = Arc::new(ValidityCache::default());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                if stream
+                    .set_read_timeout(Some(CONNECTION_TIMEOUT))
+                    .and_then(|_| stream.set_write_timeout(Some(CONNECTION_TIMEOUT)))
+                    .is_err()
+                {
+                    continue;
+                }
+                let Some(slot) = ConnectionSlot::acquire(&connections, MAX_CONNECTIONS) else {
+                    let _ = http::write_response(
+                        &mut stream,
+                        "503 Service Unavailable",
+                        "text/plain",
+                        b"Servidor ocupado",
+                    );
+                    continue;
+                };
+                let blockchain = blockchain.clone();
+                let validity = validity.clone();
+                thread::spawn(move || {
+                    if let Err(erro) = handle_connection(stream, &blockchain, &validity) {
+                        warn!("Erro na conexão do explorador"; erro = erro);
+                    }
+                    drop(slot);
+                });
+            }
+        });
+
+        info!("Explorador de blocos iniciado"; addr = local_addr);
+        Ok(ExplorerServer { local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/* Resultado da validação da cadeia pela altura e hash do último bloco.
+** Validar a cadeia percorre todos os blocos, o resultado é reaproveitado
+** entre as páginas enquanto a cadeia não muda
+* */
+#[derive(Default)]
+pub struct ValidityCache(Mutex<Option<(u64, Hash, bool)>>);
+
+impl ValidityCache {
+    pub fn is_chain_valid(&self, blockchain: &Blockchain) -> bool {
+        let (height, hash) = (blockchain.height(), blockchain.last_block().hash);
+        let mut cached = self.0.lock().unwrap();
+        match *cached {
+            Some((cached_height, cached_hash, valid))
+                if (cached_height, cached_hash) == (height, hash) =>
+            {
+                valid
+            }
+            _ => {
+                let valid = blockchain.validate_chain().is_ok();
+                *cached = Some((height, hash, valid));
+                valid
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    blockchain: &Mutex<Blockchain>,
+    validity: &ValidityCache,
+) -> Result<(), String> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(|erro| erro.to_string())?);
+    let request = http::read_request(&mut reader)?;
+    if request.method != "GET" {
+        return http::write_response(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Utilize GET",
+        );
+    }
+
+    let (status, body) = render(&blockchain.lock().unwrap(), validity, &request.path);
+    http::write_response(
+        &mut stream,
+        status,
+        "text/html; charset=utf-8",
+        body.as_bytes(),
+    )
+}
+
+// Página do caminho requisitado e o status HTTP
+pub fn render(
+    blockchain: &Blockchain,
+    validity: &ValidityCache,
+    path: &str,
+) -> (&'static str, String) {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    let content = match segments.as_slice() {
+        [] => Some(recent_blocks(blockchain)),
+        ["block", id] => block_page(blockchain, id),
+        ["tx", id] => id
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| transaction_page(blockchain, id)),
+        ["account", address] => {
+            let cursor = query_param(query, "cursor").and_then(|cursor| cursor.parse().ok());
+            Some(account_page(blockchain, address, cursor))
+        }
+        _ => None,
+    };
+    let valid = validity.is_chain_valid(blockchain);
+    match content {
+        Some((title, content)) => ("200 OK", layout(blockchain, valid, &title, &content)),
+        None => (
+            "404 Not Found",
+            layout(
+                blockchain,
+                valid,
+                "Não encontrado",
+                "<p>Página não encontrada</p>",
+            ),
+        ),
+    }
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+// Escapa os caracteres especiais do HTML
+pub fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => String::from("&amp;"),
+            '<' => String::from("&lt;"),
+            '>' => String::from("&gt;"),
+            '"' => String::from("&quot;"),
+            '\'' => String::from("&#39;"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+// Estrutura comum das páginas com o resultado da validação da cadeia
+fn layout(blockchain: &Blockchain, valid: bool, title: &str, content: &str) -> String {
+    let banner = if valid {
+        "<p class=\"valid\">Cadeia válida</p>"
+    } else {
+        "<p class=\"invalid\">Cadeia inválida</p>"
+    };
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>body{{font-family:monospace;margin:2em}}td,th{{padding:0 1em;text-align:left}}\
+         .valid{{background:#cfc;padding:.5em}}.invalid{{background:#fcc;padding:.5em}}</style>\
+         </head><body><h1><a href=\"/\">Explorador</a></h1>{banner}\
+         <p>Altura {height}</p><h2>{title}</h2>{content}</body></html>",
+        title = escape(title),
+        banner = banner,
+        height = blockchain.height(),
+        content = content,
+    )
+}
+
+fn block_link(id: u64) -> String {
+    format!("<a href=\"/block/{id}\">{id}</a>", id = id)
+}
+
+fn transaction_link(id: u64) -> String {
+    format!("<a href=\"/tx/{id}\">{id}</a>", id = id)
+}
+
+fn account_link(address: &str) -> String {
+    if address.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<a href=\"/account/{address}\">{address}</a>",
+        address = escape(address)
+    )
+}
+
+fn recent_blocks(blockchain: &Blockchain) -> (String, String) {
+    let mut rows = String::new();
+    for block in blockchain.chain.iter().rev().take(RECENT_BLOCKS) {
+        let _ = write!(
+            rows,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            block_link(block.id),
+            block.timestamp,
+            block.transactions.len(),
+            block.hash
+        );
+    }
+    (
+        String::from("Blocos recentes"),
+        format!(
+            "<table><tr><th>Bloco</th><th>Timestamp</th><th>Transações</th><th>Hash</th></tr>{}</table>",
+            rows
+        ),
+    )
+}
+
+fn block_page(blockchain: &Blockchain, id: &str) -> Option<(String, String)> {
+    let block = match id.parse::<u64>() {
+        Ok(id) => blockchain.block_by_id(id),
+        Err(_) => id
+            .parse::<Hash>()
+            .ok()
+            .and_then(|hash| blockchain.block_by_hash(&hash)),
+    }?;
+    let mut content = format!(
+        "<table><tr><th>Id</th><td>{}</td></tr><tr><th>Timestamp</th><td>{}</td></tr>\
+         <tr><th>Hash</th><td>{}</td></tr><tr><th>Bloco anterior</th><td>{}</td></tr>\
+         <tr><th>Raiz do estado</th><td>{}</td></tr><tr><th>Raiz dos recibos</th><td>{}</td></tr>\
+         <tr><th>Produtor</th><td>{}</td></tr></table>",
+        block.id,
+        block.timestamp,
+        block.hash,
+        previous_link(blockchain, block),
+        escape(&block.state_root),
+        escape(&block.receipts_root),
+        escape(&block.producer),
+    );
+    content.push_str(&transactions_table(&block.transactions));
+    Some((format!("Bloco {}", block.id), content))
+}
+
+fn previous_link(blockchain: &Blockchain, block: &Block) -> String {
+    match block.id.checked_sub(1) {
+        Some(id) if blockchain.block_by_id(id).is_some() => format!(
+            "<a href=\"/block/{}\">{}</a>",
+            id, block.hash_previous_block
+        ),
+        _ => block.hash_previous_block.to_string(),
+    }
+}
+
+fn transactions_table(transactions: &HashMap<u64, Transaction>) -> String {
+    let mut ordered = transactions.iter().collect::<Vec<_>>();
+    ordered.sort_by_key(|(id, _)| **id);
+    let mut rows = String::new();
+    for (id, transaction) in ordered {
+        let _ = write!(
+            rows,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            transaction_link(*id),
+            kind_name(&transaction.kind),
+            account_link(&transaction.from),
+            account_link(&transaction.to),
+            transaction.value
+        );
+    }
+    format!(
+        "<h3>Transações</h3><table><tr><th>Id</th><th>Tipo</th><th>Origem</th><th>Destino</th>\
+         <th>Valor</th></tr>{}</table>",
+        rows
+    )
+}
+
+fn kind_name(kind: &TransactionKind) -> &'static str {
+    match kind {
+        TransactionKind::Transfer => "Transfer",
+        TransactionKind::Deploy { .. } => "Deploy",
+        TransactionKind::Call { .. } => "Call",
+        TransactionKind::RegisterMultisig { .. } => "RegisterMultisig",
+        TransactionKind::Spend { .. } => "Spend",
+        TransactionKind::Governance { .. } => "Governance",
+        TransactionKind::Bond { .. } => "Bond",
+        TransactionKind::Unbond { .. } => "Unbond",
+        TransactionKind::Evidence { .. } => "Evidence",
+    }
+}
+
+fn transaction_page(blockchain: &Blockchain, id: u64) -> Option<(String, String)> {
+    let (status, transaction) = match blockchain.pending_transactions.get(&id) {
+        Some(transaction) => (String::from("pendente"), transaction),
+        None => {
+            let (block, transaction) = blockchain.find_transaction(id)?;
+            (
+                format!("incluída no bloco {}", block_link(block.id)),
+                transaction,
+            )
+        }
+    };
+    let mut content = format!(
+        "<table><tr><th>Status</th><td>{}</td></tr><tr><th>Tipo</th><td>{}</td></tr>\
+         <tr><th>Origem</th><td>{}</td></tr><tr><th>Destino</th><td>{}</td></tr>\
+         <tr><th>Valor</th><td>{}</td></tr></table>",
+        status,
+        kind_name(&transaction.kind),
+        account_link(&transaction.from),
+        account_link(&transaction.to),
+        transaction.value,
+    );
+    if let Some(receipt) = blockchain.receipt(id) {
+        let json = serde_json::to_string_pretty(&receipt).unwrap_or_default();
+        let _ = write!(content, "<h3>Recibo</h3><pre>{}</pre>", escape(&json));
+    }
+    Some((format!("Transação {}", id), content))
+}
+
+fn account_page(blockchain: &Blockchain, address: &str, cursor: Option<u64>) -> (String, String) {
+    let page = blockchain.history_page(address, cursor, HISTORY_PAGE_SIZE);
+    let mut rows = String::new();
+    for transfer in &page.transfers {
+        let direction = match transfer.direction {
+            Direction::Incoming => "entrada",
+            Direction::Outgoing => "saída",
+        };
+        let _ = write!(
+            rows,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            transaction_link(transfer.transaction_id),
+            block_link(transfer.block_id),
+            direction,
+            account_link(&transfer.counterparty),
+            transfer.value
+        );
+    }
+    let mut content = format!(
+        "<p>Saldo {}</p><h3>Histórico</h3><table><tr><th>Transação</th><th>Bloco</th>\
+         <th>Direção</th><th>Outra parte</th><th>Valor</th></tr>{}</table>",
+        blockchain.balance(address),
+        rows
+    );
+    if let Some(next) = page.next_cursor {
+        let _ = write!(
+            content,
+            "<p><a href=\"/account/{}?cursor={}\">Próxima página</a></p>",
+            escape(address),
+            next
+        );
+    }
+    (format!("Conta {}", address), content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::tests::test_account;
+    use std::{
+        io::{Read, Write},
+        sync::atomic::Ordering,
+    };
+
+    fn get(addr: SocketAddr, path: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[test]
+    fn test_explorer_pages() {
+        let mut blockchain = Blockchain::new();
+        let (key, _) = test_account(101);
+        let (recipient_key, recipient) = test_account(102);
+        for value in 1..=5 {
+            blockchain.send_transaction(&key, &recipient, value as f64);
+        }
+        blockchain.send_transaction(&recipient_key, "<script>", 1.0);
+        let hash = blockchain.chain[1].hash;
+        let blockchain = Arc::new(Mutex::new(blockchain));
+        let config = ExplorerConfig {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+        };
+        let addr = ExplorerServer::start(config, blockchain.clone())
+            .unwrap()
+            .local_addr();
+
+        let (status, body) = get(addr, "/");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("Cadeia válida"));
+        assert!(body.contains("<a href=\"/block/1\">1</a>"));
+
+        let (status, body) = get(addr, &format!("/block/{}", hash));
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("Bloco 1"));
+        assert!(body.contains("<a href=\"/tx/5\">5</a>"));
+        assert_eq!(get(addr, "/block/1").1, body);
+
+        let (_, body) = get(addr, "/tx/1");
+        assert!(body.contains("incluída no bloco"));
+        assert!(body.contains("Recibo"));
+        let (_, body) = get(addr, "/tx/6");
+        assert!(body.contains("pendente"));
+        assert!(body.contains("&lt;script&gt;"));
+        assert!(!body.contains("<script>"));
+
+        let (_, body) = get(addr, &format!("/account/{}", recipient));
+        assert!(body.contains("Saldo 15"));
+        assert!(body.contains("entrada"));
+
+        assert_eq!(get(addr, "/block/9").0, "HTTP/1.1 404 Not Found");
+        assert_eq!(get(addr, "/tx/abc").0, "HTTP/1.1 404 Not Found");
+
+        // O resultado da validação é guardado até a cadeia mudar, então a cadeia
+        // corrompida aparece no topo de todas as páginas
+        let mut chain = blockchain.lock().unwrap();
+        chain.corrupt_block(1, 1, 100.0).unwrap();
+        for value in 1..=4 {
+            chain.send_transaction(&key, &recipient, value as f64);
+        }
+        assert_eq!(chain.height(), 2);
+        drop(chain);
+        assert!(get(addr, "/").1.contains("Cadeia inválida"));
+        assert!(get(addr, "/tx/1").1.contains("Cadeia inválida"));
+    }
+
+    #[test]
+    fn test_validity_cache() {
+        let mut blockchain = Blockchain::new();
+        let (key, _) = test_account(101);
+        for value in 1..=5 {
+            blockchain.send_transaction(&key, "0x2", value as f64);
+        }
+        let validity = ValidityCache::default();
+        assert!(render(&blockchain, &validity, "/")
+            .1
+            .contains("Cadeia válida"));
+
+        // Sem um novo bloco o resultado guardado é reaproveitado
+        blockchain.corrupt_block(1, 1, 100.0).unwrap();
+        assert!(render(&blockchain, &validity, "/")
+            .1
+            .contains("Cadeia válida"));
+        assert!(!ValidityCache::default().is_chain_valid(&blockchain));
+        for value in 1..=5 {
+            blockchain.send_transaction(&key, "0x2", value as f64);
+        }
+        assert!(render(&blockchain, &validity, "/")
+            .1
+            .contains("Cadeia inválida"));
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let slots = (0..MAX_CONNECTIONS)
+            .map(|_| ConnectionSlot::acquire(&connections, MAX_CONNECTIONS).unwrap())
+            .collect::<Vec<_>>();
+        assert!(ConnectionSlot::acquire(&connections, MAX_CONNECTIONS).is_none());
+        drop(slots);
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+        assert!(ConnectionSlot::acquire(&connections, MAX_CONNECTIONS).is_some());
+    }
+
+    #[test]
+    fn test_account_pagination() {
+        let mut blockchain = Blockchain::new();
+        let (key, _) = test_account(101);
+        for _ in 0..(HISTORY_PAGE_SIZE + 5) {
+            blockchain.send_transaction(&key, "0x2", 1.0);
+        }
+        let validity = ValidityCache::default();
+        let (_, first) = render(&blockchain, &validity, "/account/0x2");
+        let next = format!("/account/0x2?cursor={}", HISTORY_PAGE_SIZE);
+        assert!(first.contains(&next));
+        let (_, second) = render(&blockchain, &validity, &next);
+        assert!(!second.contains("Próxima página"));
+        assert_eq!(second.matches("<a href=\"/tx/").count(), 5);
+    }
+}
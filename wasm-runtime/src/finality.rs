@@ -0,0 +1,239 @@
+/* Mod Finality
+** - Camada de finalidade BFT sobre o consenso dos validadores (prova de
+**   autoridade ou de participação)
+** - Checkpoints: blocos com id múltiplo de `checkpoint_interval` (genesis)
+** - Pré-commits: votos assinados pelos validadores na altura e na hash de um
+**   checkpoint da cadeia, propagados pela rede (mod network). O peso de cada
+**   validador vem do consenso (`Consensus::voting_power`): a participação na
+**   prova de participação, peso 1 na prova de autoridade
+** - O checkpoint fica final quando os pré-commits somam mais de 2/3 do peso.
+**   Blocos finalizados nunca são reorganizados e `is_chain_valid` não checa
+**   novamente os corpos dos blocos até o último checkpoint finalizado
+** - Os votos e o último checkpoint finalizado ficam no Storage fora do estado
+**   dos blocos: desfazer um bloco não desfaz a finalidade
+** - Segurança: cada validador vota em uma única hash por altura. A maior altura
+**   já assinada pela chave local fica gravada e o nó nunca assina outra hash
+**   nessa altura ou abaixo dela, mesmo depois de reiniciar; um segundo voto
+**   de um validador em outra hash da mesma altura é recusado e reportado
+*/
+use crate::{
+    hash::Hash,
+    storage::{encode_hex, load, save, Storage},
+    transaction::TransactionSignature,
+    wallet, warn,
+};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const FINALIZED_KEY: &str = "finality/finalized";
+
+fn votes_key(block_id: u64) -> String {
+    format!("finality/votes/{}", block_id)
+}
+
+fn signed_key(validator: &str) -> String {
+    format!("finality/signed/{}", validator)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: u64,
+    pub hash: Hash,
+}
+
+// Pré-commit do validador `validator` (chave pública) no checkpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Precommit {
+    pub block_id: u64,
+    pub block_hash: Hash,
+    pub validator: String,
+    pub signature: String,
+}
+
+impl Precommit {
+    pub fn sign(key: &SigningKey, checkpoint: &Checkpoint) -> Self {
+        let signature = wallet::sign_hash(key, &signing_hash(checkpoint));
+        Precommit {
+            block_id: checkpoint.id,
+            block_hash: checkpoint.hash,
+            validator: signature.public_key,
+            signature: signature.signature,
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            id: self.block_id,
+            hash: self.block_hash,
+        }
+    }
+
+    pub fn verify(&self) -> Result<(), String> {
+        let signature = TransactionSignature {
+            public_key: self.validator.clone(),
+            signature: self.signature.clone(),
+        };
+        wallet::verify_signature(&signature, &signing_hash(&self.checkpoint()))
+            .map_err(|_| String::from("Assinatura do pré-commit inválida"))
+    }
+}
+
+// Mensagem assinada: altura e hash do checkpoint
+fn signing_hash(checkpoint: &Checkpoint) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"precommit");
+    hasher.update(checkpoint.id.to_be_bytes());
+    hasher.update(checkpoint.hash.as_bytes());
+    hasher.finalize().into()
+}
+
+pub fn is_checkpoint(block_id: u64, interval: u64) -> bool {
+    block_id > 0 && interval > 0 && block_id.is_multiple_of(interval)
+}
+
+// Último checkpoint finalizado, None quando nenhum foi finalizado
+pub fn finalized(storage: &dyn Storage) -> Option<Checkpoint> {
+    load(storage, FINALIZED_KEY)
+}
+
+pub fn votes(storage: &dyn Storage, block_id: u64) -> Vec<Precommit> {
+    load(storage, &votes_key(block_id)).unwrap_or_default()
+}
+
+/* Checa o pré-commit contra os votos já registrados na altura: o voto repetido
+** e o voto duplo (outra hash do mesmo validador) são recusados, o voto duplo
+** é reportado no log com as duas hashes
+*/
+pub fn check_vote(storage: &dyn Storage, vote: &Precommit) -> Result<(), String> {
+    let votes = votes(storage, vote.block_id);
+    let Some(other) = votes.iter().find(|other| other.validator == vote.validator) else {
+        return Ok(());
+    };
+    if other.block_hash == vote.block_hash {
+        return Err(String::from("Pré-commit repetido"));
+    }
+    warn!(
+        "Pré-commit duplo";
+        validator = vote.validator,
+        id = vote.block_id,
+        first = other.block_hash,
+        second = vote.block_hash
+    );
+    Err(format!(
+        "Pré-commit duplo do validador na altura {}",
+        vote.block_id
+    ))
+}
+
+// Registra o pré-commit, já checado contra a cadeia e o conjunto de votantes
+pub fn add_vote(storage: &mut dyn Storage, vote: &Precommit) -> Result<(), String> {
+    check_vote(storage, vote)?;
+    let mut votes = votes(storage, vote.block_id);
+    votes.push(vote.clone());
+    save(storage, &votes_key(vote.block_id), &votes);
+    Ok(())
+}
+
+// Maior altura já assinada pelo validador neste nó
+pub fn last_signed(storage: &dyn Storage, validator: &str) -> Option<u64> {
+    load(storage, &signed_key(validator))
+}
+
+pub fn set_last_signed(storage: &mut dyn Storage, validator: &str, block_id: u64) {
+    save(storage, &signed_key(validator), &block_id);
+}
+
+// Peso dos pré-commits no checkpoint, um voto por validador do conjunto
+pub fn voted_power(
+    storage: &dyn Storage,
+    checkpoint: &Checkpoint,
+    voting_power: &[(String, f64)],
+) -> f64 {
+    let votes = votes(storage, checkpoint.id);
+    voting_power
+        .iter()
+        .filter(|(validator, _)| {
+            votes
+                .iter()
+                .any(|vote| vote.validator == *validator && vote.block_hash == checkpoint.hash)
+        })
+        .map(|(_, power)| power)
+        .sum()
+}
+
+// Mais de 2/3 do peso total
+pub fn has_supermajority(voted: f64, voting_power: &[(String, f64)]) -> bool {
+    let total = voting_power.iter().map(|(_, power)| power).sum::<f64>();
+    total > 0.0 && voted * 3.0 > total * 2.0
+}
+
+pub fn set_finalized(storage: &mut dyn Storage, checkpoint: &Checkpoint) {
+    save(storage, FINALIZED_KEY, checkpoint);
+}
+
+// Chave pública do validador em hexadecimal
+pub fn public_key(key: &SigningKey) -> String {
+    encode_hex(key.verifying_key().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::MemoryStorage, wallet::keypair_from_seed};
+
+    #[test]
+    fn test_precommits() {
+        let keys = (1..=4)
+            .map(|seed| keypair_from_seed(&[seed; 32]))
+            .collect::<Vec<_>>();
+        let voting_power = keys
+            .iter()
+            .map(|key| (public_key(key), 1.0))
+            .collect::<Vec<_>>();
+        let checkpoint = Checkpoint {
+            id: 10,
+            hash: Hash([1; 32]),
+        };
+        let mut storage = MemoryStorage::new();
+
+        let vote = Precommit::sign(&keys[0], &checkpoint);
+        assert_eq!(vote.verify(), Ok(()));
+        let mut tampered = vote.clone();
+        tampered.block_id = 20;
+        assert!(tampered.verify().is_err());
+
+        // Votos repetidos e em outra hash não contam
+        assert_eq!(add_vote(&mut storage, &vote), Ok(()));
+        assert_eq!(
+            add_vote(&mut storage, &vote),
+            Err(String::from("Pré-commit repetido"))
+        );
+        let other = Checkpoint {
+            id: 10,
+            hash: Hash([2; 32]),
+        };
+        add_vote(&mut storage, &Precommit::sign(&keys[1], &other)).unwrap();
+        add_vote(&mut storage, &Precommit::sign(&keys[2], &checkpoint)).unwrap();
+
+        // Segundo voto do validador em outra hash da mesma altura
+        assert_eq!(
+            add_vote(&mut storage, &Precommit::sign(&keys[0], &other)),
+            Err(String::from("Pré-commit duplo do validador na altura 10"))
+        );
+        assert_eq!(votes(&storage, 10).len(), 3);
+        let voted = voted_power(&storage, &checkpoint, &voting_power);
+        assert_eq!(voted, 2.0);
+        assert!(!has_supermajority(voted, &voting_power));
+
+        // 3 de 4 passa de 2/3
+        add_vote(&mut storage, &Precommit::sign(&keys[3], &checkpoint)).unwrap();
+        let voted = voted_power(&storage, &checkpoint, &voting_power);
+        assert!(has_supermajority(voted, &voting_power));
+        assert!(!has_supermajority(0.0, &[]));
+
+        assert!(is_checkpoint(10, 5));
+        assert!(!is_checkpoint(0, 5));
+        assert!(!is_checkpoint(12, 5));
+    }
+}
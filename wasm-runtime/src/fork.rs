@@ -0,0 +1,159 @@
+/* Mod Fork
+** - Árvore de blocos: guarda os blocos dos ramos laterais, blocos que apontam
+**   para um bloco conhecido mas não fazem parte da cadeia canônica
+** - Os blocos são indexados pela hash, o ramo de um bloco é obtido seguindo
+**   `hash_previous_block` até sair da árvore (ponto de bifurcação na cadeia canônica)
+** - A cadeia canônica é a de maior trabalho acumulado. Como todos os blocos
+**   possuem o mesmo trabalho, é a cadeia mais longa; em caso de empate a cadeia
+**   atual é mantida
+** - A árvore guarda até MAX_SIDE_BLOCKS blocos, ao completar o bloco de menor
+**   altura é descartado. Blocos até o checkpoint finalizado são removidos
+**   (`prune`), esses ramos não podem mais se tornar canônicos
+*/
+use crate::{block::Block, hash::Hash};
+use std::collections::HashMap;
+
+// Quantidade máxima de blocos dos ramos laterais guardados
+pub const MAX_SIDE_BLOCKS: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct BlockTree {
+    blocks: HashMap<Hash, Block>,
+}
+
+impl BlockTree {
+    pub fn new() -> Self {
+        BlockTree::default()
+    }
+
+    pub fn insert(&mut self, block: Block) {
+        if self.blocks.len() >= MAX_SIDE_BLOCKS && !self.blocks.contains_key(&block.hash) {
+            let lowest = self
+                .blocks
+                .values()
+                .min_by_key(|block| block.id)
+                .map(|block| block.hash);
+            if let Some(hash) = lowest {
+                self.blocks.remove(&hash);
+            }
+        }
+        self.blocks.insert(block.hash, block);
+    }
+
+    pub fn get(&self, hash: &Hash) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn remove(&mut self, hash: &Hash) -> Option<Block> {
+        self.blocks.remove(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /* Ramo terminado no bloco `tip`, do bloco mais antigo da árvore até `tip`.
+     ** O bloco anterior ao primeiro bloco do ramo é o ponto de bifurcação
+     */
+    pub fn branch(&self, tip: &Hash) -> Vec<Block> {
+        let mut branch = vec![];
+        let mut hash = tip;
+        while let Some(block) = self.blocks.get(hash) {
+            branch.push(block.clone());
+            hash = &block.hash_previous_block;
+        }
+        branch.reverse();
+        branch
+    }
+
+    // Remove os blocos com id menor que `min_id`
+    pub fn prune(&mut self, min_id: u64) {
+        self.blocks.retain(|_, block| block.id >= min_id);
+    }
+
+    // Remove o bloco e todos os blocos que descendem dele
+    pub fn remove_with_descendants(&mut self, hash: &Hash) {
+        self.blocks.remove(hash);
+        let children = self
+            .blocks
+            .values()
+            .filter(|block| block.hash_previous_block == *hash)
+            .map(|block| block.hash)
+            .collect::<Vec<_>>();
+        for child in children {
+            self.remove_with_descendants(&child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::Sha256Hasher;
+    use std::collections::HashMap;
+
+    fn block(id: u64, previous: Hash) -> Block {
+        Block::new(id, previous, "", "", HashMap::new(), &Sha256Hasher)
+    }
+
+    #[test]
+    fn test_branch() {
+        let mut tree = BlockTree::new();
+        let first = block(1, Hash::ZERO);
+        let second = block(2, first.hash);
+        let third = block(3, second.hash);
+        tree.insert(second.clone());
+        tree.insert(third.clone());
+
+        // O primeiro bloco não está na árvore, é o ponto de bifurcação
+        let branch = tree.branch(&third.hash);
+        assert_eq!(branch.len(), 2);
+        assert_eq!(branch[0].hash, second.hash);
+        assert_eq!(branch[0].hash_previous_block, first.hash);
+        assert!(tree.branch(&first.hash).is_empty());
+    }
+
+    #[test]
+    fn test_remove_with_descendants() {
+        let mut tree = BlockTree::new();
+        let first = block(1, Hash::ZERO);
+        let second = block(2, first.hash);
+        let sibling = block(2, Hash([1; 32]));
+        let other = Block::new(1, Hash::ZERO, "outro", "", HashMap::new(), &Sha256Hasher);
+        for block in [&first, &second, &sibling, &other] {
+            tree.insert(block.clone());
+        }
+
+        tree.remove_with_descendants(&first.hash);
+        assert_eq!(tree.len(), 2);
+        assert!(tree.contains(&sibling.hash));
+        assert!(tree.contains(&other.hash));
+
+        tree.prune(2);
+        assert_eq!(tree.len(), 1);
+        assert!(tree.contains(&sibling.hash));
+    }
+
+    #[test]
+    fn test_bounded_tree() {
+        let mut tree = BlockTree::new();
+        let mut previous = Hash::ZERO;
+        for id in 1..=MAX_SIDE_BLOCKS as u64 + 2 {
+            let block = block(id, previous);
+            previous = block.hash;
+            tree.insert(block);
+        }
+        // Os blocos de menor altura foram descartados
+        assert_eq!(tree.len(), MAX_SIDE_BLOCKS);
+        assert_eq!(tree.blocks.values().map(|block| block.id).min(), Some(3));
+        assert_eq!(tree.branch(&previous).len(), MAX_SIDE_BLOCKS);
+    }
+}
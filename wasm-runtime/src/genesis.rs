@@ -0,0 +1,132 @@
+/* Mod Genesis
+** - Parâmetros da cadeia definidos na criação do bloco genesis, lidos de um
+**   arquivo JSON com `Genesis::from_file`
+** - chain_id: identificação da rede, utilizada no handshake entre os nós
+** - timestamp: timestamp do bloco genesis, o horário atual quando ausente
+** - alloc: saldos iniciais das contas
+** - ledger: modelo das transações, "account" (transferências entre contas,
+**   padrão) ou "utxo" (transações gastam saídas anteriores, mod utxo). No modo
+**   utxo cada saldo inicial vira uma saída da transação 0
+** - consensus: regras de produção dos blocos (mod consensus), `{"type": "open"}`
+**   (padrão, qualquer nó produz blocos), `{"type": "authority", "validators":
+**   [chaves públicas]}` ou `{"type": "stake", "validators": {chave pública:
+**   participação}, ...}` com os parâmetros de `StakeParams` (mod stake)
+** - checkpoint_interval: intervalo dos checkpoints votados pelos validadores
+**   (mod finality), padrão 10
+** - hash_algorithm: função hash dos blocos (mod hash), "sha256" (padrão),
+**   "double_sha256", "keccak256" ou "blake2b"
+** - O genesis é gravado no Storage junto com a cadeia
+** - A hash do bloco genesis é a hash dos parâmetros serializados, com a função
+**   hash do genesis. O handshake entre os nós compara essa hash
+*/
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genesis {
+    pub chain_id: String,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    #[serde(default)]
+    pub alloc: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub ledger: LedgerMode,
+    #[serde(default)]
+    pub consensus: ConsensusParams,
+    #[serde(default = "default_checkpoint_interval")]
+    pub checkpoint_interval: u64,
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+fn default_checkpoint_interval() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerMode {
+    #[default]
+    Account,
+    Utxo,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    DoubleSha256,
+    Keccak256,
+    Blake2b,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ConsensusParams {
+    #[default]
+    Open,
+    // Prova de autoridade, validadores iniciais em ordem de vez
+    Authority {
+        validators: Vec<String>,
+    },
+    // Prova de participação, validadores escolhidos por peso da participação
+    Stake(StakeParams),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StakeParams {
+    // Participação inicial de cada validador (chave pública), vinculada pelo
+    // endereço do próprio validador
+    pub validators: BTreeMap<String, f64>,
+    // Quantidade de blocos de cada época, o conjunto de validadores muda entre épocas
+    pub epoch_length: u64,
+    // Quantidade de blocos até a participação desvinculada voltar ao saldo
+    pub unbonding_period: u64,
+    // Recompensa criada a cada bloco, dividida entre os delegadores do produtor
+    pub block_reward: f64,
+    // Fração da participação removida do validador que assina dois blocos na mesma altura
+    pub slash_fraction: f64,
+}
+
+impl Default for StakeParams {
+    fn default() -> Self {
+        StakeParams {
+            validators: BTreeMap::new(),
+            epoch_length: 10,
+            unbonding_period: 20,
+            block_reward: 1.0,
+            slash_fraction: 0.5,
+        }
+    }
+}
+
+impl Default for Genesis {
+    fn default() -> Self {
+        Genesis {
+            chain_id: String::from("local"),
+            timestamp: None,
+            alloc: BTreeMap::new(),
+            ledger: LedgerMode::Account,
+            consensus: ConsensusParams::Open,
+            checkpoint_interval: default_checkpoint_interval(),
+            hash_algorithm: HashAlgorithm::Sha256,
+        }
+    }
+}
+
+impl Genesis {
+    pub fn from_file(path: &Path) -> Result<Genesis, String> {
+        let data = fs::read(path).map_err(|erro| format!("Erro ao ler o genesis: {}", erro))?;
+        serde_json::from_slice(&data).map_err(|erro| format!("Genesis inválido: {}", erro))
+    }
+
+    /* Cadeias com saldos iniciais ou prova de participação conferem o saldo do
+     ** remetente, sem saldo a transação é recusada. Na cadeia aberta sem saldos
+     ** iniciais não existe emissão de moedas e o saldo pode ficar negativo
+     */
+    pub fn checks_balances(&self) -> bool {
+        !self.alloc.is_empty() || matches!(self.consensus, ConsensusParams::Stake(_))
+    }
+}
@@ -0,0 +1,174 @@
+/* Mod Hash
+** - Hash: valor de 256 bits de tamanho fixo, representado em hexadecimal
+**   minúsculo no JSON e na exibição. `Hash::ZERO` é a hash anterior do bloco
+**   genesis, a hash do genesis é a hash dos parâmetros do genesis (mod genesis)
+** - Trait `Hasher`: função hash utilizada no cálculo da hash dos blocos,
+**   escolhida no genesis (`hash_algorithm`)
+**    - sha256 (padrão)
+**    - double_sha256: sha256 aplicada duas vezes, como no Bitcoin
+**    - keccak256: Keccak-256 original (anterior ao padrão SHA-3), como no Ethereum
+**    - blake2b: BLAKE2b com saída de 256 bits
+*/
+use crate::{
+    genesis::HashAlgorithm,
+    storage::{decode_hex, encode_hex},
+};
+use blake2::{digest::consts::U32, Blake2b};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash(pub [u8; 32]);
+
+impl Hash {
+    pub const ZERO: Hash = Hash([0; 32]);
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.0)
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Hash, String> {
+        let bytes = decode_hex(hex)?;
+        let bytes = <[u8; 32]>::try_from(bytes.as_slice())
+            .map_err(|_| String::from("Hash deve ter 32 bytes"))?;
+        Ok(Hash(bytes))
+    }
+}
+
+impl From<[u8; 32]> for Hash {
+    fn from(bytes: [u8; 32]) -> Self {
+        Hash(bytes)
+    }
+}
+
+impl FromStr for Hash {
+    type Err = String;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        Hash::from_hex(hex)
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({})", self.to_hex())
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Hash::from_hex(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+// Função hash dos blocos
+pub trait Hasher: fmt::Debug + Send {
+    fn hash(&self, data: &[u8]) -> Hash;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    fn hash(&self, data: &[u8]) -> Hash {
+        Hash(Sha256::digest(data).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DoubleSha256Hasher;
+
+impl Hasher for DoubleSha256Hasher {
+    fn hash(&self, data: &[u8]) -> Hash {
+        Hash(Sha256::digest(Sha256::digest(data)).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Keccak256Hasher;
+
+impl Hasher for Keccak256Hasher {
+    fn hash(&self, data: &[u8]) -> Hash {
+        Hash(Keccak256::digest(data).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blake2bHasher;
+
+impl Hasher for Blake2bHasher {
+    fn hash(&self, data: &[u8]) -> Hash {
+        Hash(Blake2b::<U32>::digest(data).into())
+    }
+}
+
+// Função hash escolhida no genesis
+pub fn from_algorithm(algorithm: HashAlgorithm) -> Box<dyn Hasher> {
+    match algorithm {
+        HashAlgorithm::Sha256 => Box::new(Sha256Hasher),
+        HashAlgorithm::DoubleSha256 => Box::new(DoubleSha256Hasher),
+        HashAlgorithm::Keccak256 => Box::new(Keccak256Hasher),
+        HashAlgorithm::Blake2b => Box::new(Blake2bHasher),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashers() {
+        // Vetores de teste conhecidos da entrada "abc"
+        let cases = [
+            (
+                HashAlgorithm::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                HashAlgorithm::DoubleSha256,
+                "4f8b42c22dd3729b519ba6f68d2da7cc5b2d606d05daed5ad5128cc03e6c6358",
+            ),
+            (
+                HashAlgorithm::Keccak256,
+                "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+            ),
+            (
+                HashAlgorithm::Blake2b,
+                "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+            ),
+        ];
+        for (algorithm, expected) in cases {
+            let hash = from_algorithm(algorithm).hash(b"abc");
+            assert_eq!(hash.to_string(), expected, "{:?}", algorithm);
+            assert_eq!(expected.parse::<Hash>(), Ok(hash));
+        }
+
+        let hash = Sha256Hasher.hash(b"abc");
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{}\"", hash));
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+        assert!(Hash::from_hex("abcd").is_err());
+        assert!(Hash::from_hex("0x000000000").is_err());
+        assert!("Invalid_hash".parse::<Hash>().is_err());
+        assert_eq!(Hash::ZERO.to_string(), "0".repeat(64));
+    }
+}
@@ -0,0 +1,219 @@
+/* Mod Hd
+** - Carteira hierárquica determinística: todas as contas são derivadas de uma
+**   frase mnemônica (BIP-39), permitindo recriar as contas a partir da frase
+** - A semente de 64 bytes é obtida da frase e de uma senha opcional (BIP-39)
+** - Derivação das chaves ed25519 com SLIP-0010: apenas filhos endurecidos (')
+** - Conta `n` no caminho m/44'/1'/n'/0'/0', as chaves derivadas são as mesmas
+**   utilizadas em `wallet::sign_transaction` e no keystore
+*/
+use crate::wallet::{derive_address, keypair_from_seed};
+use bip39::{Language, Mnemonic};
+use ed25519_dalek::SigningKey;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha512;
+
+type HmacSha512 = Hmac<Sha512>;
+
+// Tipo de moeda utilizado no caminho das contas (1: redes de teste)
+pub const COIN_TYPE: u32 = 1;
+
+const HARDENED: u32 = 0x8000_0000;
+
+// Nova frase mnemônica em inglês com 12, 15, 18, 21 ou 24 palavras
+pub fn generate_mnemonic(words: usize) -> Result<String, String> {
+    if !(12..=24).contains(&words) || !words.is_multiple_of(3) {
+        return Err(String::from(
+            "A frase deve ter 12, 15, 18, 21 ou 24 palavras",
+        ));
+    }
+    let mut entropy = vec![0u8; words / 3 * 4];
+    rand::thread_rng().fill_bytes(&mut entropy);
+    let mnemonic =
+        Mnemonic::from_entropy_in(Language::English, &entropy).map_err(|erro| erro.to_string())?;
+    Ok(mnemonic.to_string())
+}
+
+// Semente da frase mnemônica, a frase é validada (palavras e checksum)
+pub fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], String> {
+    let mnemonic = Mnemonic::parse_in_normalized(Language::English, phrase)
+        .map_err(|erro| format!("Frase mnemônica inválida: {}", erro))?;
+    Ok(mnemonic.to_seed_normalized(passphrase))
+}
+
+// Chave estendida: chave privada e chain code
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedKey {
+    pub key: [u8; 32],
+    pub chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    pub fn master(seed: &[u8]) -> Self {
+        ExtendedKey::from_hmac(b"ed25519 seed", seed)
+    }
+
+    // Filho endurecido `index` (o bit de endurecimento é adicionado)
+    pub fn derive_child(&self, index: u32) -> Self {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&self.key);
+        data.extend_from_slice(&(index | HARDENED).to_be_bytes());
+        ExtendedKey::from_hmac(&self.chain_code, &data)
+    }
+
+    // Caminho no formato m/44'/1'/0', todos os níveis precisam ser endurecidos
+    pub fn derive_path(&self, path: &str) -> Result<Self, String> {
+        let mut levels = path.split('/');
+        if levels.next() != Some("m") {
+            return Err(format!("Caminho inválido: {}", path));
+        }
+        levels.try_fold(self.clone(), |key, level| {
+            let index = level
+                .strip_suffix('\'')
+                .and_then(|index| index.parse::<u32>().ok())
+                .filter(|index| *index < HARDENED)
+                .ok_or_else(|| {
+                    format!(
+                        "Nível inválido no caminho, use índices endurecidos: {}",
+                        level
+                    )
+                })?;
+            Ok(key.derive_child(index))
+        })
+    }
+
+    pub fn signing_key(&self) -> SigningKey {
+        keypair_from_seed(&self.key)
+    }
+
+    fn from_hmac(key: &[u8], data: &[u8]) -> Self {
+        let mut mac =
+            HmacSha512::new_from_slice(key).expect("HMAC aceita chaves de qualquer tamanho");
+        mac.update(data);
+        let result = mac.finalize().into_bytes();
+        ExtendedKey {
+            key: result[..32].try_into().unwrap(),
+            chain_code: result[32..].try_into().unwrap(),
+        }
+    }
+}
+
+pub fn account_path(account: u32) -> String {
+    format!("m/44'/{}'/{}'/0'/0'", COIN_TYPE, account)
+}
+
+// Carteira com as contas derivadas da semente
+#[derive(Debug, Clone)]
+pub struct HdWallet {
+    master: ExtendedKey,
+}
+
+impl HdWallet {
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, String> {
+        Ok(HdWallet::from_seed(&mnemonic_to_seed(phrase, passphrase)?))
+    }
+
+    pub fn from_seed(seed: &[u8]) -> Self {
+        HdWallet {
+            master: ExtendedKey::master(seed),
+        }
+    }
+
+    pub fn account(&self, account: u32) -> SigningKey {
+        self.master
+            .derive_path(&account_path(account))
+            .expect("Caminho da conta válido")
+            .signing_key()
+    }
+
+    pub fn address(&self, account: u32) -> String {
+        derive_address(&self.account(account).verifying_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        storage::{decode_hex, encode_hex},
+        transaction::Transaction,
+        wallet::{sign_transaction, verify_transaction},
+    };
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_bip39_vector() {
+        // Vetor de teste do BIP-39, entropia zero e senha "TREZOR"
+        let seed = mnemonic_to_seed(PHRASE, "TREZOR").unwrap();
+        assert_eq!(
+            encode_hex(&seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+
+        // Checksum inválido
+        let invalid = PHRASE.replace("about", "abandon");
+        assert!(mnemonic_to_seed(&invalid, "").is_err());
+    }
+
+    #[test]
+    fn test_slip10_vector() {
+        // Vetor de teste 1 do SLIP-0010 para ed25519
+        let seed = decode_hex("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedKey::master(&seed);
+        assert_eq!(
+            encode_hex(&master.key),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            encode_hex(&master.chain_code),
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+        );
+
+        let child = master.derive_path("m/0'/1'").unwrap();
+        assert_eq!(
+            encode_hex(&child.key),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+        assert_eq!(
+            encode_hex(&child.chain_code),
+            "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14"
+        );
+        assert_eq!(child, master.derive_child(0).derive_child(1));
+
+        // Apenas derivação endurecida
+        assert!(master.derive_path("m/0").is_err());
+        assert!(master.derive_path("0'").is_err());
+    }
+
+    #[test]
+    fn test_hd_wallet_accounts() {
+        let wallet = HdWallet::from_mnemonic(PHRASE, "").unwrap();
+        assert_eq!(
+            wallet.address(0),
+            "0x2243ac3a24188b27e7fda3e382f960adf193dcf9"
+        );
+        assert_eq!(
+            wallet.address(1),
+            "0xd8885978876512c8dce0d4ff333588492404406e"
+        );
+
+        // A mesma frase recria as mesmas contas
+        let recovered = HdWallet::from_mnemonic(PHRASE, "").unwrap();
+        assert_eq!(recovered.address(1), wallet.address(1));
+
+        // As contas derivadas assinam transações normalmente
+        let mut transaction = Transaction::new(&wallet.address(1), "0x0", 1.0);
+        sign_transaction(&wallet.account(1), &mut transaction).unwrap();
+        assert_eq!(verify_transaction(&transaction), Ok(()));
+    }
+
+    #[test]
+    fn test_generate_mnemonic() {
+        let phrase = generate_mnemonic(24).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+        assert!(mnemonic_to_seed(&phrase, "").is_ok());
+        assert_ne!(phrase, generate_mnemonic(24).unwrap());
+        assert!(generate_mnemonic(13).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+/* Mod Http
+** - Leitura e escrita mínimas de HTTP/1.1 sobre TCP, utilizadas pelos servidores
+**   do nó (JSON-RPC)
+** - Uma requisição por conexão: a resposta sempre é enviada com `Connection: close`
+** - Linhas e quantidade de cabeçalhos limitadas (MAX_LINE_SIZE, MAX_HEADERS)
+** - `check_origin`: o cabeçalho Host precisa ser o endereço de escuta (localhost
+**   ou o ip, com a porta do servidor) e pedidos de outra origem (cabeçalho Origin
+**   diferente) são recusados, protegendo contra CSRF e DNS rebinding
+** - `ConnectionSlot`: limite de conexões atendidas ao mesmo tempo, utilizado
+**   pelo explorador e pelas conexões de entrada da rede
+*/
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// Tamanho máximo aceito para o corpo da requisição
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+// Tamanho máximo da linha da requisição e de cada cabeçalho
+const MAX_LINE_SIZE: u64 = 8 * 1024;
+
+// Quantidade máxima de cabeçalhos
+const MAX_HEADERS: usize = 64;
+
+// Vaga de uma conexão em atendimento, liberada quando a thread da conexão termina
+pub(crate) struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    // Ocupa uma vaga, None se já existem `max` conexões em atendimento
+    pub(crate) fn acquire(connections: &Arc<AtomicUsize>, max: usize) -> Option<ConnectionSlot> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    // Nomes dos cabeçalhos em minúsculas
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+// Lê a linha da requisição, os cabeçalhos e o corpo (indicado por Content-Length)
+pub fn read_request(reader: &mut BufReader<TcpStream>) -> Result<HttpRequest, String> {
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(String::from("Requisição HTTP inválida")),
+    };
+
+    let mut headers = HashMap::new();
+    for count in 0.. {
+        let mut line = String::new();
+        let read = read_line(reader, &mut line)?;
+        let line = line.trim_end();
+        if read == 0 || line.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(String::from("Cabeçalhos HTTP demais"));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = match headers.get("content-length") {
+        Some(length) => length
+            .parse::<usize>()
+            .map_err(|_| String::from("Content-Length inválido"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(String::from("Corpo da requisição muito grande"));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|erro| erro.to_string())?;
+
+    Ok(HttpRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+// Lê uma linha de até MAX_LINE_SIZE bytes
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<usize, String> {
+    let read = reader
+        .take(MAX_LINE_SIZE + 1)
+        .read_line(line)
+        .map_err(|erro| erro.to_string())?;
+    if read as u64 > MAX_LINE_SIZE {
+        return Err(String::from("Linha HTTP muito grande"));
+    }
+    Ok(read)
+}
+
+/* Checa se a requisição é destinada ao servidor em `local_addr`: o Host precisa
+** ser localhost ou o ip de escuta com a porta do servidor, e o Origin, quando
+** enviado pelo navegador, precisa ser a mesma origem
+*/
+pub fn check_origin(request: &HttpRequest, local_addr: SocketAddr) -> Result<(), String> {
+    let host = request
+        .header("host")
+        .ok_or_else(|| String::from("Cabeçalho Host ausente"))?;
+    if !is_local_host(host, local_addr) {
+        return Err(format!("Host não permitido: {}", host));
+    }
+    match request.header("origin") {
+        None => Ok(()),
+        Some(origin) => match origin.strip_prefix("http://") {
+            Some(origin_host) if is_local_host(origin_host, local_addr) => Ok(()),
+            _ => Err(format!("Origem não permitida: {}", origin)),
+        },
+    }
+}
+
+// Host (nome e porta) igual ao endereço de escuta
+fn is_local_host(host: &str, local_addr: SocketAddr) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => (name, port.parse::<u16>().ok()),
+        _ => (host, Some(80)),
+    };
+    let ip = local_addr.ip().to_string();
+    let names = [String::from("localhost"), ip.clone(), format!("[{}]", ip)];
+    port == Some(local_addr.port()) && names.iter().any(|allowed| allowed == name)
+}
+
+pub fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), String> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(body))
+        .map_err(|erro| erro.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> HttpRequest {
+        HttpRequest {
+            method: String::from("POST"),
+            path: String::from("/"),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_check_origin() {
+        let addr: SocketAddr = "127.0.0.1:8545".parse().unwrap();
+        for host in ["localhost:8545", "127.0.0.1:8545"] {
+            assert_eq!(check_origin(&request(&[("host", host)]), addr), Ok(()));
+        }
+        let same_origin = request(&[
+            ("host", "localhost:8545"),
+            ("origin", "http://localhost:8545"),
+        ]);
+        assert_eq!(check_origin(&same_origin, addr), Ok(()));
+        let ipv6: SocketAddr = "[::1]:8545".parse().unwrap();
+        assert_eq!(
+            check_origin(&request(&[("host", "[::1]:8545")]), ipv6),
+            Ok(())
+        );
+
+        // DNS rebinding: outro nome, outra porta ou sem Host
+        for host in [
+            "attacker.example:8545",
+            "localhost:80",
+            "localhost",
+            "127.0.0.1",
+        ] {
+            assert!(check_origin(&request(&[("host", host)]), addr).is_err());
+        }
+        assert!(check_origin(&request(&[]), addr).is_err());
+        // Pedido de outra origem enviado pelo navegador
+        let cross_origin = request(&[
+            ("host", "localhost:8545"),
+            ("origin", "http://attacker.example"),
+        ]);
+        assert_eq!(
+            check_origin(&cross_origin, addr),
+            Err(String::from(
+                "Origem não permitida: http://attacker.example"
+            ))
+        );
+        let null_origin = request(&[("host", "localhost:8545"), ("origin", "null")]);
+        assert!(check_origin(&null_origin, addr).is_err());
+    }
+
+    #[test]
+    fn test_read_line_limit() {
+        let mut line = String::new();
+        let long = vec![b'a'; MAX_LINE_SIZE as usize + 1];
+        assert!(read_line(&mut &long[..], &mut line).is_err());
+        let mut line = String::new();
+        assert_eq!(
+            read_line(&mut &b"GET / HTTP/1.1\r\n"[..], &mut line),
+            Ok(16)
+        );
+    }
+}
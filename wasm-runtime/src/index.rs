@@ -0,0 +1,361 @@
+/* Mod Index
+** - Índices dos blocos da cadeia canônica, gravados no Storage junto com o bloco:
+**    - `index/block/<hash>`: id do bloco com a hash
+**    - `index/tx/<id da transação>`: id do bloco que contém a transação. Um
+**      bloco que reutiliza o id de uma transação da cadeia é recusado
+**    - `index/txhash/<hash da transação>`: id da transação com o conteúdo
+**      (`Transaction::hash`), transações repetidas por outros nós são recusadas
+**    - `index/address/<endereço>`: quantidade de transações que envolvem o
+**      endereço (remetente, destino ou saída UTXO) e `index/address/<endereço>/<n>`:
+**      id da n-ésima transação, em ordem da cadeia. Cada bloco grava apenas as
+**      suas entradas, sem reescrever o histórico do endereço
+** - Histórico paginado: transferências de entrada e saída de um endereço, em
+**   ordem da cadeia. O cursor é a posição no histórico depois da página anterior.
+**   Apenas as transações executadas com sucesso que movem valor entram no
+**   histórico: transferências, gastos UTXO, registros multisig e publicações e
+**   chamadas de contrato com valor
+** - Os índices são gravados na mesma camada das alterações do bloco: desfazer o
+**   bloco (reorganização) desfaz também os seus índices
+** - `index/version`: versão do formato dos índices. Diretórios de dados com outra
+**   versão têm os índices reconstruídos a partir dos blocos ao abrir a cadeia
+*/
+use crate::{
+    block::Block,
+    contract,
+    hash::Hash,
+    receipt,
+    storage::{load, save, Storage},
+    transaction::{Transaction, TransactionKind},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+// Transferência de ou para o endereço consultado
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub transaction_id: u64,
+    pub block_id: u64,
+    pub direction: Direction,
+    // Outra parte da transferência: o destino nas saídas e o remetente nas entradas
+    pub counterparty: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub transfers: Vec<Transfer>,
+    // Cursor da próxima página, None na última página
+    pub next_cursor: Option<u64>,
+}
+
+// Versão do formato dos índices, gravada em VERSION_KEY
+const VERSION: u32 = 2;
+const VERSION_KEY: &str = "index/version";
+
+fn block_key(hash: &Hash) -> String {
+    format!("index/block/{}", hash)
+}
+
+fn transaction_key(transaction_id: u64) -> String {
+    format!("index/tx/{}", transaction_id)
+}
+
+fn transaction_hash_key(hash: &Hash) -> String {
+    format!("index/txhash/{}", hash)
+}
+
+fn address_key(address: &str) -> String {
+    format!("index/address/{}", address)
+}
+
+fn address_entry_key(address: &str, position: u64) -> String {
+    format!("index/address/{}/{}", address, position)
+}
+
+// Endereços envolvidos na transação
+pub fn addresses(transaction: &Transaction) -> BTreeSet<&str> {
+    let mut addresses = BTreeSet::from([transaction.from.as_str(), transaction.to.as_str()]);
+    if let TransactionKind::Spend { outputs, .. } = &transaction.kind {
+        addresses.extend(outputs.iter().map(|output| output.address.as_str()));
+    }
+    addresses.remove("");
+    addresses
+}
+
+// Recusa o bloco que reutiliza o id de uma transação já incluída na cadeia
+pub fn check_transaction_ids(storage: &dyn Storage, block: &Block) -> Result<(), String> {
+    match block
+        .transactions
+        .keys()
+        .find(|transaction_id| transaction_block(storage, **transaction_id).is_some())
+    {
+        Some(transaction_id) => Err(format!(
+            "Id de transação {} já utilizado na cadeia",
+            transaction_id
+        )),
+        None => Ok(()),
+    }
+}
+
+// Grava os índices do bloco adicionado a cadeia, sem sobrescrever outra transação
+pub fn index_block(storage: &mut dyn Storage, block: &Block) -> Result<(), String> {
+    check_transaction_ids(storage, block)?;
+    save(storage, &block_key(&block.hash), &block.id);
+    let mut ordered = block.transactions.iter().collect::<Vec<_>>();
+    ordered.sort_by_key(|(transaction_id, _)| **transaction_id);
+    for (transaction_id, transaction) in ordered {
+        save(storage, &transaction_key(*transaction_id), &block.id);
+        save(
+            storage,
+            &transaction_hash_key(&transaction.hash()),
+            transaction_id,
+        );
+        for address in addresses(transaction) {
+            let count = address_count(storage, address);
+            save(storage, &address_entry_key(address, count), transaction_id);
+            save(storage, &address_key(address), &(count + 1));
+        }
+    }
+    Ok(())
+}
+
+/* Reconstrói os índices a partir dos blocos da cadeia canônica, para diretórios
+** de dados gravados com outro formato. As entradas antigas dos ids e dos
+** endereços dos blocos são descartadas antes de gravar as novas
+*/
+pub fn rebuild(storage: &mut dyn Storage, chain: &[Block]) -> Result<(), String> {
+    for block in chain {
+        for (transaction_id, transaction) in &block.transactions {
+            storage.remove(transaction_key(*transaction_id).as_bytes());
+            for address in addresses(transaction) {
+                storage.remove(address_key(address).as_bytes());
+            }
+        }
+    }
+    for block in chain {
+        index_block(storage, block)?;
+    }
+    set_version(storage);
+    Ok(())
+}
+
+// Os índices gravados estão no formato atual
+pub fn is_current(storage: &dyn Storage) -> bool {
+    load::<u32>(storage, VERSION_KEY) == Some(VERSION)
+}
+
+pub fn set_version(storage: &mut dyn Storage) {
+    save(storage, VERSION_KEY, &VERSION);
+}
+
+/* Transferência da transação vista pelo endereço, None quando a transação
+** falhou (recibo sem sucesso) ou não moveu valor. Nos gastos UTXO o valor de
+** entrada são as saídas para o endereço e o de saída são as saídas para os
+** outros endereços (o troco não conta)
+*/
+pub fn transfer(
+    storage: &dyn Storage,
+    address: &str,
+    transaction_id: u64,
+    block_id: u64,
+    transaction: &Transaction,
+) -> Option<Transfer> {
+    if !receipt::get_receipt(storage, transaction_id).is_some_and(|receipt| receipt.is_success()) {
+        return None;
+    }
+    let direction = if transaction.from == address {
+        Direction::Outgoing
+    } else {
+        Direction::Incoming
+    };
+    let (counterparty, value) = match &transaction.kind {
+        TransactionKind::Spend { outputs, .. } => (
+            transaction.to.clone(),
+            outputs
+                .iter()
+                .filter(|output| (output.address == address) == (direction == Direction::Incoming))
+                .map(|output| output.value)
+                .sum(),
+        ),
+        // O valor da publicação vai para o endereço do contrato
+        TransactionKind::Deploy { .. } => (
+            contract::contract_address(&transaction.from, transaction.nonce),
+            transaction.value,
+        ),
+        TransactionKind::Transfer
+        | TransactionKind::Call { .. }
+        | TransactionKind::RegisterMultisig { .. } => (transaction.to.clone(), transaction.value),
+        _ => return None,
+    };
+    if value <= 0.0 {
+        return None;
+    }
+    let counterparty = match direction {
+        Direction::Incoming => transaction.from.clone(),
+        Direction::Outgoing => counterparty,
+    };
+    Some(Transfer {
+        transaction_id,
+        block_id,
+        direction,
+        counterparty,
+        value,
+    })
+}
+
+/* Ids de até `limit` transações do endereço a partir da posição do cursor e a
+** posição seguinte, None quando não há mais transações
+*/
+pub fn address_page(
+    storage: &dyn Storage,
+    address: &str,
+    cursor: Option<u64>,
+    limit: usize,
+) -> (Vec<u64>, Option<u64>) {
+    let count = address_count(storage, address);
+    let start = cursor.unwrap_or(0).min(count);
+    let end = count.min(start.saturating_add(limit as u64));
+    let ids = (start..end)
+        .filter_map(|position| load(storage, &address_entry_key(address, position)))
+        .collect();
+    (ids, Some(end).filter(|end| *end < count))
+}
+
+pub fn block_id(storage: &dyn Storage, hash: &Hash) -> Option<u64> {
+    load(storage, &block_key(hash))
+}
+
+pub fn transaction_block(storage: &dyn Storage, transaction_id: u64) -> Option<u64> {
+    load(storage, &transaction_key(transaction_id))
+}
+
+// Id da transação da cadeia com o conteúdo da hash
+pub fn transaction_by_hash(storage: &dyn Storage, hash: &Hash) -> Option<u64> {
+    load(storage, &transaction_hash_key(hash))
+}
+
+// Quantidade de transações do endereço
+pub fn address_count(storage: &dyn Storage, address: &str) -> u64 {
+    load(storage, &address_key(address)).unwrap_or(0)
+}
+
+// Ids das transações do endereço, em ordem da cadeia
+pub fn address_transactions(storage: &dyn Storage, address: &str) -> Vec<u64> {
+    address_page(storage, address, None, usize::MAX).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        contract::ExecutionStatus, hash::Sha256Hasher, receipt::Receipt, storage::MemoryStorage,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_index_block() {
+        let transactions = HashMap::from([
+            (3, Transaction::new("0x1", "0x2", 1.0)),
+            (1, Transaction::new("0x2", "0x3", 2.0)),
+            (2, Transaction::new("0x1", "0x1", 3.0)),
+        ]);
+        let block = Block::new(4, Hash::ZERO, "", "", transactions, &Sha256Hasher);
+        let mut storage = MemoryStorage::new();
+        index_block(&mut storage, &block).unwrap();
+
+        assert_eq!(block_id(&storage, &block.hash), Some(4));
+        assert_eq!(block_id(&storage, &Hash::ZERO), None);
+        assert_eq!(transaction_block(&storage, 2), Some(4));
+        assert_eq!(transaction_block(&storage, 5), None);
+        let hash = block.transactions[&2].hash();
+        assert_eq!(transaction_by_hash(&storage, &hash), Some(2));
+        assert_eq!(transaction_by_hash(&storage, &Hash::ZERO), None);
+        assert_eq!(address_transactions(&storage, "0x1"), vec![2, 3]);
+        assert_eq!(address_transactions(&storage, "0x2"), vec![1, 3]);
+        assert_eq!(address_transactions(&storage, "0x4"), Vec::<u64>::new());
+
+        // Uma chave por transação do endereço
+        assert_eq!(address_count(&storage, "0x1"), 2);
+        assert_eq!(load::<u64>(&storage, "index/address/0x1/1"), Some(3));
+
+        // Páginas de 1 transação do endereço 0x1, o cursor é a posição seguinte
+        assert_eq!(address_page(&storage, "0x1", None, 1), (vec![2], Some(1)));
+        assert_eq!(address_page(&storage, "0x1", Some(1), 1), (vec![3], None));
+        assert_eq!(address_page(&storage, "0x1", Some(2), 1), (vec![], None));
+        assert_eq!(address_page(&storage, "0x1", Some(9), 1), (vec![], None));
+
+        // O bloco seguinte acrescenta ao histórico, sem reutilizar ids
+        let transactions = HashMap::from([(4, Transaction::new("0x3", "0x1", 1.0))]);
+        let next = Block::new(5, block.hash, "", "", transactions, &Sha256Hasher);
+        index_block(&mut storage, &next).unwrap();
+        assert_eq!(address_transactions(&storage, "0x1"), vec![2, 3, 4]);
+        let transactions = HashMap::from([(3, Transaction::new("0x3", "0x4", 5.0))]);
+        let reused = Block::new(6, next.hash, "", "", transactions, &Sha256Hasher);
+        assert_eq!(
+            index_block(&mut storage, &reused),
+            Err(String::from("Id de transação 3 já utilizado na cadeia"))
+        );
+        assert_eq!(transaction_block(&storage, 3), Some(4));
+        assert_eq!(address_transactions(&storage, "0x4"), Vec::<u64>::new());
+
+        // Reconstrução dos índices de um diretório no formato anterior
+        let mut old = MemoryStorage::new();
+        save(&mut old, "index/address/0x1", &vec![2u64, 3]);
+        save(&mut old, &transaction_key(3), &4u64);
+        assert!(!is_current(&old));
+        rebuild(&mut old, &[block.clone(), next.clone()]).unwrap();
+        rebuild(&mut old, &[block.clone(), next]).unwrap();
+        assert!(is_current(&old));
+        assert_eq!(address_transactions(&old, "0x1"), vec![2, 3, 4]);
+        assert_eq!(address_transactions(&old, "0x2"), vec![1, 3]);
+        assert_eq!(transaction_block(&old, 4), Some(5));
+
+        // Transferências apenas das transações executadas com sucesso
+        let receipt = |transaction_id, status| Receipt {
+            transaction_id,
+            block_id: 4,
+            status,
+            gas_used: 0,
+            events: vec![],
+            state_root: String::new(),
+        };
+        let failed = ExecutionStatus::Failed(String::from("Nonce inválido, esperado 0"));
+        let receipts = [
+            receipt(1, failed),
+            receipt(2, ExecutionStatus::Success),
+            receipt(3, ExecutionStatus::Success),
+        ];
+        receipt::save_receipts(&mut storage, 4, &receipts);
+        let sent = transfer(&storage, "0x1", 3, 4, &block.transactions[&3]).unwrap();
+        assert_eq!(sent.direction, Direction::Outgoing);
+        assert_eq!((sent.counterparty.as_str(), sent.value), ("0x2", 1.0));
+        let received = transfer(&storage, "0x2", 3, 4, &block.transactions[&3]).unwrap();
+        assert_eq!(received.direction, Direction::Incoming);
+        assert_eq!(received.counterparty, "0x1");
+        assert_eq!(
+            transfer(&storage, "0x2", 1, 4, &block.transactions[&1]),
+            None
+        );
+        assert_eq!(
+            transfer(&storage, "0x2", 9, 4, &block.transactions[&3]),
+            None
+        );
+
+        // Transações que não movem valor não são transferências
+        let mut vote = Transaction::new("0x1", "", 0.0);
+        vote.kind = TransactionKind::Bond {
+            validator: String::from("0x5"),
+        };
+        vote.value = 2.0;
+        assert_eq!(transfer(&storage, "0x1", 2, 4, &vote), None);
+        let empty = Transaction::new("0x1", "0x2", 0.0);
+        assert_eq!(transfer(&storage, "0x1", 2, 4, &empty), None);
+    }
+}
@@ -2,6 +2,71 @@
 // OBS: `std` and `main` are only available when running tests.
 #![cfg_attr(all(target_arch = "wasm32", not(test)), no_std, no_main)]
 
+// Coleções da `alloc`, utilizadas pelos módulos disponíveis também no WebAssembly.
+extern crate alloc;
+
+// Alocador global do WebAssembly, sem a `std`.
+#[cfg(target_arch = "wasm32")]
+#[global_allocator]
+static ALLOCATOR: dlmalloc::GlobalDlmalloc = dlmalloc::GlobalDlmalloc;
+
+// Fachada de log com níveis, disponível em todos os targets.
+pub mod logger;
+// Armazenamento chave-valor, no WebAssembly sobre as funções importadas do host.
+pub mod storage;
+
+// Módulos da blockchain, dependem da `std` e por isso não são compilados para WebAssembly.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod block;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod blockchain;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod consensus;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod contract;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod explorer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod finality;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fork;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod genesis;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hash;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hd;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod http;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod index;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod multisig;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod network;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod receipt;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rpc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod stake;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod state;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tamper;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transaction;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod trie;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod utxo;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod wallet;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ws;
+
 // Override the default panic handler when compilling to WebAssembly.
 // Reference: https://doc.rust-lang.org/nomicon/panic-handler.html
 #[cfg(target_arch = "wasm32")]
@@ -17,6 +82,22 @@ pub mod ext {
     extern "C" {
         #[allow(clippy::missing_safety_doc)]
         pub fn console_log(ptr: *const u8, len: u32);
+
+        // Retorna o tamanho do valor, ou -1 se a chave não existe. O valor só é
+        // copiado para `value_ptr` quando cabe em `value_cap` bytes.
+        #[allow(clippy::missing_safety_doc)]
+        pub fn storage_get(
+            key_ptr: *const u8,
+            key_len: u32,
+            value_ptr: *mut u8,
+            value_cap: u32,
+        ) -> i32;
+
+        #[allow(clippy::missing_safety_doc)]
+        pub fn storage_set(key_ptr: *const u8, key_len: u32, value_ptr: *const u8, value_len: u32);
+
+        #[allow(clippy::missing_safety_doc)]
+        pub fn storage_remove(key_ptr: *const u8, key_len: u32);
     }
 }
 
@@ -30,6 +111,49 @@ pub mod ext {
             println!("{message}");
         }
     }
+
+    // Armazenamento do host simulado, um mapa por thread
+    thread_local! {
+        static HOST_STORAGE: std::cell::RefCell<std::collections::BTreeMap<Vec<u8>, Vec<u8>>> =
+            const { std::cell::RefCell::new(std::collections::BTreeMap::new()) };
+    }
+
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn storage_get(
+        key_ptr: *const u8,
+        key_len: u32,
+        value_ptr: *mut u8,
+        value_cap: u32,
+    ) -> i32 {
+        let key = core::slice::from_raw_parts(key_ptr, key_len as usize);
+        HOST_STORAGE.with(|storage| match storage.borrow().get(key) {
+            Some(value) => {
+                if value.len() <= value_cap as usize {
+                    core::ptr::copy_nonoverlapping(value.as_ptr(), value_ptr, value.len());
+                }
+                value.len() as i32
+            }
+            None => -1,
+        })
+    }
+
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn storage_set(
+        key_ptr: *const u8,
+        key_len: u32,
+        value_ptr: *const u8,
+        value_len: u32,
+    ) {
+        let key = core::slice::from_raw_parts(key_ptr, key_len as usize);
+        let value = core::slice::from_raw_parts(value_ptr, value_len as usize);
+        HOST_STORAGE.with(|storage| storage.borrow_mut().insert(key.to_vec(), value.to_vec()));
+    }
+
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn storage_remove(key_ptr: *const u8, key_len: u32) {
+        let key = core::slice::from_raw_parts(key_ptr, key_len as usize);
+        HOST_STORAGE.with(|storage| storage.borrow_mut().remove(key));
+    }
 }
 
 /// Logs a message to the console.
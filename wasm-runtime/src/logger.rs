@@ -0,0 +1,197 @@
+/* Mod Logger
+** - Fachada de log com níveis (error, warn, info, debug) e campos estruturados
+** - Toda mensagem é enviada para `crate::log`, que escreve no stdout quando
+**   nativo e chama o `console_log` do host quando compilado para WebAssembly
+** - O nível máximo é global e pode ser alterado em tempo de execução, o que
+**   permite silenciar a blockchain em testes e quando usada como biblioteca
+*/
+use core::{
+    fmt::{self, Display, Write},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+// Tamanho máximo de uma linha de log, mensagens maiores são truncadas
+const BUFFER_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+// Filtro de nível, `Off` desliga todas as mensagens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LevelFilter {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(LevelFilter::Info as u8);
+
+pub fn set_max_level(filter: LevelFilter) {
+    MAX_LEVEL.store(filter as u8, Ordering::Relaxed);
+}
+
+pub fn max_level() -> LevelFilter {
+    match MAX_LEVEL.load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        _ => LevelFilter::Debug,
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+// Buffer de tamanho fixo, não depende de alocação (compatível com `no_std`)
+struct LineBuffer {
+    data: [u8; BUFFER_SIZE],
+    len: usize,
+}
+
+impl LineBuffer {
+    fn new() -> Self {
+        LineBuffer {
+            data: [0; BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Ao truncar pode sobrar um caractere utf-8 incompleto no final
+        match core::str::from_utf8(&self.data[..self.len]) {
+            Ok(line) => line,
+            Err(erro) => core::str::from_utf8(&self.data[..erro.valid_up_to()]).unwrap_or(""),
+        }
+    }
+}
+
+impl Write for LineBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let available = BUFFER_SIZE - self.len;
+        let count = s.len().min(available);
+        self.data[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+/* Formata uma linha de log no formato:
+** [NIVEL] mensagem chave=valor chave=valor
+*/
+fn format_record(
+    out: &mut impl Write,
+    level: Level,
+    message: fmt::Arguments,
+    fields: &[(&str, &dyn Display)],
+) -> fmt::Result {
+    write!(out, "[{}] {}", level.as_str(), message)?;
+    for (key, value) in fields {
+        write!(out, " {}={}", key, value)?;
+    }
+    Ok(())
+}
+
+// Ponto de entrada das macros, descarta a mensagem se o nível estiver desligado
+pub fn log_record(level: Level, message: fmt::Arguments, fields: &[(&str, &dyn Display)]) {
+    if !enabled(level) {
+        return;
+    }
+    let mut line = LineBuffer::new();
+    let _ = format_record(&mut line, level, message, fields);
+    crate::log(line.as_str());
+}
+
+/* Macros de log. Uso:
+** info!("Novo bloco adicionado a cadeia"; id = block.id, hash = block.hash);
+** debug!("Bloco: {:?}", block);
+*/
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $fmt:literal $(, $arg:expr)* $(; $($key:ident = $value:expr),+ )?) => {
+        $crate::logger::log_record(
+            $level,
+            format_args!($fmt $(, $arg)*),
+            &[$($((stringify!($key), &$value as &dyn core::fmt::Display)),+)?],
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($tokens:tt)+) => { $crate::log_at!($crate::logger::Level::Error, $($tokens)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($tokens:tt)+) => { $crate::log_at!($crate::logger::Level::Warn, $($tokens)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($tokens:tt)+) => { $crate::log_at!($crate::logger::Level::Info, $($tokens)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($tokens:tt)+) => { $crate::log_at!($crate::logger::Level::Debug, $($tokens)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_record() {
+        let mut line = LineBuffer::new();
+        let id = 3;
+        let hash = "a843dbfe";
+        format_record(
+            &mut line,
+            Level::Info,
+            format_args!("Novo bloco adicionado a cadeia"),
+            &[("id", &id), ("hash", &hash)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            line.as_str(),
+            "[INFO] Novo bloco adicionado a cadeia id=3 hash=a843dbfe"
+        );
+    }
+
+    #[test]
+    fn test_line_buffer_truncate() {
+        let mut line = LineBuffer::new();
+        for _ in 0..BUFFER_SIZE {
+            line.write_str("ab").unwrap();
+        }
+        assert_eq!(line.as_str().len(), BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_level_order() {
+        // Error é o nível mais restritivo, Debug o mais verboso
+        assert!((Level::Error as u8) < (Level::Debug as u8));
+        assert!((LevelFilter::Off as u8) < (Level::Error as u8));
+    }
+}
@@ -0,0 +1,249 @@
+/* Mod Multisig
+** - Contas M-de-N: definidas por N chaves públicas ed25519 e o limite M, o
+**   endereço é "0x" + os 20 primeiros bytes do sha256 do limite e das chaves
+**   ordenadas (a ordem das chaves não altera a conta)
+** - A conta é registrada no estado por uma transação `RegisterMultisig` e fica
+**   gravada em `multisig/<endereço>`. Depois do registro toda transação com
+**   remetente na conta precisa de M assinaturas válidas de chaves distintas
+**   da conta, checadas na aplicação do bloco
+** - Transferências para o endereço antes do registro são aceitas como para
+**   qualquer endereço (ele não se distingue de um endereço comum): o saldo fica
+**   bloqueado até o registro, e o nonce da conta impede a repetição de uma
+**   transação já assinada pelos donos
+** - Assinaturas parciais: cada dono assina `Transaction::signing_hash` de forma
+**   independente (offline), as assinaturas são combinadas em `signatures`
+*/
+use crate::{
+    state,
+    storage::{encode_hex, load, save, Storage},
+    transaction::{Transaction, TransactionKind, TransactionSignature},
+    wallet::{self, parse_public_key},
+};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultisigAccount {
+    // Quantidade mínima de assinaturas (M)
+    pub threshold: u32,
+    // Chaves públicas dos donos em hexadecimal, ordenadas (N)
+    pub public_keys: Vec<String>,
+}
+
+impl MultisigAccount {
+    pub fn new(threshold: u32, public_keys: &[String]) -> Result<Self, String> {
+        let mut public_keys = public_keys
+            .iter()
+            .map(|public_key| public_key.to_lowercase())
+            .collect::<Vec<_>>();
+        public_keys.sort();
+        let account = MultisigAccount {
+            threshold,
+            public_keys,
+        };
+        account.validate()?;
+        Ok(account)
+    }
+
+    // Checa o limite e as chaves: 1 <= M <= N, chaves válidas, distintas e ordenadas
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threshold == 0 || self.threshold as usize > self.public_keys.len() {
+            return Err(format!(
+                "Limite inválido: {} de {} chaves",
+                self.threshold,
+                self.public_keys.len()
+            ));
+        }
+        for public_key in &self.public_keys {
+            let parsed = parse_public_key(public_key)?;
+            if encode_hex(parsed.as_bytes()) != *public_key {
+                return Err(String::from("Chave pública inválida"));
+            }
+        }
+        if self.public_keys.windows(2).any(|keys| keys[0] >= keys[1]) {
+            return Err(String::from("Chaves públicas repetidas ou fora de ordem"));
+        }
+        Ok(())
+    }
+
+    pub fn address(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"multisig");
+        hasher.update(self.threshold.to_be_bytes());
+        for public_key in &self.public_keys {
+            hasher.update(public_key.as_bytes());
+        }
+        format!("0x{}", encode_hex(&hasher.finalize()[..20]))
+    }
+
+    // Transação que registra a conta, `value` é transferido de `from` para a conta
+    pub fn register_transaction(&self, from: &str, value: f64) -> Transaction {
+        let mut transaction = Transaction::new(from, &self.address(), value);
+        transaction.kind = TransactionKind::RegisterMultisig {
+            threshold: self.threshold,
+            public_keys: self.public_keys.clone(),
+        };
+        transaction
+    }
+}
+
+fn multisig_key(address: &str) -> String {
+    format!("multisig/{}", address)
+}
+
+// Conta multisig registrada no endereço
+pub fn get_account(storage: &dyn Storage, address: &str) -> Option<MultisigAccount> {
+    load(storage, &multisig_key(address))
+}
+
+// Registra a conta no estado, uma conta só pode ser registrada uma vez
+pub fn register(storage: &mut dyn Storage, account: &MultisigAccount) -> Result<String, String> {
+    account.validate()?;
+    let address = account.address();
+    if get_account(storage, &address).is_some() {
+        return Err(format!("Conta multisig {} já registrada", address));
+    }
+    let key = multisig_key(&address);
+    save(storage, &key, account);
+    let value = serde_json::to_vec(account).expect("Erro ao serializar conta multisig");
+    state::record_change(storage, key.as_bytes(), Some(&value));
+    Ok(address)
+}
+
+// Assinatura parcial de um dono da conta, produzida offline
+pub fn sign_partial(key: &SigningKey, transaction: &Transaction) -> TransactionSignature {
+    wallet::sign_hash(key, &transaction.signing_hash())
+}
+
+/* Adiciona assinaturas parciais à transação. Cada assinatura precisa ser
+** válida para a transação; uma chave que já assinou não é adicionada novamente
+*/
+pub fn combine(
+    transaction: &mut Transaction,
+    signatures: &[TransactionSignature],
+) -> Result<(), String> {
+    let hash = transaction.signing_hash();
+    for signature in signatures {
+        wallet::verify_signature(signature, &hash)?;
+        let signed = transaction
+            .signatures
+            .iter()
+            .any(|other| other.public_key == signature.public_key);
+        if !signed {
+            transaction.signatures.push(signature.clone());
+        }
+    }
+    Ok(())
+}
+
+/* Checa a transação da conta: remetente igual ao endereço da conta, todas as
+** assinaturas válidas e de chaves da conta, com pelo menos M chaves distintas
+*/
+pub fn verify_transaction(
+    account: &MultisigAccount,
+    transaction: &Transaction,
+) -> Result<(), String> {
+    if account.address() != transaction.from {
+        return Err(String::from("Conta multisig não corresponde ao remetente"));
+    }
+    let hash = transaction.signing_hash();
+    let mut signers = vec![];
+    for signature in &transaction.signatures {
+        let public_key = signature.public_key.to_lowercase();
+        if !account.public_keys.contains(&public_key) {
+            return Err(String::from("Chave pública não pertence à conta multisig"));
+        }
+        wallet::verify_signature(signature, &hash)?;
+        if !signers.contains(&public_key) {
+            signers.push(public_key);
+        }
+    }
+    if signers.len() < account.threshold as usize {
+        return Err(format!(
+            "Assinaturas insuficientes: {} de {}",
+            signers.len(),
+            account.threshold
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::keypair_from_seed;
+
+    fn keys() -> Vec<SigningKey> {
+        (1..=3).map(|seed| keypair_from_seed(&[seed; 32])).collect()
+    }
+
+    fn public_keys(keys: &[SigningKey]) -> Vec<String> {
+        keys.iter()
+            .map(|key| encode_hex(key.verifying_key().as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_multisig_account() {
+        let mut public_keys = public_keys(&keys());
+        let account = MultisigAccount::new(2, &public_keys).unwrap();
+        assert_eq!(account.address().len(), 42);
+
+        // A ordem das chaves não altera o endereço, o limite altera
+        public_keys.reverse();
+        assert_eq!(MultisigAccount::new(2, &public_keys).unwrap(), account);
+        let other = MultisigAccount::new(3, &public_keys).unwrap();
+        assert_ne!(other.address(), account.address());
+
+        assert!(MultisigAccount::new(0, &public_keys).is_err());
+        assert!(MultisigAccount::new(4, &public_keys).is_err());
+        let repeated = vec![public_keys[0].clone(), public_keys[0].clone()];
+        assert!(MultisigAccount::new(1, &repeated).is_err());
+        assert!(MultisigAccount::new(1, &[String::from("00")]).is_err());
+    }
+
+    #[test]
+    fn test_partial_signatures() {
+        let keys = keys();
+        let account = MultisigAccount::new(2, &public_keys(&keys)).unwrap();
+        let mut transaction = Transaction::new(&account.address(), "0x2", 5.0);
+
+        // Assinaturas produzidas separadamente e combinadas depois
+        let first = sign_partial(&keys[0], &transaction);
+        let third = sign_partial(&keys[2], &transaction);
+
+        combine(&mut transaction, std::slice::from_ref(&first)).unwrap();
+        assert_eq!(
+            verify_transaction(&account, &transaction),
+            Err(String::from("Assinaturas insuficientes: 1 de 2"))
+        );
+
+        // A mesma chave não conta duas vezes
+        combine(&mut transaction, std::slice::from_ref(&first)).unwrap();
+        let mut duplicated = transaction.clone();
+        duplicated.signatures.push(first);
+        assert!(verify_transaction(&account, &duplicated).is_err());
+
+        combine(&mut transaction, &[third]).unwrap();
+        assert_eq!(verify_transaction(&account, &transaction), Ok(()));
+
+        // Alteração da transação invalida as assinaturas
+        let mut tampered = transaction.clone();
+        tampered.value = 50.0;
+        assert_eq!(
+            verify_transaction(&account, &tampered),
+            Err(String::from("Assinatura inválida"))
+        );
+        assert!(combine(&mut tampered, &transaction.signatures).is_err());
+
+        // Chave de fora da conta
+        let outsider = keypair_from_seed(&[9u8; 32]);
+        let mut foreign = transaction.clone();
+        foreign.signatures[0] = sign_partial(&outsider, &foreign);
+        assert_eq!(
+            verify_transaction(&account, &foreign),
+            Err(String::from("Chave pública não pertence à conta multisig"))
+        );
+    }
+}
@@ -0,0 +1,1136 @@
+/* Mod Network
+** - Rede peer-to-peer entre nós da blockchain sobre TCP
+** - Mensagens em JSON, uma mensagem por linha
+** - Ao conectar, os nós trocam um handshake com a versão do protocolo, o id da
+**   cadeia e a hash do bloco genesis. Conexões incompatíveis são encerradas
+** - Novas transações são propagadas para as transações pendentes dos peers e
+**   novos blocos minerados são anunciados e importados pelos peers
+** - Itens já vistos (transações e blocos) não são processados nem propagados novamente.
+**   As transações são identificadas pela hash do conteúdo, os ids são locais de cada nó.
+**   Um item só é marcado como visto depois de aceito (transação nas pendentes,
+**   bloco importado), um item recusado pode chegar de novo e ser reprocessado. Os
+**   MAX_SEEN itens mais recentes são lembrados, os mais antigos são esquecidos
+** - No máximo MAX_PEERS peers conectados e MAX_PEERS conexões de entrada em
+**   atendimento (inclusive durante o handshake), as demais são encerradas
+** - Mensagens maiores que MAX_MESSAGE_SIZE encerram a conexão. O handshake do peer
+**   precisa chegar em HANDSHAKE_TIMEOUT e os envios têm limite de WRITE_TIMEOUT
+** - Sincronização (download inicial dos blocos): o nó pergunta a altura dos peers,
+**   pede ao peer com a maior altura os cabeçalhos e depois os corpos dos blocos em
+**   lotes, e importa cada bloco com `Blockchain::import_block`. Como os blocos
+**   importados ficam gravados, uma sincronização interrompida continua da altura atual
+** - Ancestral comum: o nó compara os cabeçalhos do peer com os blocos conhecidos
+**   (cadeia e ramos laterais), descendo em lotes a partir da altura local até o
+**   checkpoint finalizado. Os blocos do peer acima do ancestral entram na árvore
+**   de blocos e, quando o ramo passa a cadeia local, acontece a reorganização
+** - Pré-commits dos validadores nos checkpoints (mod finality) são propagados
+**   como as transações, os pré-commits do validador local são enviados depois
+**   de cada bloco minerado ou importado
+*/
+use crate::{
+    block::{Block, BlockHeader},
+    blockchain::Blockchain,
+    debug,
+    finality::Precommit,
+    hash::Hash,
+    http::ConnectionSlot,
+    info,
+    transaction::Transaction,
+    warn,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+pub const PROTOCOL_VERSION: u32 = 5;
+
+// Quantidade máxima de blocos pedidos em cada lote da sincronização
+pub const SYNC_BATCH_SIZE: u64 = 16;
+
+// Tempo máximo de espera pela resposta de um peer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tempo máximo de espera pelo handshake do peer e pela conexão
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tempo máximo de um envio, um peer que não lê as mensagens é desconectado
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Tamanho máximo de uma mensagem (uma linha JSON)
+pub const MAX_MESSAGE_SIZE: u64 = 8 * 1024 * 1024;
+
+// Quantidade máxima de peers conectados e de conexões de entrada em atendimento
+pub const MAX_PEERS: usize = 16;
+
+// Quantidade de itens vistos lembrados pelo nó
+const MAX_SEEN: usize = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Message {
+    Handshake {
+        version: u32,
+        chain_id: String,
+        genesis_hash: Hash,
+        height: u64,
+    },
+    Transaction {
+        id: u64,
+        transaction: Transaction,
+    },
+    Block(Block),
+    Precommit(Precommit),
+    // Pedidos e respostas da sincronização, a resposta repete o request_id do pedido
+    GetHeight {
+        request_id: u64,
+    },
+    Height {
+        request_id: u64,
+        height: u64,
+    },
+    GetHeaders {
+        request_id: u64,
+        from: u64,
+        count: u64,
+    },
+    Headers {
+        request_id: u64,
+        headers: Vec<BlockHeader>,
+    },
+    GetBodies {
+        request_id: u64,
+        ids: Vec<u64>,
+    },
+    Bodies {
+        request_id: u64,
+        bodies: Vec<HashMap<u64, Transaction>>,
+    },
+}
+
+impl Message {
+    // Id do pedido respondido, apenas para as respostas da sincronização
+    fn response_id(&self) -> Option<u64> {
+        match self {
+            Message::Height { request_id, .. }
+            | Message::Headers { request_id, .. }
+            | Message::Bodies { request_id, .. } => Some(*request_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    // Identificação da rede, nós de cadeias diferentes não se conectam
+    pub chain_id: String,
+    // Endereço de escuta, a porta 0 escolhe uma porta livre
+    pub listen_addr: SocketAddr,
+}
+
+struct Peer {
+    addr: SocketAddr,
+    stream: Mutex<TcpStream>,
+}
+
+impl Peer {
+    fn send(&self, message: &Message) -> Result<(), String> {
+        let mut line = serde_json::to_string(message).map_err(|erro| erro.to_string())?;
+        line.push('\n');
+        let mut stream = self.stream.lock().unwrap();
+        stream
+            .write_all(line.as_bytes())
+            .map_err(|erro| erro.to_string())
+    }
+}
+
+// Itens já vistos, ao passar de MAX_SEEN itens o mais antigo é esquecido
+#[derive(Default)]
+struct SeenSet {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenSet {
+    fn contains(&self, key: &str) -> bool {
+        self.keys.contains(key)
+    }
+
+    // Retorna false se o item já havia sido visto
+    fn insert(&mut self, key: String) -> bool {
+        if !self.keys.insert(key.clone()) {
+            return false;
+        }
+        self.order.push_back(key);
+        if self.order.len() > MAX_SEEN {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+// Estado compartilhado entre as threads do nó
+struct Shared {
+    blockchain: Arc<Mutex<Blockchain>>,
+    config: NodeConfig,
+    peers: Mutex<Vec<Arc<Peer>>>,
+    seen: Mutex<SeenSet>,
+    // Conexões de entrada em atendimento, limitadas a MAX_PEERS
+    inbound: Arc<AtomicUsize>,
+    // Pedidos aguardando resposta, indexados pelo request_id
+    requests: Mutex<HashMap<u64, Sender<Message>>>,
+    next_request_id: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct Node {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+}
+
+fn transaction_key(transaction: &Transaction) -> String {
+    format!("tx/{}", transaction.hash())
+}
+
+fn block_key(block: &Block) -> String {
+    format!("block/{}", block.hash)
+}
+
+fn precommit_key(vote: &Precommit) -> String {
+    format!("precommit/{}/{}", vote.block_hash, vote.validator)
+}
+
+impl Node {
+    // Inicia o nó: abre o endereço de escuta e aceita conexões em uma thread
+    pub fn start(config: NodeConfig, blockchain: Arc<Mutex<Blockchain>>) -> Result<Node, String> {
+        let listener = TcpListener::bind(config.listen_addr).map_err(|erro| erro.to_string())?;
+        let local_addr = listener.local_addr().map_err(|erro| erro.to_string())?;
+        let node = Node {
+            shared: Arc::new(Shared {
+                blockchain,
+                config,
+                peers: Mutex::new(Vec::new()),
+                seen: Mutex::new(SeenSet::default()),
+                inbound: Arc::new(AtomicUsize::new(0)),
+                requests: Mutex::new(HashMap::new()),
+                next_request_id: AtomicU64::new(1),
+            }),
+            local_addr,
+        };
+
+        let accept_node = node.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let node = accept_node.clone();
+                let Some(slot) = ConnectionSlot::acquire(&node.shared.inbound, MAX_PEERS) else {
+                    warn!("Conexão recusada, limite de conexões atingido");
+                    continue;
+                };
+                thread::spawn(move || {
+                    if let Err(erro) = node.handle_connection(stream) {
+                        warn!("Conexão recusada"; erro = erro);
+                    }
+                    drop(slot);
+                });
+            }
+        });
+
+        info!("Nó iniciado"; addr = local_addr, chain_id = node.shared.config.chain_id);
+        Ok(node)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn blockchain(&self) -> Arc<Mutex<Blockchain>> {
+        self.shared.blockchain.clone()
+    }
+
+    pub fn peer_count(&self) -> usize {
+        self.shared.peers.lock().unwrap().len()
+    }
+
+    // Conecta a um peer, retorna após o handshake
+    pub fn connect(&self, addr: SocketAddr) -> Result<(), String> {
+        let stream = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)
+            .map_err(|erro| erro.to_string())?;
+        let (peer, reader) = self.handshake(stream)?;
+        let node = self.clone();
+        thread::spawn(move || node.read_messages(peer, reader));
+        Ok(())
+    }
+
+    // Cria uma transação local e a propaga para os peers
+    pub fn submit_transaction(&self, transaction: Transaction) -> u64 {
+        let (id, new_blocks) = {
+            let mut blockchain = self.shared.blockchain.lock().unwrap();
+            Node::submit_locked(&mut blockchain, transaction.clone())
+        };
+        self.publish_transaction(id, transaction, new_blocks);
+        id
+    }
+
+    /* Checa a transação com `Blockchain::check_transaction` antes de aceitar e
+     ** a propaga para os peers. Utilizada pelo `tx_submit` do servidor RPC
+     */
+    pub fn submit_checked(&self, transaction: Transaction) -> Result<u64, String> {
+        let (id, new_blocks) = {
+            let mut blockchain = self.shared.blockchain.lock().unwrap();
+            blockchain.check_transaction(&transaction)?;
+            Node::submit_locked(&mut blockchain, transaction.clone())
+        };
+        self.publish_transaction(id, transaction, new_blocks);
+        Ok(id)
+    }
+
+    // Adiciona a transação, retorna o id e os blocos minerados ao completar o bloco
+    fn submit_locked(blockchain: &mut Blockchain, transaction: Transaction) -> (u64, Vec<Block>) {
+        let height = blockchain.height();
+        let id = blockchain.submit_transaction(transaction);
+        (id, blockchain.chain[height as usize + 1..].to_vec())
+    }
+
+    fn publish_transaction(&self, id: u64, transaction: Transaction, new_blocks: Vec<Block>) {
+        self.mark_seen(transaction_key(&transaction));
+        self.broadcast(&Message::Transaction { id, transaction }, None);
+
+        // A transação pode ter completado um bloco, que também é anunciado
+        for block in new_blocks {
+            self.announce_block(block);
+        }
+        self.announce_precommits();
+    }
+
+    // Minera as transações pendentes e anuncia o novo bloco
+    pub fn mine_block(&self) -> Block {
+        let block = {
+            let mut blockchain = self.shared.blockchain.lock().unwrap();
+            blockchain.mine_block();
+            blockchain.last_block().clone()
+        };
+        self.announce_block(block.clone());
+        self.announce_precommits();
+        block
+    }
+
+    /* Sincroniza com o peer de maior altura até alcançá-lo.
+     ** Retorna a quantidade de blocos importados
+     */
+    pub fn sync(&self) -> Result<u64, String> {
+        let mut imported = 0;
+        loop {
+            let count = self.sync_batch()?;
+            if count == 0 {
+                return Ok(imported);
+            }
+            imported += count;
+        }
+    }
+
+    /* Baixa e importa um lote de até SYNC_BATCH_SIZE blocos do peer de maior
+     ** altura. Retorna 0 quando a cadeia local já está atualizada
+     */
+    pub fn sync_batch(&self) -> Result<u64, String> {
+        let local_height = self.shared.blockchain.lock().unwrap().height();
+
+        // Peer com a maior altura
+        let peers = self.shared.peers.lock().unwrap().clone();
+        let mut best: Option<(Arc<Peer>, u64)> = None;
+        for peer in peers {
+            match self.request(&peer, |request_id| Message::GetHeight { request_id }) {
+                Ok(Message::Height { height, .. }) => {
+                    if best
+                        .as_ref()
+                        .is_none_or(|(_, best_height)| height > *best_height)
+                    {
+                        best = Some((peer, height));
+                    }
+                }
+                Ok(_) => warn!("Resposta inesperada"; peer = peer.addr),
+                Err(erro) => warn!("Peer não informou a altura"; peer = peer.addr, erro = erro),
+            }
+        }
+        let (peer, peer_height) = match best {
+            Some((peer, height)) if height > local_height => (peer, height),
+            _ => return Ok(0),
+        };
+
+        // Cabeçalhos: devem continuar o ancestral comum e estar encadeados entre si
+        let ancestor = self.common_ancestor(&peer, local_height)?;
+        let from = ancestor.id + 1;
+        let count = SYNC_BATCH_SIZE.min(peer_height - ancestor.id);
+        let headers = self.request_headers(&peer, from, count)?;
+        let mut previous_hash = ancestor.hash;
+        for (i, header) in headers.iter().enumerate() {
+            if header.id != from + i as u64 || header.hash_previous_block != previous_hash {
+                return Err(format!("Cabeçalho {} fora da sequência", header.id));
+            }
+            previous_hash = header.hash;
+        }
+
+        // Corpos dos blocos, cada bloco é validado na importação
+        let ids = headers.iter().map(|header| header.id).collect::<Vec<_>>();
+        let bodies =
+            match self.request(&peer, |request_id| Message::GetBodies { request_id, ids })? {
+                Message::Bodies { bodies, .. } => bodies,
+                _ => return Err(String::from("Resposta inesperada, corpos esperados")),
+            };
+        if bodies.len() != headers.len() {
+            return Err(String::from(
+                "Quantidade de corpos diferente dos cabeçalhos",
+            ));
+        }
+
+        let mut imported = 0;
+        for (header, transactions) in headers.into_iter().zip(bodies) {
+            let block = Block::from_parts(header, transactions);
+            let key = block_key(&block);
+            let mut blockchain = self.shared.blockchain.lock().unwrap();
+            blockchain.import_block(block)?;
+            self.mark_seen(key);
+            imported += 1;
+        }
+        self.announce_precommits();
+        let height = self.shared.blockchain.lock().unwrap().height();
+        info!("Lote sincronizado"; peer = peer.addr, blocks = imported, height = height);
+        Ok(imported)
+    }
+
+    /* Último cabeçalho do peer conhecido localmente, na cadeia ou em um ramo
+     ** lateral. Procura em lotes descendo a partir de `height`, sem passar do
+     ** checkpoint finalizado
+     */
+    fn common_ancestor(&self, peer: &Peer, height: u64) -> Result<BlockHeader, String> {
+        let finalized = self.shared.blockchain.lock().unwrap().finalized().id;
+        let mut end = height;
+        loop {
+            let start = end.saturating_sub(SYNC_BATCH_SIZE - 1).max(finalized);
+            let headers = self.request_headers(peer, start, end - start + 1)?;
+            if headers
+                .iter()
+                .enumerate()
+                .any(|(i, header)| header.id != start + i as u64)
+            {
+                return Err(String::from("Cabeçalhos fora da sequência"));
+            }
+            let known = {
+                let blockchain = self.shared.blockchain.lock().unwrap();
+                headers
+                    .into_iter()
+                    .rev()
+                    .find(|header| blockchain.is_known_block(&header.hash))
+            };
+            if let Some(header) = known {
+                if header.id < height {
+                    debug!("Ancestral comum"; peer = peer.addr, id = header.id, hash = header.hash);
+                }
+                return Ok(header);
+            }
+            if start == finalized {
+                return Err(String::from(
+                    "Peer em outro ramo abaixo do checkpoint finalizado",
+                ));
+            }
+            end = start - 1;
+        }
+    }
+
+    fn request_headers(
+        &self,
+        peer: &Peer,
+        from: u64,
+        count: u64,
+    ) -> Result<Vec<BlockHeader>, String> {
+        let headers = match self.request(peer, |request_id| Message::GetHeaders {
+            request_id,
+            from,
+            count,
+        })? {
+            Message::Headers { headers, .. } => headers,
+            _ => return Err(String::from("Resposta inesperada, cabeçalhos esperados")),
+        };
+        if headers.is_empty() {
+            return Err(String::from("Peer não enviou cabeçalhos"));
+        }
+        Ok(headers)
+    }
+
+    // Envia um pedido ao peer e aguarda a resposta com o mesmo request_id
+    fn request(&self, peer: &Peer, build: impl FnOnce(u64) -> Message) -> Result<Message, String> {
+        let request_id = self.shared.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel();
+        self.shared
+            .requests
+            .lock()
+            .unwrap()
+            .insert(request_id, sender);
+
+        let response = peer.send(&build(request_id)).and_then(|_| {
+            receiver
+                .recv_timeout(REQUEST_TIMEOUT)
+                .map_err(|_| String::from("Tempo de resposta esgotado"))
+        });
+        self.shared.requests.lock().unwrap().remove(&request_id);
+        response
+    }
+
+    fn announce_block(&self, block: Block) {
+        self.mark_seen(block_key(&block));
+        self.broadcast(&Message::Block(block), None);
+    }
+
+    // Envia aos peers os pré-commits do validador local
+    fn announce_precommits(&self) {
+        let precommits = self.shared.blockchain.lock().unwrap().take_precommits();
+        for vote in precommits {
+            self.mark_seen(precommit_key(&vote));
+            self.broadcast(&Message::Precommit(vote), None);
+        }
+    }
+
+    // Marca um item aceito como visto, retorna false se ele já havia sido visto
+    fn mark_seen(&self, key: String) -> bool {
+        self.shared.seen.lock().unwrap().insert(key)
+    }
+
+    fn is_seen(&self, key: &str) -> bool {
+        self.shared.seen.lock().unwrap().contains(key)
+    }
+
+    fn broadcast(&self, message: &Message, except: Option<SocketAddr>) {
+        let peers = self.shared.peers.lock().unwrap().clone();
+        for peer in peers.iter().filter(|peer| Some(peer.addr) != except) {
+            if let Err(erro) = peer.send(message) {
+                warn!("Erro ao enviar mensagem"; peer = peer.addr, erro = erro);
+            }
+        }
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> Result<(), String> {
+        let (peer, reader) = self.handshake(stream)?;
+        self.read_messages(peer, reader);
+        Ok(())
+    }
+
+    /* Envia o handshake local e valida o handshake do peer.
+     ** Em caso de sucesso o peer é adicionado à lista de peers do nó
+     */
+    fn handshake(&self, stream: TcpStream) -> Result<(Arc<Peer>, BufReader<TcpStream>), String> {
+        let addr = stream.peer_addr().map_err(|erro| erro.to_string())?;
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+            .map_err(|erro| erro.to_string())?;
+        let reader = stream.try_clone().map_err(|erro| erro.to_string())?;
+        let peer = Arc::new(Peer {
+            addr,
+            stream: Mutex::new(stream),
+        });
+        let mut reader = BufReader::new(reader);
+
+        let (genesis_hash, height) = {
+            let blockchain = self.shared.blockchain.lock().unwrap();
+            (blockchain.genesis_hash(), blockchain.height())
+        };
+        peer.send(&Message::Handshake {
+            version: PROTOCOL_VERSION,
+            chain_id: self.shared.config.chain_id.clone(),
+            genesis_hash,
+            height,
+        })?;
+
+        match read_message(&mut reader)? {
+            Some(Message::Handshake {
+                version,
+                chain_id,
+                genesis_hash: peer_genesis_hash,
+                height: peer_height,
+            }) => {
+                if version != PROTOCOL_VERSION {
+                    return Err(format!("Versão do protocolo incompatível: {}", version));
+                }
+                if chain_id != self.shared.config.chain_id {
+                    return Err(format!("Id da cadeia incompatível: {}", chain_id));
+                }
+                if peer_genesis_hash != genesis_hash {
+                    return Err(String::from("Bloco genesis incompatível"));
+                }
+                info!("Peer conectado"; peer = addr, height = peer_height);
+            }
+            _ => return Err(String::from("Handshake esperado")),
+        }
+        // Depois do handshake o peer pode ficar sem enviar mensagens
+        reader
+            .get_ref()
+            .set_read_timeout(None)
+            .map_err(|erro| erro.to_string())?;
+
+        let mut peers = self.shared.peers.lock().unwrap();
+        if peers.len() >= MAX_PEERS {
+            return Err(String::from("Limite de peers atingido"));
+        }
+        peers.push(peer.clone());
+        drop(peers);
+        Ok((peer, reader))
+    }
+
+    // Lê as mensagens do peer até a conexão ser encerrada
+    fn read_messages(&self, peer: Arc<Peer>, mut reader: BufReader<TcpStream>) {
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(message)) => self.handle_message(&peer, message),
+                Ok(None) => break,
+                Err(erro) => {
+                    warn!("Mensagem inválida"; peer = peer.addr, erro = erro);
+                    break;
+                }
+            }
+        }
+        self.shared
+            .peers
+            .lock()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, &peer));
+        info!("Peer desconectado"; peer = peer.addr);
+    }
+
+    fn handle_message(&self, peer: &Peer, message: Message) {
+        // Respostas são entregues ao pedido que as aguarda
+        if let Some(request_id) = message.response_id() {
+            let sender = self.shared.requests.lock().unwrap().remove(&request_id);
+            match sender {
+                Some(sender) => {
+                    let _ = sender.send(message);
+                }
+                None => debug!("Resposta sem pedido"; peer = peer.addr, request_id = request_id),
+            }
+            return;
+        }
+
+        match message {
+            Message::Handshake { .. } => warn!("Handshake repetido"; peer = peer.addr),
+            Message::Transaction { id, transaction } => {
+                let key = transaction_key(&transaction);
+                if self.is_seen(&key) {
+                    return;
+                }
+                // Marcada com a trava da blockchain: aceita e vista ao mesmo tempo
+                let added = {
+                    let mut blockchain = self.shared.blockchain.lock().unwrap();
+                    blockchain.add_pending_transaction(id, transaction.clone())
+                        && self.mark_seen(key)
+                };
+                if added {
+                    debug!("Transação recebida"; peer = peer.addr, id = id);
+                    self.broadcast(&Message::Transaction { id, transaction }, Some(peer.addr));
+                }
+            }
+            Message::Block(block) => {
+                let key = block_key(&block);
+                if self.is_seen(&key) {
+                    return;
+                }
+                // Um bloco recusado não é marcado, ele pode ser importado quando chegar de novo
+                let imported = {
+                    let mut blockchain = self.shared.blockchain.lock().unwrap();
+                    blockchain
+                        .import_block(block.clone())
+                        .map(|()| self.mark_seen(key))
+                };
+                match imported {
+                    Ok(true) => {
+                        self.broadcast(&Message::Block(block), Some(peer.addr));
+                        self.announce_precommits();
+                    }
+                    Ok(false) => {}
+                    Err(erro) => {
+                        warn!("Bloco recusado"; peer = peer.addr, id = block.id, erro = erro)
+                    }
+                }
+            }
+            Message::Precommit(vote) => {
+                let key = precommit_key(&vote);
+                if self.is_seen(&key) {
+                    return;
+                }
+                let added = {
+                    let mut blockchain = self.shared.blockchain.lock().unwrap();
+                    blockchain
+                        .add_precommit(vote.clone())
+                        .map(|_| self.mark_seen(key))
+                };
+                match added {
+                    Ok(true) => self.broadcast(&Message::Precommit(vote), Some(peer.addr)),
+                    Ok(false) => {}
+                    Err(erro) => debug!("Pré-commit recusado"; peer = peer.addr, erro = erro),
+                }
+            }
+            Message::GetHeight { request_id } => {
+                let height = self.shared.blockchain.lock().unwrap().height();
+                self.reply(peer, Message::Height { request_id, height });
+            }
+            Message::GetHeaders {
+                request_id,
+                from,
+                count,
+            } => {
+                let headers = {
+                    let blockchain = self.shared.blockchain.lock().unwrap();
+                    blockchain
+                        .chain
+                        .iter()
+                        .skip(from as usize)
+                        .take(count.min(SYNC_BATCH_SIZE) as usize)
+                        .map(Block::header)
+                        .collect()
+                };
+                self.reply(
+                    peer,
+                    Message::Headers {
+                        request_id,
+                        headers,
+                    },
+                );
+            }
+            Message::GetBodies { request_id, ids } => {
+                let bodies = {
+                    let blockchain = self.shared.blockchain.lock().unwrap();
+                    ids.iter()
+                        .take(SYNC_BATCH_SIZE as usize)
+                        .filter_map(|id| blockchain.chain.get(*id as usize))
+                        .map(|block| block.transactions.clone())
+                        .collect()
+                };
+                self.reply(peer, Message::Bodies { request_id, bodies });
+            }
+            Message::Height { .. } | Message::Headers { .. } | Message::Bodies { .. } => {}
+        }
+    }
+
+    fn reply(&self, peer: &Peer, message: Message) {
+        if let Err(erro) = peer.send(&message) {
+            warn!("Erro ao enviar resposta"; peer = peer.addr, erro = erro);
+        }
+    }
+}
+
+/* Lê uma mensagem (uma linha JSON), retorna None quando a conexão é encerrada.
+** A leitura para em MAX_MESSAGE_SIZE bytes, uma linha maior é um erro
+*/
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Message>, String> {
+    let mut line = String::new();
+    let read = reader
+        .take(MAX_MESSAGE_SIZE + 1)
+        .read_line(&mut line)
+        .map_err(|erro| erro.to_string())?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read as u64 > MAX_MESSAGE_SIZE {
+        return Err(String::from("Mensagem excede o tamanho máximo"));
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|erro| erro.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::wallet::{self, tests::test_account};
+    use std::time::{Duration, Instant};
+
+    // Transferência assinada pela conta de teste, com o nonce informado
+    fn signed_transfer(nonce: u64, value: f64) -> Transaction {
+        let (key, from) = test_account(101);
+        let mut transaction =
+            Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", value);
+        transaction.nonce = nonce;
+        wallet::sign_transaction(&key, &mut transaction).unwrap();
+        transaction
+    }
+
+    pub(crate) fn start_node(chain_id: &str) -> Node {
+        let config = NodeConfig {
+            chain_id: chain_id.to_string(),
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+        };
+        Node::start(config, Arc::new(Mutex::new(Blockchain::new()))).unwrap()
+    }
+
+    // Aguarda a condição ser verdadeira, com limite de 5 segundos
+    pub(crate) fn wait_until(condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_handshake() {
+        let first = start_node("rede-teste");
+        let second = start_node("rede-teste");
+        let other_chain = start_node("outra-rede");
+
+        first.connect(second.local_addr()).unwrap();
+        assert!(wait_until(|| second.peer_count() == 1));
+        assert_eq!(first.peer_count(), 1);
+
+        // Id da cadeia diferente, a conexão é recusada pelos dois lados
+        assert!(first.connect(other_chain.local_addr()).is_err());
+        assert_eq!(first.peer_count(), 1);
+
+        // Mesmo id da cadeia com outros parâmetros do genesis
+        let genesis = crate::genesis::Genesis {
+            chain_id: String::from("rede-teste"),
+            alloc: [(String::from("0x1"), 10.0)].into(),
+            ..Default::default()
+        };
+        let blockchain =
+            Blockchain::with_genesis(Box::new(crate::storage::MemoryStorage::new()), &genesis)
+                .unwrap();
+        let other_genesis = Node::start(
+            NodeConfig {
+                chain_id: String::from("rede-teste"),
+                listen_addr: "127.0.0.1:0".parse().unwrap(),
+            },
+            Arc::new(Mutex::new(blockchain)),
+        )
+        .unwrap();
+        assert_eq!(
+            first.connect(other_genesis.local_addr()),
+            Err(String::from("Bloco genesis incompatível"))
+        );
+        assert_eq!(first.peer_count(), 1);
+    }
+
+    #[test]
+    fn test_connection_limits() {
+        let node = start_node("rede-teste");
+
+        // Conexão sem handshake é encerrada pelo nó depois de HANDSHAKE_TIMEOUT
+        let silent = TcpStream::connect(node.local_addr()).unwrap();
+        let mut reader = BufReader::new(silent);
+        assert!(matches!(
+            read_message(&mut reader),
+            Ok(Some(Message::Handshake { .. }))
+        ));
+        assert!(matches!(read_message(&mut reader), Ok(None)));
+
+        // Mensagem maior que o limite encerra a conexão
+        let mut stream = TcpStream::connect(node.local_addr()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        read_message(&mut reader).unwrap();
+        let handshake = Message::Handshake {
+            version: PROTOCOL_VERSION,
+            chain_id: String::from("rede-teste"),
+            genesis_hash: node.blockchain().lock().unwrap().genesis_hash(),
+            height: 0,
+        };
+        let mut line = serde_json::to_string(&handshake).unwrap();
+        line.push('\n');
+        stream.write_all(line.as_bytes()).unwrap();
+        assert!(wait_until(|| node.peer_count() == 1));
+        let oversized = vec![b' '; MAX_MESSAGE_SIZE as usize + 1];
+        let _ = stream.write_all(&oversized);
+        assert!(wait_until(|| node.peer_count() == 0));
+
+        let limited = read_message(&mut &oversized[..]);
+        assert_eq!(
+            limited.unwrap_err(),
+            String::from("Mensagem excede o tamanho máximo")
+        );
+    }
+
+    // Conexão TCP direta com o nó, após o handshake
+    fn raw_peer(node: &Node) -> (TcpStream, BufReader<TcpStream>) {
+        let mut stream = TcpStream::connect(node.local_addr()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        assert!(matches!(
+            read_message(&mut reader),
+            Ok(Some(Message::Handshake { .. }))
+        ));
+        let genesis_hash = node.blockchain().lock().unwrap().genesis_hash();
+        send_raw(
+            &mut stream,
+            &Message::Handshake {
+                version: PROTOCOL_VERSION,
+                chain_id: node.shared.config.chain_id.clone(),
+                genesis_hash,
+                height: 0,
+            },
+        );
+        (stream, reader)
+    }
+
+    fn send_raw(stream: &mut TcpStream, message: &Message) {
+        let mut line = serde_json::to_string(message).unwrap();
+        line.push('\n');
+        stream.write_all(line.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_peer_limit() {
+        let node = start_node("rede-teste");
+        let mut peers = (0..MAX_PEERS).map(|_| raw_peer(&node)).collect::<Vec<_>>();
+        assert!(wait_until(|| node.peer_count() == MAX_PEERS));
+
+        // Acima do limite a conexão é encerrada sem handshake
+        let other = start_node("rede-teste");
+        assert!(other.connect(node.local_addr()).is_err());
+        assert_eq!(node.peer_count(), MAX_PEERS);
+
+        // Uma vaga liberada aceita uma nova conexão
+        peers.pop();
+        assert!(wait_until(|| node.peer_count() == MAX_PEERS - 1));
+        assert!(wait_until(|| other.connect(node.local_addr()).is_ok()));
+        assert!(wait_until(|| node.peer_count() == MAX_PEERS));
+    }
+
+    #[test]
+    fn test_seen_items() {
+        // Bloco recusado (anterior desconhecido) é importado quando chega de novo
+        let source = start_seeded_node(2);
+        let node = start_node("rede-teste");
+        let (mut stream, _reader) = raw_peer(&node);
+        let blocks = source.blockchain().lock().unwrap().chain.clone();
+        let chain = node.blockchain();
+        send_raw(&mut stream, &Message::Block(blocks[2].clone()));
+        send_raw(&mut stream, &Message::Block(blocks[1].clone()));
+        assert!(wait_until(|| chain.lock().unwrap().height() == 1));
+        send_raw(&mut stream, &Message::Block(blocks[2].clone()));
+        assert!(wait_until(|| chain.lock().unwrap().height() == 2));
+        assert_eq!(node.shared.seen.lock().unwrap().len(), 2);
+
+        // Apenas os MAX_SEEN itens mais recentes são lembrados
+        let mut seen = SeenSet::default();
+        for i in 0..=MAX_SEEN {
+            assert!(seen.insert(i.to_string()));
+        }
+        assert_eq!(seen.len(), MAX_SEEN);
+        assert!(!seen.contains("0"));
+        assert!(!seen.insert(MAX_SEEN.to_string()));
+        assert!(seen.insert(String::from("0")));
+    }
+
+    #[test]
+    fn test_gossip_transactions_and_blocks() {
+        let first = start_node("rede-teste");
+        let second = start_node("rede-teste");
+        let third = start_node("rede-teste");
+
+        // first <-> second <-> third, a third só recebe os itens propagados pela second
+        first.connect(second.local_addr()).unwrap();
+        second.connect(third.local_addr()).unwrap();
+        assert!(wait_until(
+            || second.peer_count() == 2 && third.peer_count() == 1
+        ));
+
+        let id = first.submit_transaction(signed_transfer(0, 4.0));
+        let third_chain = third.blockchain();
+        assert!(wait_until(|| third_chain
+            .lock()
+            .unwrap()
+            .pending_transactions
+            .contains_key(&id)));
+
+        let block = first.mine_block();
+        assert!(wait_until(|| third_chain.lock().unwrap().height() == 1));
+        let third_chain = third_chain.lock().unwrap();
+        assert_eq!(third_chain.last_block().hash, block.hash);
+        assert!(third_chain.pending_transactions.is_empty());
+        assert_eq!(
+            third_chain.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            4.0
+        );
+    }
+
+    #[test]
+    fn test_rpc_submit() {
+        use crate::rpc::{self, SubmitHook};
+        use serde_json::json;
+
+        let first = start_node("rede-teste");
+        let second = start_node("rede-teste");
+        first.connect(second.local_addr()).unwrap();
+        assert!(wait_until(|| second.peer_count() == 1));
+
+        // O tx_submit do RPC passa pelo nó: a transação é propagada
+        let node = first.clone();
+        let submit: SubmitHook = Arc::new(move |transaction| node.submit_checked(transaction));
+        let tx_submit = |transaction: Transaction| {
+            let body = json!({ "jsonrpc": "2.0", "method": "tx_submit", "params": [transaction], "id": 1 });
+            rpc::handle_body(&first.blockchain(), &submit, body.to_string().as_bytes()).unwrap()
+        };
+        let id = tx_submit(signed_transfer(0, 1.0))["result"]
+            .as_u64()
+            .unwrap();
+        let second_chain = second.blockchain();
+        assert!(wait_until(|| second_chain
+            .lock()
+            .unwrap()
+            .pending_transactions
+            .contains_key(&id)));
+        assert_eq!(
+            tx_submit(signed_transfer(0, 1.0))["error"]["code"],
+            rpc::INVALID_PARAMS
+        );
+
+        // A quinta transação completa o bloco, que é anunciado aos peers
+        for nonce in 1..5 {
+            tx_submit(signed_transfer(nonce, 1.0));
+        }
+        assert_eq!(first.blockchain().lock().unwrap().height(), 1);
+        assert!(wait_until(|| second_chain.lock().unwrap().height() == 1));
+        let second_chain = second_chain.lock().unwrap();
+        assert_eq!(
+            second_chain.last_block().hash,
+            first.blockchain().lock().unwrap().last_block().hash
+        );
+        assert!(second_chain.pending_transactions.is_empty());
+    }
+
+    #[test]
+    fn test_deduplication() {
+        let first = start_node("rede-teste");
+        let second = start_node("rede-teste");
+        let third = start_node("rede-teste");
+
+        // Ciclo first -> second -> third -> first, os itens não devem circular para sempre
+        first.connect(second.local_addr()).unwrap();
+        second.connect(third.local_addr()).unwrap();
+        third.connect(first.local_addr()).unwrap();
+        assert!(wait_until(|| first.peer_count() == 2
+            && second.peer_count() == 2
+            && third.peer_count() == 2));
+
+        first.submit_transaction(signed_transfer(0, 4.0));
+        first.mine_block();
+        for node in [&first, &second, &third] {
+            let chain = node.blockchain();
+            assert!(wait_until(|| chain.lock().unwrap().height() == 1));
+        }
+        assert_eq!(first.shared.seen.lock().unwrap().len(), 2);
+        assert_eq!(third.shared.seen.lock().unwrap().len(), 2);
+    }
+
+    // Nó com `blocks` blocos minerados, cada bloco com uma transferência
+    fn start_seeded_node(blocks: u64) -> Node {
+        let node = start_node("rede-teste");
+        for nonce in 0..blocks {
+            node.submit_transaction(signed_transfer(nonce, 1.0));
+            node.mine_block();
+        }
+        node
+    }
+
+    #[test]
+    fn test_sync() {
+        let source = start_seeded_node(20);
+        let other = start_seeded_node(3);
+        let node = start_node("rede-teste");
+
+        // Sincroniza com o peer de maior altura
+        node.connect(other.local_addr()).unwrap();
+        node.connect(source.local_addr()).unwrap();
+        assert_eq!(node.sync().unwrap(), 20);
+
+        let chain = node.blockchain();
+        let chain = chain.lock().unwrap();
+        assert_eq!(chain.height(), 20);
+        assert_eq!(
+            chain.last_block().hash,
+            source.blockchain().lock().unwrap().last_block().hash
+        );
+        assert_eq!(
+            chain.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            20.0
+        );
+        assert!(chain.is_chain_valid());
+        drop(chain);
+
+        // Já atualizado, nada a importar
+        assert_eq!(node.sync().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sync_fork() {
+        // Nó local em outro ramo desde o genesis, mais longo que um lote
+        let source = start_seeded_node(SYNC_BATCH_SIZE + 10);
+        let node = start_node("rede-teste");
+        for nonce in 0..SYNC_BATCH_SIZE + 4 {
+            node.submit_transaction(signed_transfer(nonce, 2.0));
+            node.mine_block();
+        }
+        let chain = node.blockchain();
+        assert_eq!(
+            chain
+                .lock()
+                .unwrap()
+                .balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            40.0
+        );
+
+        // O ramo do peer é baixado a partir do ancestral comum e a cadeia é reorganizada
+        node.connect(source.local_addr()).unwrap();
+        assert_eq!(node.sync().unwrap(), SYNC_BATCH_SIZE + 10);
+        let chain = chain.lock().unwrap();
+        assert_eq!(chain.height(), SYNC_BATCH_SIZE + 10);
+        assert_eq!(
+            chain.last_block().hash,
+            source.blockchain().lock().unwrap().last_block().hash
+        );
+        assert_eq!(
+            chain.balance("0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"),
+            (SYNC_BATCH_SIZE + 10) as f64
+        );
+        assert!(chain.is_chain_valid());
+        drop(chain);
+        assert_eq!(node.sync().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sync_resume_after_restart() {
+        use crate::storage::HostStorage;
+
+        let source = start_seeded_node(20);
+
+        // Primeiro lote gravado no storage do host, depois o nó é encerrado
+        let config = NodeConfig {
+            chain_id: String::from("rede-teste"),
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+        };
+        let blockchain = Blockchain::with_storage(Box::new(HostStorage)).unwrap();
+        let node = Node::start(config.clone(), Arc::new(Mutex::new(blockchain))).unwrap();
+        node.connect(source.local_addr()).unwrap();
+        assert_eq!(node.sync_batch().unwrap(), SYNC_BATCH_SIZE);
+        drop(node);
+
+        // O nó reiniciado carrega os blocos gravados e continua da altura atual
+        let blockchain = Blockchain::with_storage(Box::new(HostStorage)).unwrap();
+        assert_eq!(blockchain.height(), SYNC_BATCH_SIZE);
+        let node = Node::start(config, Arc::new(Mutex::new(blockchain))).unwrap();
+        node.connect(source.local_addr()).unwrap();
+        assert_eq!(node.sync().unwrap(), 20 - SYNC_BATCH_SIZE);
+        assert_eq!(node.blockchain().lock().unwrap().height(), 20);
+    }
+}
@@ -0,0 +1,138 @@
+/* Mod Receipt
+** - Recibo gerado para cada transação aplicada em um bloco: status, gas consumido,
+**   eventos emitidos pelos contratos e a raiz do estado após a transação
+** - Os recibos de um bloco são gravados em `receipts/<id do bloco>` e o índice
+**   `receipt/<id da transação>` aponta para o bloco que contém a transação
+** - A raiz dos recibos (raiz de Merkle das hashes dos recibos) faz parte do
+**   cabeçalho do bloco e entra no cálculo da hash do bloco
+*/
+use crate::{
+    contract::ExecutionStatus,
+    storage::{load, save, Storage},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Evento emitido por um contrato com a função do host `log`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub contract: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub transaction_id: u64,
+    pub block_id: u64,
+    pub status: ExecutionStatus,
+    pub gas_used: u64,
+    pub events: Vec<Event>,
+    // Raiz do estado após aplicar a transação
+    pub state_root: String,
+}
+
+impl Receipt {
+    pub fn is_success(&self) -> bool {
+        self.status == ExecutionStatus::Success
+    }
+
+    pub fn hash(&self) -> String {
+        let data = serde_json::to_vec(self).expect("Erro ao serializar recibo");
+        format!("{:x}", Sha256::digest(data))
+    }
+}
+
+/* Raiz de Merkle de uma lista de hashes: cada nível combina pares de hashes,
+** o último elemento de um nível ímpar é combinado com ele mesmo
+*/
+pub fn merkle_root(leaves: &[String]) -> String {
+    if leaves.is_empty() {
+        return format!("{:x}", Sha256::digest(b""));
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let right = pair.get(1).unwrap_or(&pair[0]);
+                format!("{:x}", Sha256::digest(format!("{}{}", pair[0], right)))
+            })
+            .collect();
+    }
+    level.remove(0)
+}
+
+// Raiz dos recibos, os recibos devem estar ordenados pelo id da transação
+pub fn receipts_root(receipts: &[Receipt]) -> String {
+    let hashes = receipts.iter().map(Receipt::hash).collect::<Vec<_>>();
+    merkle_root(&hashes)
+}
+
+fn block_receipts_key(block_id: u64) -> String {
+    format!("receipts/{}", block_id)
+}
+
+fn transaction_key(transaction_id: u64) -> String {
+    format!("receipt/{}", transaction_id)
+}
+
+// Grava os recibos do bloco e o índice de transação para bloco
+pub fn save_receipts(storage: &mut dyn Storage, block_id: u64, receipts: &[Receipt]) {
+    save(storage, &block_receipts_key(block_id), &receipts);
+    for receipt in receipts {
+        save(storage, &transaction_key(receipt.transaction_id), &block_id);
+    }
+}
+
+pub fn get_block_receipts(storage: &dyn Storage, block_id: u64) -> Vec<Receipt> {
+    load(storage, &block_receipts_key(block_id)).unwrap_or_default()
+}
+
+pub fn get_receipt(storage: &dyn Storage, transaction_id: u64) -> Option<Receipt> {
+    let block_id = load::<u64>(storage, &transaction_key(transaction_id))?;
+    get_block_receipts(storage, block_id)
+        .into_iter()
+        .find(|receipt| receipt.transaction_id == transaction_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn receipt(transaction_id: u64) -> Receipt {
+        Receipt {
+            transaction_id,
+            block_id: 1,
+            status: ExecutionStatus::Success,
+            gas_used: 0,
+            events: vec![],
+            state_root: String::from("0x0"),
+        }
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let leaves = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let root = merkle_root(&leaves);
+        assert_eq!(root.len(), 64);
+        assert_eq!(root, merkle_root(&leaves));
+
+        // Alterar qualquer folha altera a raiz
+        let changed = vec!["a".to_string(), "b".to_string(), "d".to_string()];
+        assert_ne!(root, merkle_root(&changed));
+
+        // Uma única folha é a própria raiz
+        assert_eq!(merkle_root(&leaves[..1]), "a");
+    }
+
+    #[test]
+    fn test_save_and_get_receipts() {
+        let mut storage = MemoryStorage::new();
+        save_receipts(&mut storage, 1, &[receipt(1), receipt(2)]);
+
+        assert_eq!(get_block_receipts(&storage, 1).len(), 2);
+        assert_eq!(get_receipt(&storage, 2), Some(receipt(2)));
+        assert_eq!(get_receipt(&storage, 3), None);
+    }
+}
@@ -0,0 +1,596 @@
+/* Mod Rpc
+** - Servidor HTTP JSON-RPC 2.0 do nó, escuta apenas em localhost. Pedidos com
+**   Host diferente do endereço de escuta ou de outra origem são recusados com
+**   403 (`http::check_origin`)
+** - Cada pedido é um POST com o corpo JSON-RPC, pedidos em lote (lista) e
+**   notificações (pedidos sem id, sem resposta) seguem a especificação 2.0
+** - Métodos, parâmetros por posição:
+**    - chain_getBlock [id] e chain_getBlockByHash [hash]: bloco da cadeia ou null
+**    - chain_height: altura da cadeia
+**    - chain_validate: resultado de `is_chain_valid`
+**    - chain_getGenesis: parâmetros do genesis (inclusive o modelo `ledger`)
+**    - consensus_validators: validadores atuais da prova de autoridade
+**    - stake_getValidators: participação atual dos validadores (prova de participação)
+**    - stake_getDelegations [endereço]: valor vinculado pelo endereço a cada validador
+**    - tx_submit [transação]: adiciona a transação às pendentes, retorna o id.
+**      Transações sem assinatura válida do remetente, com nonce já utilizado
+**      e, no modo UTXO, gastos duplos são recusados. No nó (`with_submit`) a
+**      transação passa por `Node::submit_checked`, que a propaga aos peers e
+**      anuncia o bloco completado por ela
+**    - tx_get [id]: transação pendente ou incluída em um bloco (com o recibo)
+**    - account_getBalance [endereço]: saldo da conta
+**    - account_getNonce [endereço]: nonce da próxima transação do endereço,
+**      depois das transações pendentes
+**    - account_getBalanceAt [endereço, bloco]: saldo da conta depois do bloco
+**    - account_getHistory [endereço, cursor?, limite?]: página das transferências
+**      de entrada e saída do endereço, com o cursor da próxima página
+**    - account_getUtxos [endereço]: saídas disponíveis do endereço (modo UTXO)
+**    - account_getProof [endereço, bloco]: prova da conta na raiz do estado do
+**      bloco, checada pelo cliente com `state::verify_account_proof`
+**    - mempool_list: transações pendentes ordenadas pelo id
+*/
+use crate::{
+    blockchain::Blockchain, debug, hash::Hash, http, info, transaction::Transaction, warn,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{
+    io::BufReader,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+// Códigos de erro definidos pela especificação JSON-RPC 2.0
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+// Quantidade máxima de transferências por página do histórico
+const HISTORY_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    // Endereço de escuta, precisa ser um endereço de loopback
+    pub listen_addr: SocketAddr,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        RpcConfig {
+            listen_addr: "127.0.0.1:8545".parse().unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: &str) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+/* Recebe as transações do `tx_submit`: checa, adiciona às pendentes e retorna
+** o id. Chamada sem a trava da blockchain
+*/
+pub type SubmitHook = Arc<dyn Fn(Transaction) -> Result<u64, String> + Send + Sync>;
+
+// Submissão direta na blockchain, sem propagação para peers
+pub fn local_submit(blockchain: Arc<Mutex<Blockchain>>) -> SubmitHook {
+    Arc::new(move |transaction| {
+        let mut blockchain = blockchain.lock().unwrap();
+        blockchain.check_transaction(&transaction)?;
+        Ok(blockchain.submit_transaction(transaction))
+    })
+}
+
+pub struct RpcServer {
+    local_addr: SocketAddr,
+}
+
+impl RpcServer {
+    // Inicia o servidor com as transações submetidas direto na blockchain
+    pub fn start(
+        config: RpcConfig,
+        blockchain: Arc<Mutex<Blockchain>>,
+    ) -> Result<RpcServer, String> {
+        let submit = local_submit(blockchain.clone());
+        RpcServer::with_submit(config, blockchain, submit)
+    }
+
+    // Inicia o servidor: aceita conexões em uma thread, uma thread por conexão
+    pub fn with_submit(
+        config: RpcConfig,
+        blockchain: Arc<Mutex<Blockchain>>,
+        submit: SubmitHook,
+    ) -> Result<RpcServer, String> {
+        if !config.listen_addr.ip().is_loopback() {
+            return Err(String::from("O servidor RPC só pode escutar em localhost"));
+        }
+        let listener = TcpListener::bind(config.listen_addr).map_err(|erro| erro.to_string())?;
+        let local_addr = listener.local_addr().map_err(|erro| erro.to_string())?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let blockchain = blockchain.clone();
+                let submit = submit.clone();
+                thread::spawn(move || {
+                    if let Err(erro) = handle_connection(stream, &blockchain, &submit) {
+                        warn!("Erro na conexão RPC"; erro = erro);
+                    }
+                });
+            }
+        });
+
+        info!("Servidor RPC iniciado"; addr = local_addr);
+        Ok(RpcServer { local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    blockchain: &Mutex<Blockchain>,
+    submit: &SubmitHook,
+) -> Result<(), String> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(|erro| erro.to_string())?);
+    let request = http::read_request(&mut reader)?;
+    let local_addr = stream.local_addr().map_err(|erro| erro.to_string())?;
+    if let Err(erro) = http::check_origin(&request, local_addr) {
+        warn!("Pedido RPC recusado"; erro = erro);
+        return http::write_response(&mut stream, "403 Forbidden", "text/plain", erro.as_bytes());
+    }
+    if request.method != "POST" {
+        return http::write_response(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Utilize POST com um pedido JSON-RPC",
+        );
+    }
+
+    match handle_body(blockchain, submit, &request.body) {
+        Some(response) => http::write_response(
+            &mut stream,
+            "200 OK",
+            "application/json",
+            response.to_string().as_bytes(),
+        ),
+        None => http::write_response(&mut stream, "204 No Content", "application/json", b""),
+    }
+}
+
+/* Processa o corpo da requisição: um pedido ou um lote de pedidos.
+** Retorna None quando não existe resposta (apenas notificações)
+*/
+pub fn handle_body(
+    blockchain: &Mutex<Blockchain>,
+    submit: &SubmitHook,
+    body: &[u8],
+) -> Option<Value> {
+    let value = match serde_json::from_slice::<Value>(body) {
+        Ok(value) => value,
+        Err(_) => return Some(error_response(Value::Null, PARSE_ERROR, "JSON inválido")),
+    };
+    match value {
+        Value::Array(requests) if requests.is_empty() => {
+            Some(error_response(Value::Null, INVALID_REQUEST, "Lote vazio"))
+        }
+        Value::Array(requests) => {
+            let responses = requests
+                .into_iter()
+                .filter_map(|request| handle_request(blockchain, submit, request))
+                .collect::<Vec<_>>();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => handle_request(blockchain, submit, request),
+    }
+}
+
+fn handle_request(
+    blockchain: &Mutex<Blockchain>,
+    submit: &SubmitHook,
+    request: Value,
+) -> Option<Value> {
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            return Some(error_response(
+                Value::Null,
+                INVALID_REQUEST,
+                "Pedido inválido",
+            ))
+        }
+    };
+    debug!("Pedido RPC"; method = request.method);
+    // A submissão pode propagar a transação, por isso não segura a trava da blockchain
+    let result = match request.method.as_str() {
+        "tx_submit" => param::<Transaction>(&request.params, 0).and_then(|transaction| {
+            submit(transaction)
+                .map(|id| json!(id))
+                .map_err(|erro| RpcError::new(INVALID_PARAMS, &erro))
+        }),
+        method => call(&mut blockchain.lock().unwrap(), method, &request.params),
+    };
+
+    // Notificações não recebem resposta
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(erro) => error_response(id, erro.code, &erro.message),
+    })
+}
+
+pub(crate) fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message },
+        "id": id,
+    })
+}
+
+// Executa um método sobre a blockchain, exceto o `tx_submit` (`SubmitHook`)
+pub fn call(blockchain: &mut Blockchain, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "chain_getBlock" => {
+            let id = param::<u64>(params, 0)?;
+            Ok(json!(blockchain.chain.get(id as usize)))
+        }
+        "chain_getBlockByHash" => {
+            let hash = param::<Hash>(params, 0)?;
+            Ok(json!(blockchain.block_by_hash(&hash)))
+        }
+        "chain_height" => Ok(json!(blockchain.height())),
+        "chain_validate" => Ok(json!(blockchain.is_chain_valid())),
+        "chain_getGenesis" => Ok(json!(blockchain.genesis())),
+        "consensus_validators" => Ok(json!(blockchain.validators())),
+        "stake_getValidators" => Ok(json!(blockchain.stake_validators())),
+        "stake_getDelegations" => {
+            let address = param::<String>(params, 0)?;
+            Ok(json!(blockchain.delegations(&address)))
+        }
+        "tx_get" => {
+            let id = param::<u64>(params, 0)?;
+            Ok(transaction_info(blockchain, id))
+        }
+        "account_getBalance" => {
+            let address = param::<String>(params, 0)?;
+            Ok(json!(blockchain.balance(&address)))
+        }
+        "account_getNonce" => {
+            let address = param::<String>(params, 0)?;
+            Ok(json!(blockchain.next_nonce(&address)))
+        }
+        "account_getBalanceAt" => {
+            let address = param::<String>(params, 0)?;
+            let block_id = param::<u64>(params, 1)?;
+            blockchain
+                .balance_at(&address, block_id)
+                .map(|balance| json!(balance))
+                .map_err(|erro| RpcError::new(INVALID_PARAMS, &erro))
+        }
+        "account_getHistory" => {
+            let address = param::<String>(params, 0)?;
+            let cursor = optional_param::<u64>(params, 1)?;
+            let limit = optional_param::<usize>(params, 2)?
+                .unwrap_or(HISTORY_PAGE_SIZE)
+                .clamp(1, HISTORY_PAGE_SIZE);
+            Ok(json!(blockchain.history_page(&address, cursor, limit)))
+        }
+        "account_getProof" => {
+            let address = param::<String>(params, 0)?;
+            let block_id = param::<u64>(params, 1)?;
+            blockchain
+                .account_proof(&address, block_id)
+                .map(|proof| json!(proof))
+                .map_err(|erro| RpcError::new(INVALID_PARAMS, &erro))
+        }
+        "account_getUtxos" => {
+            let address = param::<String>(params, 0)?;
+            Ok(json!(blockchain.unspent_outputs(&address)))
+        }
+        "mempool_list" => {
+            let mut pending = blockchain.pending_transactions.iter().collect::<Vec<_>>();
+            pending.sort_by_key(|(id, _)| **id);
+            let pending = pending
+                .into_iter()
+                .map(|(id, transaction)| json!({ "id": id, "transaction": transaction }))
+                .collect::<Vec<_>>();
+            Ok(Value::Array(pending))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, "Método não encontrado")),
+    }
+}
+
+// Parâmetro da posição `index`
+fn param<T: DeserializeOwned>(params: &Value, index: usize) -> Result<T, RpcError> {
+    params
+        .get(index)
+        .cloned()
+        .and_then(|value| serde_json::from_value(value).ok())
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, &format!("Parâmetro {} inválido", index)))
+}
+
+// Parâmetro opcional da posição `index`, ausente ou null
+fn optional_param<T: DeserializeOwned>(
+    params: &Value,
+    index: usize,
+) -> Result<Option<T>, RpcError> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(_) => param(params, index).map(Some),
+    }
+}
+
+// Transação pendente ou incluída em um bloco, null se não existe
+fn transaction_info(blockchain: &Blockchain, id: u64) -> Value {
+    if let Some(transaction) = blockchain.pending_transactions.get(&id) {
+        return json!({ "id": id, "status": "pending", "transaction": transaction });
+    }
+    match blockchain.find_transaction(id) {
+        Some((block, transaction)) => json!({
+            "id": id,
+            "status": "included",
+            "block_id": block.id,
+            "transaction": transaction,
+            "receipt": blockchain.receipt(id),
+        }),
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::{self, tests::test_account};
+    use std::io::{Read, Write};
+
+    // Envia uma requisição HTTP e retorna a linha de status e o corpo da resposta
+    fn post(addr: SocketAddr, method: &str, body: &str) -> (String, String) {
+        let headers = format!("Host: localhost:{}\r\n", addr.port());
+        send(addr, method, &headers, body)
+    }
+
+    fn send(addr: SocketAddr, method: &str, headers: &str, body: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // O servidor pode encerrar a conexão antes de ler todo o pedido (cabeçalhos demais)
+        let _ = write!(
+            stream,
+            "{} / HTTP/1.1\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            headers,
+            body.len(),
+            body
+        );
+        // Conexão encerrada sem resposta retorna o status vazio
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+        (
+            head.lines().next().unwrap_or_default().to_string(),
+            body.to_string(),
+        )
+    }
+
+    fn rpc(addr: SocketAddr, method: &str, params: Value) -> Value {
+        let body = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        let (_, response) = post(addr, "POST", &body.to_string());
+        let response = serde_json::from_str::<Value>(&response).unwrap();
+        assert_eq!(response["id"], 1);
+        response
+    }
+
+    fn start_server() -> SocketAddr {
+        let config = RpcConfig {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+        };
+        let blockchain = Arc::new(Mutex::new(Blockchain::new()));
+        RpcServer::start(config, blockchain).unwrap().local_addr()
+    }
+
+    #[test]
+    fn test_rpc_methods() {
+        let addr = start_server();
+        let (key, from) = test_account(101);
+        // Transferência assinada com o próximo nonce do remetente, consultado no nó
+        let transfer = || {
+            let nonce = rpc(addr, "account_getNonce", json!([from]))["result"].clone();
+            let mut transaction =
+                Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 4.0);
+            transaction.nonce = nonce.as_u64().unwrap();
+            wallet::sign_transaction(&key, &mut transaction).unwrap();
+            json!(transaction)
+        };
+
+        let transaction = transfer();
+        assert_eq!(rpc(addr, "tx_submit", json!([transaction]))["result"], 1);
+        // A mesma transação não é aceita novamente
+        let response = rpc(addr, "tx_submit", json!([transaction]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(rpc(addr, "account_getNonce", json!([from]))["result"], 1);
+        let mempool = rpc(addr, "mempool_list", json!([]));
+        assert_eq!(mempool["result"][0]["id"], 1);
+        assert_eq!(
+            rpc(addr, "tx_get", json!([1]))["result"]["status"],
+            "pending"
+        );
+
+        // A quinta transação completa o bloco
+        for _ in 0..4 {
+            rpc(addr, "tx_submit", json!([transfer()]));
+        }
+        assert_eq!(rpc(addr, "chain_height", json!([]))["result"], 1);
+        assert_eq!(rpc(addr, "mempool_list", json!([]))["result"], json!([]));
+
+        let block = rpc(addr, "chain_getBlock", json!([1]))["result"].clone();
+        assert_eq!(block["id"], 1);
+        let by_hash = rpc(addr, "chain_getBlockByHash", json!([block["hash"]]));
+        assert_eq!(by_hash["result"], block);
+        assert_eq!(
+            rpc(addr, "chain_getBlock", json!([7]))["result"],
+            Value::Null
+        );
+
+        let included = rpc(addr, "tx_get", json!([1]))["result"].clone();
+        assert_eq!(included["status"], "included");
+        assert_eq!(included["block_id"], 1);
+        assert_eq!(included["receipt"]["status"], "Success");
+        assert_eq!(rpc(addr, "tx_get", json!([99]))["result"], Value::Null);
+
+        let balance = rpc(
+            addr,
+            "account_getBalance",
+            json!(["0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"]),
+        );
+        assert_eq!(balance["result"], 20.0);
+        assert_eq!(rpc(addr, "chain_validate", json!([]))["result"], true);
+
+        let proof = rpc(
+            addr,
+            "account_getProof",
+            json!(["0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 1]),
+        )["result"]
+            .clone();
+        let proof = serde_json::from_value::<crate::state::AccountProof>(proof).unwrap();
+        let root = block["state_root"].as_str().unwrap();
+        let account = crate::state::verify_account_proof(root, &proof).unwrap();
+        assert_eq!(account.balance, 20.0);
+
+        let address = "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962";
+        let balance_at = rpc(addr, "account_getBalanceAt", json!([address, 0]));
+        assert_eq!(balance_at["result"], 0.0);
+        let history = rpc(addr, "account_getHistory", json!([address, null, 3]))["result"].clone();
+        assert_eq!(history["transfers"].as_array().unwrap().len(), 3);
+        assert_eq!(history["transfers"][0]["direction"], "incoming");
+        assert_eq!(history["next_cursor"], 3);
+        let history = rpc(addr, "account_getHistory", json!([address, 3]))["result"].clone();
+        assert_eq!(history["transfers"].as_array().unwrap().len(), 2);
+        assert_eq!(history["next_cursor"], Value::Null);
+
+        let genesis = rpc(addr, "chain_getGenesis", json!([]))["result"].clone();
+        assert_eq!(genesis["chain_id"], "local");
+        assert_eq!(genesis["ledger"], "account");
+        assert_eq!(
+            rpc(addr, "consensus_validators", json!([]))["result"],
+            json!([])
+        );
+        assert_eq!(
+            rpc(addr, "stake_getDelegations", json!(["0x1"]))["result"],
+            json!({})
+        );
+        assert_eq!(
+            rpc(addr, "account_getUtxos", json!(["0x1"]))["result"],
+            json!([])
+        );
+    }
+
+    #[test]
+    fn test_rpc_errors() {
+        let addr = start_server();
+
+        let response = rpc(addr, "chain_inexistente", json!([]));
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let response = rpc(addr, "chain_getBlock", json!(["um"]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+        let forged = json!({
+            "from": "0xEf8801eaf234ff82801821FFe2d780237F9967",
+            "to": "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            "value": 4.0,
+            "signature": { "public_key": "00", "signature": "00" },
+        });
+        let response = rpc(addr, "tx_submit", json!([forged]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        // Transações sem assinatura são recusadas
+        let unsigned = json!({
+            "from": "0xEf8801eaf234ff82801821FFe2d780237F9967",
+            "to": "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            "value": 4.0,
+        });
+        let response = rpc(addr, "tx_submit", json!([unsigned]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(response["error"]["message"], "Transação sem assinatura");
+
+        let (status, body) = post(addr, "POST", "{ invalido");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let response = serde_json::from_str::<Value>(&body).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        let (status, _) = post(addr, "GET", "");
+        assert_eq!(status, "HTTP/1.1 405 Method Not Allowed");
+
+        // Host de outro nome (DNS rebinding) ou pedido de outra origem
+        let body = json!({ "jsonrpc": "2.0", "method": "chain_height", "id": 1 }).to_string();
+        let rebinding = "Host: attacker.example:8545\r\n";
+        let (status, _) = send(addr, "POST", rebinding, &body);
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        let cross_origin = format!(
+            "Host: localhost:{}\r\nOrigin: http://attacker.example\r\n",
+            addr.port()
+        );
+        let (status, _) = send(addr, "POST", &cross_origin, &body);
+        assert_eq!(status, "HTTP/1.1 403 Forbidden");
+        let same_origin = format!(
+            "Host: 127.0.0.1:{0}\r\nOrigin: http://127.0.0.1:{0}\r\n",
+            addr.port()
+        );
+        let (status, _) = send(addr, "POST", &same_origin, &body);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+
+        // Cabeçalhos demais encerram a conexão sem resposta
+        let many_headers =
+            format!("Host: localhost:{}\r\n", addr.port()) + &"X-Extra: 1\r\n".repeat(100);
+        let (status, _) = send(addr, "POST", &many_headers, &body);
+        assert!(status.is_empty());
+
+        // Apenas endereços de loopback
+        let config = RpcConfig {
+            listen_addr: "0.0.0.0:0".parse().unwrap(),
+        };
+        assert!(RpcServer::start(config, Arc::new(Mutex::new(Blockchain::new()))).is_err());
+    }
+
+    #[test]
+    fn test_rpc_batch_and_notifications() {
+        let addr = start_server();
+        let batch = json!([
+            { "jsonrpc": "2.0", "method": "chain_height", "id": 1 },
+            { "jsonrpc": "2.0", "method": "chain_validate", "id": 2 },
+            { "jsonrpc": "2.0", "method": "chain_height" },
+            { "jsonrpc": "1.0", "method": "chain_height", "id": 3 },
+        ]);
+        let (_, body) = post(addr, "POST", &batch.to_string());
+        let responses = serde_json::from_str::<Value>(&body).unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 3);
+        assert_eq!(responses[0]["result"], 0);
+        assert_eq!(responses[1]["result"], true);
+        assert_eq!(responses[2]["error"]["code"], INVALID_REQUEST);
+
+        // Somente notificações, sem resposta
+        let notification = json!({ "jsonrpc": "2.0", "method": "chain_height" });
+        let (status, body) = post(addr, "POST", &notification.to_string());
+        assert_eq!(status, "HTTP/1.1 204 No Content");
+        assert!(body.is_empty());
+    }
+}
@@ -0,0 +1,576 @@
+/* Mod Stake
+** - Prova de participação, escolhida no genesis (`consensus: {"type": "stake"}`)
+** - Vínculo (bond): transações `Bond` movem `value` do saldo do remetente para
+**   a participação do validador (chave pública) indicado. Vincular a outro
+**   validador é a delegação, a participação do validador é a soma dos vínculos
+** - Desvínculo (unbond): transações `Unbond` retiram a participação, que volta
+**   ao saldo depois de `unbonding_period` blocos
+** - Épocas de `epoch_length` blocos: os validadores e suas participações são
+**   fixados no fim da época anterior. O produtor de cada bloco é sorteado com
+**   peso da participação, a partir da altura do bloco
+** - Recompensa: cada bloco cria `block_reward`, dividida entre os delegadores
+**   do produtor na proporção dos vínculos
+** - Punição (slashing): transações `Evidence` com dois cabeçalhos assinados
+**   pelo mesmo produtor na mesma altura removem `slash_fraction` dos vínculos
+**   e dos desvínculos pendentes do validador, que sai do conjunto. O nó que
+**   recebe o bloco conflitante envia a evidência (`Blockchain::import_block`)
+** - Todo o estado do staking fica na árvore do estado, em `stake/...`
+*/
+use crate::{
+    block::{Block, BlockHeader},
+    consensus::{self, Consensus},
+    genesis::StakeParams,
+    state,
+    storage::{encode_hex, load, save, Storage},
+    wallet::{derive_address, parse_public_key},
+};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+const PARAMS_KEY: &str = "stake/params";
+const BONDS_KEY: &str = "stake/bonds";
+const UNBONDING_KEY: &str = "stake/unbonding";
+const JAILED_KEY: &str = "stake/jailed";
+
+fn epoch_key(epoch: u64) -> String {
+    format!("stake/epoch/{}", epoch)
+}
+
+fn slashed_key(validator: &str, height: u64) -> String {
+    format!("stake/slashed/{}/{}", validator, height)
+}
+
+// Vínculos de cada validador: delegador -> valor vinculado
+pub type Bonds = BTreeMap<String, BTreeMap<String, f64>>;
+
+// Participação desvinculada, aguardando o fim do período de desvínculo
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unbonding {
+    pub delegator: String,
+    pub validator: String,
+    pub amount: f64,
+    // Altura do bloco em que o valor volta ao saldo do delegador
+    pub release_height: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorStake {
+    pub public_key: String,
+    pub stake: f64,
+}
+
+// Grava o valor no storage e na árvore do estado
+fn store<T: Serialize>(storage: &mut dyn Storage, key: &str, value: &T) {
+    save(storage, key, value);
+    let data = serde_json::to_vec(value).expect("Erro ao serializar staking");
+    state::record_change(storage, key.as_bytes(), Some(&data));
+}
+
+fn params(storage: &dyn Storage) -> Result<StakeParams, String> {
+    load(storage, PARAMS_KEY)
+        .ok_or_else(|| String::from("Staking exige o consenso por prova de participação"))
+}
+
+// Indica se a cadeia utiliza a prova de participação
+pub fn enabled(storage: &dyn Storage) -> bool {
+    params(storage).is_ok()
+}
+
+/* Estado inicial: parâmetros, vínculos dos validadores do genesis (pelo
+** endereço do próprio validador) e o conjunto da época 0
+*/
+pub fn init_state(storage: &mut dyn Storage, params: &StakeParams) -> Result<(), String> {
+    if params.epoch_length == 0 {
+        return Err(String::from("Época sem blocos"));
+    }
+    if !(0.0..=1.0).contains(&params.slash_fraction) {
+        return Err(String::from("Fração da punição inválida"));
+    }
+    if params.validators.is_empty() {
+        return Err(String::from("Prova de participação sem validadores"));
+    }
+    let mut bonds = Bonds::new();
+    for (public_key, stake) in &params.validators {
+        let address = derive_address(&parse_public_key(public_key)?);
+        if *stake <= 0.0 {
+            return Err(format!("Participação inválida do validador {}", public_key));
+        }
+        bonds
+            .entry(public_key.clone())
+            .or_default()
+            .insert(address, *stake);
+    }
+    store(storage, PARAMS_KEY, params);
+    store(storage, BONDS_KEY, &bonds);
+    snapshot_epoch(storage, 0);
+    Ok(())
+}
+
+pub fn bonds(storage: &dyn Storage) -> Bonds {
+    load(storage, BONDS_KEY).unwrap_or_default()
+}
+
+pub fn unbonding(storage: &dyn Storage) -> Vec<Unbonding> {
+    load(storage, UNBONDING_KEY).unwrap_or_default()
+}
+
+// Validadores punidos, fora do conjunto
+pub fn jailed(storage: &dyn Storage) -> Vec<String> {
+    load(storage, JAILED_KEY).unwrap_or_default()
+}
+
+// Participação atual de cada validador, sem os punidos
+pub fn validators(storage: &dyn Storage) -> Vec<ValidatorStake> {
+    let jailed = jailed(storage);
+    bonds(storage)
+        .into_iter()
+        .filter(|(public_key, _)| !jailed.contains(public_key))
+        .map(|(public_key, delegations)| ValidatorStake {
+            public_key,
+            stake: delegations.values().sum(),
+        })
+        .filter(|validator| validator.stake > 0.0)
+        .collect()
+}
+
+// Valor vinculado pelo delegador a cada validador
+pub fn delegations(storage: &dyn Storage, delegator: &str) -> BTreeMap<String, f64> {
+    bonds(storage)
+        .into_iter()
+        .filter_map(|(validator, delegations)| Some((validator, *delegations.get(delegator)?)))
+        .collect()
+}
+
+fn snapshot_epoch(storage: &mut dyn Storage, epoch: u64) {
+    let validators = validators(storage);
+    store(storage, &epoch_key(epoch), &validators);
+}
+
+// Validadores fixados para a época
+pub fn epoch_validators(storage: &dyn Storage, epoch: u64) -> Vec<ValidatorStake> {
+    load(storage, &epoch_key(epoch)).unwrap_or_default()
+}
+
+/* Produtor do bloco `block_id`: sorteio com peso da participação entre os
+** validadores da época, sem os punidos durante a época
+*/
+pub fn slot_producer(storage: &dyn Storage, block_id: u64) -> Result<String, String> {
+    let params = params(storage)?;
+    let jailed = jailed(storage);
+    let validators = epoch_validators(storage, block_id / params.epoch_length)
+        .into_iter()
+        .filter(|validator| !jailed.contains(&validator.public_key))
+        .collect::<Vec<_>>();
+    let total = validators
+        .iter()
+        .map(|validator| validator.stake)
+        .sum::<f64>();
+    if validators.is_empty() || total <= 0.0 {
+        return Err(String::from("Conjunto de validadores vazio"));
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"slot");
+    hasher.update(block_id.to_be_bytes());
+    let hash = hasher.finalize();
+    let draw = u64::from_be_bytes(hash[..8].try_into().unwrap()) as f64 / u64::MAX as f64 * total;
+    let mut accumulated = 0.0;
+    for validator in &validators {
+        accumulated += validator.stake;
+        if draw < accumulated {
+            return Ok(validator.public_key.clone());
+        }
+    }
+    Ok(validators.last().unwrap().public_key.clone())
+}
+
+// Vincula `amount` do saldo do delegador ao validador
+pub fn bond(
+    storage: &mut dyn Storage,
+    delegator: &str,
+    validator: &str,
+    amount: f64,
+) -> Result<(), String> {
+    params(storage)?;
+    if amount <= 0.0 {
+        return Err(String::from("Valor do vínculo inválido"));
+    }
+    parse_public_key(validator)?;
+    if jailed(storage).iter().any(|jailed| jailed == validator) {
+        return Err(String::from("Validador punido não recebe vínculos"));
+    }
+    if state::get_account(storage, delegator).balance < amount {
+        return Err(String::from("Saldo insuficiente para o vínculo"));
+    }
+    state::add_balance(storage, delegator, -amount);
+    let mut bonds = bonds(storage);
+    *bonds
+        .entry(validator.to_string())
+        .or_default()
+        .entry(delegator.to_string())
+        .or_default() += amount;
+    store(storage, BONDS_KEY, &bonds);
+    Ok(())
+}
+
+// Desvincula `amount`, que volta ao saldo depois do período de desvínculo
+pub fn unbond(
+    storage: &mut dyn Storage,
+    delegator: &str,
+    validator: &str,
+    amount: f64,
+    height: u64,
+) -> Result<(), String> {
+    let params = params(storage)?;
+    let mut bonds = bonds(storage);
+    let delegations = bonds
+        .get_mut(validator)
+        .ok_or_else(|| String::from("Vínculo inexistente"))?;
+    let bonded = delegations
+        .get_mut(delegator)
+        .ok_or_else(|| String::from("Vínculo inexistente"))?;
+    if amount <= 0.0 || amount > *bonded {
+        return Err(String::from("Valor do desvínculo inválido"));
+    }
+    *bonded -= amount;
+    if *bonded == 0.0 {
+        delegations.remove(delegator);
+    }
+    if delegations.is_empty() {
+        bonds.remove(validator);
+    }
+    store(storage, BONDS_KEY, &bonds);
+
+    let mut unbonding = unbonding(storage);
+    unbonding.push(Unbonding {
+        delegator: delegator.to_string(),
+        validator: validator.to_string(),
+        amount,
+        release_height: height + params.unbonding_period,
+    });
+    store(storage, UNBONDING_KEY, &unbonding);
+    Ok(())
+}
+
+/* Checa a evidência: dois blocos diferentes assinados pelo mesmo produtor na
+** mesma altura, nesta cadeia (`chain_id`)
+*/
+pub fn check_evidence(
+    first: &BlockHeader,
+    second: &BlockHeader,
+    chain_id: &str,
+) -> Result<(), String> {
+    if first.id != second.id || first.producer != second.producer || first.producer.is_empty() {
+        return Err(String::from(
+            "Evidência sem blocos do mesmo produtor na mesma altura",
+        ));
+    }
+    if first.hash == second.hash {
+        return Err(String::from("Evidência com o mesmo bloco"));
+    }
+    for header in [first, second] {
+        Block::from_parts(header.clone(), HashMap::new()).check_signature(chain_id)?;
+    }
+    Ok(())
+}
+
+/* Pune o produtor da evidência: `slash_fraction` dos vínculos e desvínculos
+** pendentes do validador é queimada e o validador sai do conjunto.
+** Retorna o valor removido
+*/
+pub fn slash(
+    storage: &mut dyn Storage,
+    first: &BlockHeader,
+    second: &BlockHeader,
+    chain_id: &str,
+) -> Result<f64, String> {
+    let params = params(storage)?;
+    check_evidence(first, second, chain_id)?;
+    let validator = &first.producer;
+    let key = slashed_key(validator, first.id);
+    if load::<bool>(storage, &key).is_some() {
+        return Err(String::from("Validador já punido nesta altura"));
+    }
+
+    let mut slashed = 0.0;
+    let mut bonds = bonds(storage);
+    for amount in bonds
+        .get_mut(validator)
+        .into_iter()
+        .flat_map(|d| d.values_mut())
+    {
+        let cut = *amount * params.slash_fraction;
+        *amount -= cut;
+        slashed += cut;
+    }
+    store(storage, BONDS_KEY, &bonds);
+    let mut unbonding = unbonding(storage);
+    for entry in unbonding
+        .iter_mut()
+        .filter(|entry| entry.validator == *validator)
+    {
+        let cut = entry.amount * params.slash_fraction;
+        entry.amount -= cut;
+        slashed += cut;
+    }
+    store(storage, UNBONDING_KEY, &unbonding);
+
+    let mut jailed = jailed(storage);
+    if !jailed.contains(validator) {
+        jailed.push(validator.clone());
+        store(storage, JAILED_KEY, &jailed);
+    }
+    store(storage, &key, &true);
+    Ok(slashed)
+}
+
+/* Fim do bloco: desvínculos vencidos voltam ao saldo, a recompensa é dividida
+** entre os delegadores do produtor e, no último bloco da época, os validadores
+** da próxima época são fixados
+*/
+pub fn end_block(storage: &mut dyn Storage, block_id: u64, producer: &str) {
+    let Ok(params) = params(storage) else {
+        return;
+    };
+    let (released, pending): (Vec<_>, Vec<_>) = unbonding(storage)
+        .into_iter()
+        .partition(|entry| entry.release_height <= block_id);
+    if !released.is_empty() {
+        for entry in &released {
+            state::add_balance(storage, &entry.delegator, entry.amount);
+        }
+        store(storage, UNBONDING_KEY, &pending);
+    }
+
+    if let Some(delegations) = bonds(storage).get(producer) {
+        let total = delegations.values().sum::<f64>();
+        if total > 0.0 {
+            for (delegator, amount) in delegations {
+                state::add_balance(storage, delegator, params.block_reward * amount / total);
+            }
+        }
+    }
+
+    if (block_id + 1).is_multiple_of(params.epoch_length) {
+        snapshot_epoch(storage, (block_id + 1) / params.epoch_length);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProofOfStake {
+    key: Option<SigningKey>,
+    chain_id: String,
+}
+
+impl ProofOfStake {
+    pub fn new(chain_id: &str) -> Self {
+        ProofOfStake {
+            key: None,
+            chain_id: chain_id.to_string(),
+        }
+    }
+
+    pub fn with_key(chain_id: &str, key: SigningKey) -> Self {
+        ProofOfStake {
+            key: Some(key),
+            chain_id: chain_id.to_string(),
+        }
+    }
+}
+
+impl Consensus for ProofOfStake {
+    fn prepare(&self, storage: &dyn Storage, block_id: u64) -> Result<String, String> {
+        let key = consensus::validator_key(self.key.as_ref())?;
+        let expected = slot_producer(storage, block_id)?;
+        let public_key = encode_hex(key.verifying_key().as_bytes());
+        if public_key != expected {
+            return Err(format!(
+                "Bloco {} pertence ao validador {}",
+                block_id, expected
+            ));
+        }
+        Ok(public_key)
+    }
+
+    fn seal(&self, block: &mut Block) -> Result<(), String> {
+        consensus::seal_block(
+            consensus::validator_key(self.key.as_ref())?,
+            &self.chain_id,
+            block,
+        )
+    }
+
+    fn verify(&self, storage: &dyn Storage, block: &Block) -> Result<(), String> {
+        if block.producer != slot_producer(storage, block.id)? {
+            return Err(String::from("Produtor fora da sua vez"));
+        }
+        consensus::check_seal(block, &self.chain_id)
+    }
+
+    fn finalize_block(&self, storage: &mut dyn Storage, block_id: u64, producer: &str) {
+        end_block(storage, block_id, producer);
+    }
+
+    // Participação fixada para a época do bloco, sem os validadores punidos
+    fn voting_power(&self, storage: &dyn Storage, block_id: u64) -> Vec<(String, f64)> {
+        let Ok(params) = params(storage) else {
+            return vec![];
+        };
+        let jailed = jailed(storage);
+        epoch_validators(storage, block_id / params.epoch_length)
+            .into_iter()
+            .filter(|validator| !jailed.contains(&validator.public_key))
+            .map(|validator| (validator.public_key, validator.stake))
+            .collect()
+    }
+
+    fn set_key(&mut self, key: SigningKey) {
+        self.key = Some(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{Hash, Sha256Hasher};
+    use crate::{
+        consensus::tests::{public_key, validator_keys},
+        storage::MemoryStorage,
+    };
+
+    fn stake_storage(stakes: &[f64], epoch_length: u64) -> (MemoryStorage, Vec<SigningKey>) {
+        let keys = validator_keys(stakes.len() as u8);
+        let params = StakeParams {
+            validators: keys
+                .iter()
+                .zip(stakes)
+                .map(|(key, stake)| (public_key(key), *stake))
+                .collect(),
+            epoch_length,
+            unbonding_period: 3,
+            block_reward: 2.0,
+            slash_fraction: 0.5,
+        };
+        let mut storage = MemoryStorage::new();
+        init_state(&mut storage, &params).unwrap();
+        (storage, keys)
+    }
+
+    fn address(key: &SigningKey) -> String {
+        derive_address(&key.verifying_key())
+    }
+
+    #[test]
+    fn test_bond_and_unbond() {
+        let (mut storage, keys) = stake_storage(&[10.0], 4);
+        let validator = public_key(&keys[0]);
+        state::add_balance(&mut storage, "0x1", 5.0);
+
+        assert!(bond(&mut storage, "0x1", &validator, 6.0).is_err());
+        bond(&mut storage, "0x1", &validator, 4.0).unwrap();
+        assert_eq!(state::get_account(&storage, "0x1").balance, 1.0);
+        assert_eq!(validators(&storage)[0].stake, 14.0);
+
+        // O valor desvinculado só volta ao saldo depois do período
+        assert!(unbond(&mut storage, "0x1", &validator, 5.0, 1).is_err());
+        unbond(&mut storage, "0x1", &validator, 4.0, 1).unwrap();
+        assert!(delegations(&storage, "0x1").is_empty());
+        end_block(&mut storage, 3, "");
+        assert_eq!(state::get_account(&storage, "0x1").balance, 1.0);
+        end_block(&mut storage, 4, "");
+        assert_eq!(state::get_account(&storage, "0x1").balance, 5.0);
+        assert!(unbonding(&storage).is_empty());
+    }
+
+    #[test]
+    fn test_rewards_and_epochs() {
+        let (mut storage, keys) = stake_storage(&[10.0], 4);
+        let validator = public_key(&keys[0]);
+        let other = public_key(&validator_keys(2)[1]);
+        state::add_balance(&mut storage, "0x1", 30.0);
+        bond(&mut storage, "0x1", &validator, 30.0).unwrap();
+
+        // Recompensa dividida na proporção dos vínculos (10 e 30)
+        end_block(&mut storage, 1, &validator);
+        assert_eq!(
+            state::get_account(&storage, &address(&keys[0])).balance,
+            0.5
+        );
+        assert_eq!(state::get_account(&storage, "0x1").balance, 1.5);
+
+        // Um novo validador só entra no conjunto na próxima época
+        state::add_balance(&mut storage, "0x2", 5.0);
+        bond(&mut storage, "0x2", &other, 5.0).unwrap();
+        end_block(&mut storage, 2, &validator);
+        assert_eq!(epoch_validators(&storage, 0).len(), 1);
+        assert!(epoch_validators(&storage, 1).is_empty());
+        end_block(&mut storage, 3, &validator);
+        let next = epoch_validators(&storage, 1);
+        assert_eq!(next.len(), 2);
+        assert!(next.contains(&ValidatorStake {
+            public_key: other,
+            stake: 5.0
+        }));
+    }
+
+    #[test]
+    fn test_weighted_selection() {
+        let (storage, keys) = stake_storage(&[30.0, 10.0], 400);
+        let heavy = public_key(&keys[0]);
+        let produced = (0..400)
+            .filter(|id| slot_producer(&storage, *id).unwrap() == heavy)
+            .count();
+        // Cerca de 3/4 dos blocos pertencem ao validador com 3/4 da participação
+        assert!((250..350).contains(&produced), "{}", produced);
+    }
+
+    #[test]
+    fn test_slashing() {
+        let (mut storage, keys) = stake_storage(&[10.0, 10.0], 20);
+        let validator = public_key(&keys[0]);
+        state::add_balance(&mut storage, "0x1", 4.0);
+        bond(&mut storage, "0x1", &validator, 4.0).unwrap();
+        unbond(&mut storage, "0x1", &validator, 2.0, 1).unwrap();
+
+        // Dois blocos diferentes na altura 5 assinados pelo mesmo validador
+        let signer = ProofOfStake::with_key("local", keys[0].clone());
+        let mut headers = vec![];
+        for receipts_root in ["a", "b"] {
+            let mut block = Block::new(
+                5,
+                Hash::ZERO,
+                receipts_root,
+                "",
+                HashMap::new(),
+                &Sha256Hasher,
+            );
+            block.set_producer(&validator, &Sha256Hasher);
+            signer.seal(&mut block).unwrap();
+            headers.push(block.header());
+        }
+        assert!(check_evidence(&headers[0], &headers[0], "local").is_err());
+        let mut forged = headers[1].clone();
+        forged.id = 6;
+        assert!(check_evidence(&headers[0], &forged, "local").is_err());
+        // Cabeçalhos assinados em outra rede não são evidência nesta
+        assert_eq!(
+            check_evidence(&headers[0], &headers[1], "outra-rede"),
+            Err(String::from("Assinatura do produtor invalida"))
+        );
+
+        assert_eq!(
+            slash(&mut storage, &headers[0], &headers[1], "local"),
+            Ok(7.0)
+        );
+        assert!(slash(&mut storage, &headers[1], &headers[0], "local").is_err());
+        assert_eq!(delegations(&storage, "0x1")[&validator], 1.0);
+        assert_eq!(unbonding(&storage)[0].amount, 1.0);
+
+        // O validador punido sai do conjunto e não recebe novos vínculos
+        assert_eq!(validators(&storage).len(), 1);
+        assert!((0..20).all(|id| slot_producer(&storage, id).unwrap() != validator));
+        assert!(bond(&mut storage, "0x1", &validator, 1.0).is_err());
+    }
+}
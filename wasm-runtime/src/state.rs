@@ -0,0 +1,264 @@
+/* Mod State
+** - Estado das contas (saldo e nonce) gravado no Storage da blockchain
+** - Cada conta é salva na chave `account/<endereço>`
+** - As transações de um bloco são aplicadas ao estado quando o bloco é minerado
+** - Nonce: número de transações aplicadas do remetente. A próxima transação
+**   da conta precisa ter esse nonce, que é incrementado na aplicação
+** - Raiz do estado: cada alteração gravada no estado (contas, armazenamento dos
+**   contratos, contas multisig e saídas UTXO) é inserida na árvore de Merkle
+**   esparsa (mod trie) com chave sha256(chave do storage). A raiz fica em
+**   `state/root` e depende apenas do conteúdo do estado
+** - Provas do estado: `account_proof` prova o saldo de uma conta na raiz de um
+**   bloco, `verify_account_proof` permite a um cliente leve checar a prova
+**   apenas com a raiz do cabeçalho do bloco
+** - Estado histórico: os nós da árvore de cada raiz continuam no Storage, a
+**   conta em um bloco antigo é consultada na raiz do estado do bloco
+*/
+use crate::{
+    storage::{decode_hex, encode_hex, load, save, Storage},
+    transaction::Transaction,
+    trie::{self, NodeHash, Proof, EMPTY_ROOT},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const STATE_ROOT_KEY: &str = "state/root";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    // Saldo da conta
+    pub balance: f64,
+    // Nonce da próxima transação, o nonce zero não é gravado (mantém a raiz do estado)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub nonce: u64,
+}
+
+fn is_zero(nonce: &u64) -> bool {
+    *nonce == 0
+}
+
+fn account_key(address: &str) -> String {
+    format!("account/{}", address)
+}
+
+// Retorna a conta do endereço, contas inexistentes possuem saldo zero
+pub fn get_account(storage: &dyn Storage, address: &str) -> Account {
+    load(storage, &account_key(address)).unwrap_or_default()
+}
+
+pub fn set_account(storage: &mut dyn Storage, address: &str, account: &Account) {
+    let key = account_key(address);
+    save(storage, &key, account);
+    let value = serde_json::to_vec(account).expect("Erro ao serializar conta");
+    record_change(storage, key.as_bytes(), Some(&value));
+}
+
+// Raiz do estado atual em hexadecimal, o estado vazio tem a raiz zero
+pub fn state_root(storage: &dyn Storage) -> String {
+    load(storage, STATE_ROOT_KEY).unwrap_or_else(|| encode_hex(&EMPTY_ROOT))
+}
+
+fn parse_root(root: &str) -> Result<NodeHash, String> {
+    decode_hex(root)?
+        .try_into()
+        .map_err(|_| format!("Raiz do estado inválida: {}", root))
+}
+
+fn trie_key(key: &[u8]) -> NodeHash {
+    Sha256::digest(key).into()
+}
+
+// Grava uma alteração do estado na árvore, None representa remoção da chave
+pub fn record_change(storage: &mut dyn Storage, key: &[u8], value: Option<&[u8]>) {
+    let root = parse_root(&state_root(storage)).expect("Raiz do estado inválida no storage");
+    let root = match value {
+        Some(value) => trie::insert(storage, &root, &trie_key(key), value),
+        None => trie::remove(storage, &root, &trie_key(key)),
+    };
+    save(storage, STATE_ROOT_KEY, &encode_hex(&root));
+}
+
+// Conta do endereço no estado com a raiz informada
+pub fn get_account_at(storage: &dyn Storage, root: &str, address: &str) -> Result<Account, String> {
+    let key = trie_key(account_key(address).as_bytes());
+    match trie::get(storage, &parse_root(root)?, &key) {
+        Some(value) => serde_json::from_slice(&value).map_err(|erro| erro.to_string()),
+        None => Ok(Account::default()),
+    }
+}
+
+// Prova da conta na raiz do estado informada, com o valor gravado na conta
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub address: String,
+    // Conta serializada em hexadecimal, None se a conta não existe nessa raiz
+    pub value: Option<String>,
+    pub proof: Proof,
+}
+
+pub fn account_proof(
+    storage: &dyn Storage,
+    root: &str,
+    address: &str,
+) -> Result<AccountProof, String> {
+    let root = parse_root(root)?;
+    let key = trie_key(account_key(address).as_bytes());
+    Ok(AccountProof {
+        address: address.to_string(),
+        value: trie::get(storage, &root, &key).map(|value| encode_hex(&value)),
+        proof: trie::prove(storage, &root, &key),
+    })
+}
+
+/* Checa a prova contra a raiz do estado de um bloco e retorna a conta
+** provada (contas inexistentes possuem saldo zero)
+*/
+pub fn verify_account_proof(root: &str, proof: &AccountProof) -> Result<Account, String> {
+    let value = proof.value.as_deref().map(decode_hex).transpose()?;
+    let key = trie_key(account_key(&proof.address).as_bytes());
+    trie::verify(&parse_root(root)?, &key, value.as_deref(), &proof.proof)?;
+    match value {
+        Some(value) => serde_json::from_slice(&value).map_err(|erro| erro.to_string()),
+        None => Ok(Account::default()),
+    }
+}
+
+/* Aplica uma transferência ao estado das contas.
+** O saldo do remetente não é verificado aqui e pode ficar negativo. Cadeias
+** com saldos iniciais ou prova de participação conferem antes com `check_debit`
+*/
+pub fn apply_transaction(storage: &mut dyn Storage, transaction: &Transaction) {
+    transfer(
+        storage,
+        &transaction.from,
+        &transaction.to,
+        transaction.value,
+    );
+}
+
+// Soma `amount` ao saldo da conta, valores negativos debitam a conta
+pub fn add_balance(storage: &mut dyn Storage, address: &str, amount: f64) {
+    // Sem alteração no saldo a raiz do estado não muda
+    if amount == 0.0 {
+        return;
+    }
+    let mut account = get_account(storage, address);
+    account.balance += amount;
+    set_account(storage, address, &account);
+}
+
+/* Confere o valor da transação e se o saldo da conta cobre o débito `amount`
+** (valor mais taxas). Valores negativos creditariam o remetente
+*/
+pub fn check_debit(
+    storage: &dyn Storage,
+    address: &str,
+    value: f64,
+    amount: f64,
+) -> Result<(), String> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("Valor da transação inválido: {}", value));
+    }
+    let balance = get_account(storage, address).balance;
+    if balance < amount {
+        return Err(format!(
+            "Saldo insuficiente: {} disponível, {} necessário",
+            balance, amount
+        ));
+    }
+    Ok(())
+}
+
+// Incrementa o nonce da conta depois de aplicar uma transação do remetente
+pub fn increment_nonce(storage: &mut dyn Storage, address: &str) {
+    let mut account = get_account(storage, address);
+    account.nonce += 1;
+    set_account(storage, address, &account);
+}
+
+pub fn transfer(storage: &mut dyn Storage, from: &str, to: &str, value: f64) {
+    add_balance(storage, from, -value);
+    add_balance(storage, to, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn test_apply_transaction() {
+        let mut storage = MemoryStorage::new();
+        let transaction = Transaction::new(
+            "0xEf8801eaf234ff82801821FFe2d780237F9967",
+            "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            4.0,
+        );
+
+        apply_transaction(&mut storage, &transaction);
+
+        assert_eq!(get_account(&storage, &transaction.from).balance, -4.0);
+        assert_eq!(get_account(&storage, &transaction.to).balance, 4.0);
+        assert_eq!(get_account(&storage, "0x0").balance, 0.0);
+    }
+
+    #[test]
+    fn test_state_root() {
+        let mut first = MemoryStorage::new();
+        let mut second = MemoryStorage::new();
+        let empty_root = state_root(&first);
+        let transaction = Transaction::new(
+            "0xEf8801eaf234ff82801821FFe2d780237F9967",
+            "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            4.0,
+        );
+
+        apply_transaction(&mut first, &transaction);
+        apply_transaction(&mut second, &transaction);
+        assert_ne!(state_root(&first), empty_root);
+        assert_eq!(state_root(&first), state_root(&second));
+
+        // Um valor diferente gera uma raiz diferente
+        add_balance(&mut second, &transaction.to, 1.0);
+        assert_ne!(state_root(&first), state_root(&second));
+
+        // A raiz depende apenas do estado, não da ordem das alterações
+        add_balance(&mut second, &transaction.to, -1.0);
+        assert_eq!(state_root(&first), state_root(&second));
+        let mut third = MemoryStorage::new();
+        add_balance(&mut third, &transaction.to, 4.0);
+        add_balance(&mut third, &transaction.from, -4.0);
+        assert_eq!(state_root(&third), state_root(&first));
+    }
+
+    #[test]
+    fn test_account_proof() {
+        let mut storage = MemoryStorage::new();
+        add_balance(&mut storage, "0x1", 10.0);
+        add_balance(&mut storage, "0x2", 5.0);
+        let root = state_root(&storage);
+        add_balance(&mut storage, "0x1", 1.0);
+
+        // Prova na raiz antiga, com o saldo da época
+        let proof = account_proof(&storage, &root, "0x1").unwrap();
+        assert_eq!(verify_account_proof(&root, &proof).unwrap().balance, 10.0);
+        assert!(verify_account_proof(&state_root(&storage), &proof).is_err());
+
+        // Saldo alterado na prova
+        let mut forged = proof.clone();
+        let account = Account {
+            balance: 100.0,
+            nonce: 0,
+        };
+        forged.value = Some(encode_hex(&serde_json::to_vec(&account).unwrap()));
+        assert!(verify_account_proof(&root, &forged).is_err());
+
+        // Conta inexistente
+        let missing = account_proof(&storage, &root, "0x3").unwrap();
+        assert_eq!(missing.value, None);
+        assert_eq!(verify_account_proof(&root, &missing).unwrap().balance, 0.0);
+        let mut renamed = proof;
+        renamed.address = String::from("0x3");
+        assert!(verify_account_proof(&root, &renamed).is_err());
+    }
+}
@@ -0,0 +1,521 @@
+/* Mod Storage
+** - Trait Storage: armazenamento chave-valor abstrato utilizado pela blockchain
+**   para persistir os blocos e o estado das contas
+** - MemoryStorage: implementação em memória, utilizada nos testes
+** - HostStorage: implementação que chama as funções importadas do host
+**   (`storage_get`, `storage_set` e `storage_remove`), assim como o `console_log`
+** - FileStorage: implementação em arquivo, utilizada pelo diretório de dados da CLI.
+**   Erros de escrita não interrompem o nó: ficam guardados e são retornados por
+**   `flush`, checado pela blockchain depois de gravar cada bloco. Com erro o
+**   bloco é desfeito antes de ser publicado
+** - Os valores são serializados em JSON antes de serem gravados
+** - Sem a `std` (apenas `alloc`): o módulo também é compilado para WebAssembly,
+**   onde o HostStorage chama os imports do host. O FileStorage é apenas nativo.
+**   A blockchain depende da `std` e roda apenas no nó nativo, onde o HostStorage
+**   usa os imports simulados (testes)
+*/
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    format,
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    string::{String, ToString},
+};
+
+pub trait Storage: Send + core::fmt::Debug {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    fn set(&mut self, key: &[u8], value: &[u8]);
+    fn remove(&mut self, key: &[u8]);
+
+    // Erro de uma escrita anterior, os armazenamentos em memória não falham
+    fn flush(&mut self) -> Result<(), alloc::string::String> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStorage {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.entries.insert(key.to_vec(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.entries.remove(key);
+    }
+}
+
+// Armazenamento fornecido pelo host através dos imports do WebAssembly
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HostStorage;
+
+impl Storage for HostStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        // Primeira chamada com buffer vazio descobre o tamanho do valor
+        let mut empty = [0u8; 0];
+        let len = unsafe {
+            crate::ext::storage_get(key.as_ptr(), key.len() as u32, empty.as_mut_ptr(), 0)
+        };
+        if len < 0 {
+            return None;
+        }
+        let mut value = vec![0u8; len as usize];
+        unsafe {
+            crate::ext::storage_get(
+                key.as_ptr(),
+                key.len() as u32,
+                value.as_mut_ptr(),
+                value.len() as u32,
+            );
+        }
+        Some(value)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        unsafe {
+            crate::ext::storage_set(
+                key.as_ptr(),
+                key.len() as u32,
+                value.as_ptr(),
+                value.len() as u32,
+            );
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        unsafe {
+            crate::ext::storage_remove(key.as_ptr(), key.len() as u32);
+        }
+    }
+}
+
+/* Armazenamento em arquivo: cada alteração é acrescentada ao final do arquivo,
+** uma linha JSON `[chave, valor]` em hexadecimal (valor null para remoção).
+** Ao abrir, as alterações são lidas novamente em memória:
+** - uma última linha incompleta (escrita interrompida) é removida do arquivo
+** - com mais de COMPACT_MIN_LINES linhas e mais que o dobro das chaves o arquivo
+**   é compactado, reescrito com uma linha por chave
+** - depois de um erro de escrita as alterações seguintes não são gravadas, o
+**   erro é retornado por `flush`
+*/
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct FileStorage {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    file: File,
+    path: PathBuf,
+    // Linhas gravadas no arquivo, utilizadas para decidir a compactação
+    lines: usize,
+    error: Option<String>,
+}
+
+// Quantidade mínima de linhas do arquivo para a compactação na abertura
+#[cfg(not(target_arch = "wasm32"))]
+pub const COMPACT_MIN_LINES: usize = 10_000;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|erro| format!("Erro ao abrir o storage: {}", erro))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .map_err(|erro| format!("Erro ao ler o storage: {}", erro))?;
+
+        // Linha final sem quebra de linha: escrita interrompida, é descartada
+        let complete = data
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |position| position + 1);
+        if complete < data.len() {
+            crate::warn!("Linha incompleta removida do storage"; bytes = data.len() - complete);
+            file.set_len(complete as u64)
+                .map_err(|erro| format!("Erro ao truncar o storage: {}", erro))?;
+        }
+
+        let mut entries = BTreeMap::new();
+        let mut lines = 0;
+        for line in data[..complete].split(|byte| *byte == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = serde_json::from_slice::<(String, Option<String>)>(line)
+                .map_err(|erro| format!("Storage corrompido: {}", erro))?;
+            let key = decode_hex(&key)?;
+            match value {
+                Some(value) => entries.insert(key, decode_hex(&value)?),
+                None => entries.remove(&key),
+            };
+            lines += 1;
+        }
+
+        let mut storage = FileStorage {
+            entries,
+            file,
+            path: path.to_path_buf(),
+            lines,
+            error: None,
+        };
+        if lines > COMPACT_MIN_LINES && lines > 2 * storage.entries.len() {
+            storage.compact()?;
+        }
+        Ok(storage)
+    }
+
+    /* Reescreve o arquivo com uma linha por chave. O novo arquivo é gravado ao
+     ** lado e renomeado sobre o anterior, uma falha mantém o arquivo anterior
+     */
+    pub fn compact(&mut self) -> Result<(), String> {
+        let temporary = self.path.with_extension("compact");
+        let mut data = Vec::new();
+        for (key, value) in &self.entries {
+            data.extend(line(key, Some(value)).into_bytes());
+        }
+        let written = File::create(&temporary)
+            .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary, &self.path));
+        if let Err(erro) = written {
+            let _ = fs::remove_file(&temporary);
+            return Err(format!("Erro ao compactar o storage: {}", erro));
+        }
+        self.file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(|erro| format!("Erro ao abrir o storage: {}", erro))?;
+        crate::info!("Storage compactado"; before = self.lines, after = self.entries.len());
+        self.lines = self.entries.len();
+        Ok(())
+    }
+
+    fn append(&mut self, key: &[u8], value: Option<&[u8]>) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.file.write_all(line(key, value).as_bytes()) {
+            Ok(()) => self.lines += 1,
+            Err(erro) => {
+                let erro = format!("Erro ao gravar no arquivo do storage: {}", erro);
+                crate::error!("Storage sem gravação"; erro = erro);
+                self.error = Some(erro);
+            }
+        }
+    }
+}
+
+// Linha do arquivo com a alteração da chave
+#[cfg(not(target_arch = "wasm32"))]
+fn line(key: &[u8], value: Option<&[u8]>) -> String {
+    let mut line = serde_json::to_string(&(encode_hex(key), value.map(encode_hex)))
+        .expect("Erro ao serializar alteração");
+    line.push('\n');
+    line
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.append(key, Some(value));
+        self.entries.insert(key.to_vec(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if self.entries.remove(key).is_some() {
+            self.append(key, None);
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        match &self.error {
+            Some(erro) => Err(erro.to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    // Apenas dígitos hexadecimais ASCII, os pares são fatiados por byte
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(String::from("Hexadecimal inválido"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| String::from("Hexadecimal inválido"))
+        })
+        .collect()
+}
+
+/* Camada de alterações sobre outro Storage: leituras consultam primeiro as
+** alterações pendentes, escritas ficam na camada até `commit`. Permite aplicar
+** um bloco e descartar todas as alterações se ele for inválido
+*/
+#[derive(Debug)]
+pub struct OverlayStorage {
+    base: Box<dyn Storage>,
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl OverlayStorage {
+    pub fn new(base: Box<dyn Storage>) -> Self {
+        OverlayStorage {
+            base,
+            changes: BTreeMap::new(),
+        }
+    }
+
+    // Grava as alterações no Storage base e o devolve
+    pub fn commit(mut self) -> Box<dyn Storage> {
+        for (key, value) in &self.changes {
+            match value {
+                Some(value) => self.base.set(key, value),
+                None => self.base.remove(key),
+            }
+        }
+        self.base
+    }
+
+    /* Registro para desfazer as alterações após o commit: valor anterior de
+     ** cada chave alterada (None se a chave não existia no Storage base)
+     */
+    pub fn undo_log(&self) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+        self.changes
+            .keys()
+            .map(|key| (key.clone(), self.base.get(key)))
+            .collect()
+    }
+
+    // Descarta as alterações e devolve o Storage base sem modificações
+    pub fn discard(self) -> Box<dyn Storage> {
+        self.base
+    }
+}
+
+impl Storage for OverlayStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.changes.get(key) {
+            Some(value) => value.clone(),
+            None => self.base.get(key),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.changes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.changes.insert(key.to_vec(), None);
+    }
+
+    fn flush(&mut self) -> Result<(), alloc::string::String> {
+        self.base.flush()
+    }
+}
+
+// Lê e desserializa um valor, retorna None se a chave não existe ou o valor é inválido
+pub fn load<T: DeserializeOwned>(storage: &dyn Storage, key: &str) -> Option<T> {
+    let bytes = storage.get(key.as_bytes())?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+// Serializa e grava um valor
+pub fn save<T: Serialize>(storage: &mut dyn Storage, key: &str, value: &T) {
+    let bytes = serde_json::to_vec(value).expect("Erro ao serializar valor");
+    storage.set(key.as_bytes(), &bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_storage() {
+        let mut storage = MemoryStorage::new();
+        assert_eq!(storage.get(b"chave"), None);
+
+        storage.set(b"chave", b"valor");
+        assert_eq!(storage.get(b"chave"), Some(b"valor".to_vec()));
+        assert_eq!(storage.len(), 1);
+
+        storage.remove(b"chave");
+        assert_eq!(storage.get(b"chave"), None);
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn test_host_storage() {
+        // Quando nativo, os imports do host usam um mapa por thread
+        let mut storage = HostStorage;
+        assert_eq!(storage.get(b"host/chave"), None);
+
+        storage.set(b"host/chave", b"valor do host");
+        assert_eq!(storage.get(b"host/chave"), Some(b"valor do host".to_vec()));
+
+        storage.set(b"host/vazio", b"");
+        assert_eq!(storage.get(b"host/vazio"), Some(vec![]));
+
+        storage.remove(b"host/chave");
+        assert_eq!(storage.get(b"host/chave"), None);
+    }
+
+    #[test]
+    fn test_overlay_storage() {
+        let mut base = MemoryStorage::new();
+        base.set(b"mantida", b"1");
+        base.set(b"removida", b"2");
+
+        let mut overlay = OverlayStorage::new(Box::new(base.clone()));
+        overlay.set(b"nova", b"3");
+        overlay.remove(b"removida");
+        assert_eq!(overlay.get(b"nova"), Some(b"3".to_vec()));
+        assert_eq!(overlay.get(b"removida"), None);
+        assert_eq!(overlay.get(b"mantida"), Some(b"1".to_vec()));
+
+        // Valores anteriores das chaves alteradas
+        assert_eq!(
+            overlay.undo_log(),
+            vec![
+                (b"nova".to_vec(), None),
+                (b"removida".to_vec(), Some(b"2".to_vec()))
+            ]
+        );
+
+        let discarded = OverlayStorage::new(Box::new(base.clone())).discard();
+        assert_eq!(discarded.get(b"removida"), Some(b"2".to_vec()));
+
+        let committed = overlay.commit();
+        assert_eq!(committed.get(b"nova"), Some(b"3".to_vec()));
+        assert_eq!(committed.get(b"removida"), None);
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storage.db");
+
+        let mut storage = FileStorage::open(&path).unwrap();
+        storage.set(b"mantida", b"1");
+        storage.set(b"alterada", b"2");
+        storage.set(b"alterada", b"3");
+        storage.set(b"removida", b"4");
+        storage.remove(b"removida");
+        drop(storage);
+
+        // As alterações são lidas novamente ao abrir o arquivo
+        let storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.get(b"mantida"), Some(b"1".to_vec()));
+        assert_eq!(storage.get(b"alterada"), Some(b"3".to_vec()));
+        assert_eq!(storage.get(b"removida"), None);
+    }
+
+    #[test]
+    fn test_file_storage_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storage.db");
+        let mut storage = FileStorage::open(&path).unwrap();
+        storage.set(b"chave", b"1");
+        drop(storage);
+
+        // Escrita interrompida no meio da linha
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[\"6e6f7661\",\"3").unwrap();
+        drop(file);
+        let mut storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.get(b"chave"), Some(b"1".to_vec()));
+        assert_eq!(storage.get(b"nova"), None);
+        storage.set(b"nova", b"2");
+        drop(storage);
+        let storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.get(b"nova"), Some(b"2".to_vec()));
+
+        // Erro de escrita retornado por flush, sem interromper o nó
+        let mut storage = FileStorage::open(&path).unwrap();
+        storage.file = File::open(&path).unwrap();
+        storage.set(b"perdida", b"3");
+        assert!(storage.flush().is_err());
+        storage.set(b"depois", b"4");
+        assert!(storage.flush().is_err());
+        drop(storage);
+        let storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.get(b"perdida"), None);
+    }
+
+    #[test]
+    fn test_file_storage_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storage.db");
+        let mut storage = FileStorage::open(&path).unwrap();
+        for i in 0..=COMPACT_MIN_LINES {
+            storage.set(b"contador", i.to_string().as_bytes());
+        }
+        storage.set(b"removida", b"1");
+        storage.remove(b"removida");
+        drop(storage);
+
+        // Reaberto com mais linhas que chaves, o arquivo é reescrito
+        let mut storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.lines, 1);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        let expected = COMPACT_MIN_LINES.to_string().into_bytes();
+        assert_eq!(storage.get(b"contador"), Some(expected.clone()));
+        storage.set(b"nova", b"2");
+        drop(storage);
+        let storage = FileStorage::open(&path).unwrap();
+        assert_eq!(storage.get(b"contador"), Some(expected));
+        assert_eq!(storage.get(b"nova"), Some(b"2".to_vec()));
+        assert_eq!(storage.get(b"removida"), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("00ff"), Ok(vec![0, 255]));
+        for invalid in ["0", "zz", "+f", "aéa", "éé"] {
+            assert!(decode_hex(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_load_save() {
+        let mut storage = MemoryStorage::new();
+        save(&mut storage, "numero", &42u64);
+        assert_eq!(load::<u64>(&storage, "numero"), Some(42));
+        assert_eq!(load::<u64>(&storage, "inexistente"), None);
+    }
+}
@@ -0,0 +1,388 @@
+/* Mod Tamper
+** - Simulação de ataques à cadeia, para testes e demonstrações:
+**    - alterar o valor, o remetente ou o destino de uma transação
+**    - remover ou injetar uma transação em um bloco
+**    - trocar dois blocos de posição
+**    - reescrever o timestamp de um bloco
+**    - refazer a hash dos blocos a partir do bloco atacado até o topo
+**      (`RemineSuffix`), reencadeando os blocos seguintes
+** - Os ataques são aplicados em uma cópia dos blocos: a cadeia, o storage e os
+**   índices do nó não são alterados
+** - `simulate` checa a cópia alterada e retorna o primeiro bloco inválido com a
+**   regra de validação que detectou o ataque, ou None se o ataque não foi
+**   detectado. Alvos inexistentes retornam erro
+** - Os blocos refeitos mantêm a assinatura original do produtor: na prova de
+**   autoridade o ataque é detectado pela assinatura, na cadeia aberta apenas
+**   alterações no conjunto das transações são detectadas (recibos)
+*/
+use crate::{block::Block, blockchain::Blockchain, hash::Hasher, transaction::Transaction};
+
+#[derive(Debug, Clone)]
+pub enum Attack {
+    ChangeValue {
+        block_id: usize,
+        transaction_id: u64,
+        value: f64,
+    },
+    ChangeSender {
+        block_id: usize,
+        transaction_id: u64,
+        from: String,
+    },
+    ChangeRecipient {
+        block_id: usize,
+        transaction_id: u64,
+        to: String,
+    },
+    DeleteTransaction {
+        block_id: usize,
+        transaction_id: u64,
+    },
+    InjectTransaction {
+        block_id: usize,
+        transaction_id: u64,
+        transaction: Transaction,
+    },
+    SwapBlocks {
+        first: usize,
+        second: usize,
+    },
+    RewriteTimestamp {
+        block_id: usize,
+        timestamp: u64,
+    },
+    // Aplica o ataque e refaz a hash dos blocos do bloco atacado até o topo
+    RemineSuffix(Box<Attack>),
+}
+
+// Bloco em que o ataque foi detectado e a regra de validação que falhou
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub block_id: u64,
+    pub rule: String,
+}
+
+// Aplica o ataque em uma cópia dos blocos e checa a cópia com as regras da cadeia
+pub fn simulate(blockchain: &Blockchain, attack: &Attack) -> Result<Option<Detection>, String> {
+    let chain = tamper(blockchain, attack)?;
+    Ok(blockchain
+        .validate_blocks(&chain)
+        .err()
+        .map(|(block_id, rule)| Detection { block_id, rule }))
+}
+
+// Cópia dos blocos da cadeia com o ataque aplicado
+pub fn tamper(blockchain: &Blockchain, attack: &Attack) -> Result<Vec<Block>, String> {
+    let mut chain = blockchain.chain.clone();
+    apply(&mut chain, blockchain.hasher(), attack)?;
+    Ok(chain)
+}
+
+// Altera os blocos e retorna as posições alteradas
+fn apply(chain: &mut [Block], hasher: &dyn Hasher, attack: &Attack) -> Result<Vec<usize>, String> {
+    match attack {
+        Attack::ChangeValue {
+            block_id,
+            transaction_id,
+            value,
+        } => {
+            transaction(chain, *block_id, *transaction_id)?.value = *value;
+            Ok(vec![*block_id])
+        }
+        Attack::ChangeSender {
+            block_id,
+            transaction_id,
+            from,
+        } => {
+            transaction(chain, *block_id, *transaction_id)?.from = from.clone();
+            Ok(vec![*block_id])
+        }
+        Attack::ChangeRecipient {
+            block_id,
+            transaction_id,
+            to,
+        } => {
+            transaction(chain, *block_id, *transaction_id)?.to = to.clone();
+            Ok(vec![*block_id])
+        }
+        Attack::DeleteTransaction {
+            block_id,
+            transaction_id,
+        } => {
+            block(chain, *block_id)?
+                .transactions
+                .remove(transaction_id)
+                .ok_or_else(|| missing_transaction(*block_id, *transaction_id))?;
+            Ok(vec![*block_id])
+        }
+        Attack::InjectTransaction {
+            block_id,
+            transaction_id,
+            transaction,
+        } => {
+            let block = block(chain, *block_id)?;
+            if block.transactions.contains_key(transaction_id) {
+                return Err(format!(
+                    "Transação {} já existe no bloco {}",
+                    transaction_id, block_id
+                ));
+            }
+            block
+                .transactions
+                .insert(*transaction_id, transaction.clone());
+            Ok(vec![*block_id])
+        }
+        Attack::SwapBlocks { first, second } => {
+            block(chain, *first)?;
+            block(chain, *second)?;
+            chain.swap(*first, *second);
+            Ok(vec![*first, *second])
+        }
+        Attack::RewriteTimestamp {
+            block_id,
+            timestamp,
+        } => {
+            block(chain, *block_id)?.timestamp = *timestamp;
+            Ok(vec![*block_id])
+        }
+        Attack::RemineSuffix(attack) => {
+            let tampered = apply(chain, hasher, attack)?;
+            let start = tampered.into_iter().min().unwrap_or(1).max(1);
+            for id in start..chain.len() {
+                chain[id].hash_previous_block = chain[id - 1].hash;
+                chain[id].hash = chain[id].calculate_hash(hasher);
+            }
+            Ok((start..chain.len()).collect())
+        }
+    }
+}
+
+// O bloco genesis não é alvo dos ataques
+fn block(chain: &mut [Block], block_id: usize) -> Result<&mut Block, String> {
+    match chain.get_mut(block_id) {
+        Some(block) if block_id > 0 => Ok(block),
+        _ => Err(format!("Bloco {} não existe na cadeia", block_id)),
+    }
+}
+
+fn transaction(
+    chain: &mut [Block],
+    block_id: usize,
+    transaction_id: u64,
+) -> Result<&mut Transaction, String> {
+    block(chain, block_id)?
+        .transactions
+        .get_mut(&transaction_id)
+        .ok_or_else(|| missing_transaction(block_id, transaction_id))
+}
+
+fn missing_transaction(block_id: usize, transaction_id: u64) -> String {
+    format!(
+        "Transação {} não existe no bloco {}",
+        transaction_id, block_id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        consensus::tests::{public_key, validator_keys},
+        genesis::{ConsensusParams, Genesis},
+        storage::MemoryStorage,
+        wallet::tests::test_account,
+    };
+
+    // Cadeia aberta com 3 blocos de 5 transações (ids 1 a 15)
+    fn open_chain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        let (key, _) = test_account(101);
+        for value in 1..=15 {
+            blockchain.send_transaction(&key, "0x2", value as f64);
+        }
+        assert_eq!(blockchain.height(), 3);
+        blockchain
+    }
+
+    fn detected(block_id: u64, rule: &str) -> Option<Detection> {
+        Some(Detection {
+            block_id,
+            rule: String::from(rule),
+        })
+    }
+
+    #[test]
+    fn test_attacks_detected() {
+        let cases = [
+            (
+                Attack::ChangeValue {
+                    block_id: 2,
+                    transaction_id: 6,
+                    value: 100.0,
+                },
+                detected(2, "Hash invalida"),
+            ),
+            (
+                Attack::ChangeSender {
+                    block_id: 1,
+                    transaction_id: 1,
+                    from: String::from("0x3"),
+                },
+                detected(1, "Hash invalida"),
+            ),
+            (
+                Attack::ChangeRecipient {
+                    block_id: 3,
+                    transaction_id: 15,
+                    to: String::from("0x3"),
+                },
+                detected(3, "Hash invalida"),
+            ),
+            (
+                Attack::DeleteTransaction {
+                    block_id: 2,
+                    transaction_id: 7,
+                },
+                detected(2, "Hash invalida"),
+            ),
+            (
+                Attack::InjectTransaction {
+                    block_id: 2,
+                    transaction_id: 16,
+                    transaction: Transaction::new("0x2", "0x3", 50.0),
+                },
+                detected(2, "Hash invalida"),
+            ),
+            (
+                Attack::SwapBlocks {
+                    first: 1,
+                    second: 2,
+                },
+                detected(1, "Hash do Bloco Anterior incompatível"),
+            ),
+            (
+                Attack::RewriteTimestamp {
+                    block_id: 3,
+                    timestamp: 0,
+                },
+                detected(3, "Hash invalida"),
+            ),
+            // Refazer as hashes não esconde a alteração no conjunto das transações
+            (
+                Attack::RemineSuffix(Box::new(Attack::DeleteTransaction {
+                    block_id: 2,
+                    transaction_id: 7,
+                })),
+                detected(2, "Transações do bloco não correspondem aos recibos"),
+            ),
+            (
+                Attack::RemineSuffix(Box::new(Attack::InjectTransaction {
+                    block_id: 1,
+                    transaction_id: 16,
+                    transaction: Transaction::new("0x2", "0x3", 50.0),
+                })),
+                detected(1, "Transações do bloco não correspondem aos recibos"),
+            ),
+        ];
+        // Os ataques são aplicados em cópias, a cadeia do nó continua válida
+        let blockchain = open_chain();
+        for (attack, expected) in cases {
+            assert_eq!(simulate(&blockchain, &attack), Ok(expected), "{:?}", attack);
+            assert!(blockchain.is_chain_valid());
+        }
+        assert_eq!(blockchain.height(), 3);
+        assert_eq!(blockchain.chain[2].transactions[&6].value, 6.0);
+    }
+
+    #[test]
+    fn test_remined_attacks() {
+        // Na cadeia aberta os dados das transações refeitos não são detectados
+        let blockchain = open_chain();
+        let attack = Attack::RemineSuffix(Box::new(Attack::ChangeValue {
+            block_id: 2,
+            transaction_id: 6,
+            value: 100.0,
+        }));
+        assert_eq!(simulate(&blockchain, &attack), Ok(None));
+        let tampered = tamper(&blockchain, &attack).unwrap();
+        assert_eq!(tampered[2].transactions[&6].value, 100.0);
+        assert_ne!(tampered[3].hash, blockchain.last_block().hash);
+        assert_eq!(blockchain.chain[2].transactions[&6].value, 6.0);
+        let hash = blockchain.chain[3].hash;
+        assert_eq!(
+            blockchain.block_by_hash(&hash).map(|block| block.id),
+            Some(3)
+        );
+
+        // Na prova de autoridade a assinatura do produtor não bate com a nova hash
+        let keys = validator_keys(1);
+        let genesis = Genesis {
+            timestamp: Some(1_700_000_000),
+            consensus: ConsensusParams::Authority {
+                validators: keys.iter().map(public_key).collect(),
+            },
+            ..Genesis::default()
+        };
+        let mut blockchain =
+            Blockchain::with_genesis(Box::new(MemoryStorage::new()), &genesis).unwrap();
+        blockchain.set_validator_key(keys[0].clone());
+        for _ in 0..2 {
+            blockchain.send_transaction(&test_account(101).0, "0x2", 4.0);
+            blockchain.try_mine_block().unwrap();
+        }
+        assert!(blockchain.is_chain_valid());
+        let attack = Attack::RemineSuffix(Box::new(Attack::ChangeSender {
+            block_id: 1,
+            transaction_id: 1,
+            from: String::from("0x3"),
+        }));
+        assert_eq!(
+            simulate(&blockchain, &attack),
+            Ok(detected(1, "Assinatura do produtor invalida"))
+        );
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_missing_targets() {
+        let mut blockchain = open_chain();
+        let attacks = [
+            Attack::ChangeValue {
+                block_id: 4,
+                transaction_id: 1,
+                value: 1.0,
+            },
+            Attack::ChangeRecipient {
+                block_id: 1,
+                transaction_id: 6,
+                to: String::from("0x3"),
+            },
+            Attack::DeleteTransaction {
+                block_id: 0,
+                transaction_id: 1,
+            },
+            Attack::InjectTransaction {
+                block_id: 1,
+                transaction_id: 1,
+                transaction: Transaction::new("0x2", "0x3", 1.0),
+            },
+            Attack::SwapBlocks {
+                first: 1,
+                second: 9,
+            },
+            Attack::RemineSuffix(Box::new(Attack::RewriteTimestamp {
+                block_id: 9,
+                timestamp: 0,
+            })),
+        ];
+        for attack in attacks {
+            assert!(simulate(&blockchain, &attack).is_err(), "{:?}", attack);
+        }
+        // Nenhum ataque com alvo inexistente altera a cadeia
+        assert!(blockchain.is_chain_valid());
+        assert_eq!(
+            blockchain.corrupt_block(1, 6, 1.0),
+            Err(String::from("Transação 6 não existe no bloco 1"))
+        );
+    }
+}
@@ -1,6 +1,14 @@
 //Mod Transação
+use crate::{
+    block::BlockHeader,
+    consensus::GovernanceAction,
+    hash::Hash,
+    utxo::{OutPoint, TxOutput},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     // Origem da transação
     pub from: String,
@@ -8,4 +16,207 @@ pub struct Transaction {
     pub to: String,
     // Valor da transação
     pub value: f64,
+    // Tipo da transação: transferência, publicação ou chamada de contrato
+    #[serde(default)]
+    pub kind: TransactionKind,
+    // Número da transação do remetente, igual ao nonce da conta (mod state).
+    // Impede que a mesma transação assinada seja aplicada duas vezes
+    #[serde(default)]
+    pub nonce: u64,
+    // Assinatura do remetente (mod wallet)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<TransactionSignature>,
+    // Assinaturas parciais dos donos de uma conta multisig (mod multisig)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<TransactionSignature>,
+}
+
+// Chave pública e assinatura ed25519 em hexadecimal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum TransactionKind {
+    // Transferência de `value` entre `from` e `to`
+    #[default]
+    Transfer,
+    // Publica o bytecode wasm de um contrato, o endereço do contrato é
+    // derivado do remetente e do nonce da transação. O remetente paga
+    // `gas_price` por unidade do gas da publicação (por byte do bytecode)
+    Deploy {
+        code: Vec<u8>,
+        #[serde(default)]
+        gas_price: f64,
+    },
+    // Chama a função exportada `function` do contrato no endereço `to`.
+    // O remetente paga `gas_price` por unidade de gas consumida, até `gas_limit`
+    Call {
+        function: String,
+        args: Vec<i64>,
+        gas_limit: u64,
+        gas_price: f64,
+    },
+    // Registra a conta multisig M-de-N no endereço `to` (derivado das chaves e do
+    // limite), `value` é transferido para a conta
+    RegisterMultisig {
+        threshold: u32,
+        public_keys: Vec<String>,
+    },
+    // Gasta saídas não gastas e cria novas saídas (modo UTXO, mod utxo)
+    Spend {
+        inputs: Vec<OutPoint>,
+        outputs: Vec<TxOutput>,
+    },
+    // Voto do validador que assina a transação na alteração do conjunto de
+    // validadores (prova de autoridade, mod consensus)
+    Governance {
+        action: GovernanceAction,
+    },
+    // Vincula `value` do remetente ao validador (prova de participação, mod stake)
+    Bond {
+        validator: String,
+    },
+    // Desvincula `amount` do validador, devolvido depois do período de desvínculo
+    Unbond {
+        validator: String,
+        amount: f64,
+    },
+    // Dois cabeçalhos assinados pelo mesmo produtor na mesma altura, pune o produtor.
+    // Transação do sistema: gerada pelo nó, sem remetente nem assinatura
+    Evidence {
+        first: Box<BlockHeader>,
+        second: Box<BlockHeader>,
+    },
+}
+
+impl Transaction {
+    // Nova transação de transferência
+    pub fn new(from: &str, to: &str, value: f64) -> Self {
+        Transaction {
+            from: from.to_string(),
+            to: to.to_string(),
+            value,
+            kind: TransactionKind::Transfer,
+            nonce: 0,
+            signature: None,
+            signatures: vec![],
+        }
+    }
+
+    // Hash dos dados assinados: todos os campos da transação exceto a assinatura
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let data = serde_json::to_vec(&(&self.from, &self.to, self.value, self.nonce, &self.kind))
+            .expect("Erro ao serializar transação");
+        Sha256::digest(data).into()
+    }
+
+    /* Hash do conteúdo da transação, independente do id atribuído por cada nó.
+     ** Identifica a transação na deduplicação entre os nós e nas pendentes
+     */
+    pub fn hash(&self) -> Hash {
+        Hash(self.signing_hash())
+    }
+
+    /* Transações do sistema são geradas pelos nós (evidências de assinatura
+     ** dupla), não movem valor e são validadas pelo conteúdo, sem assinatura
+     */
+    pub fn is_system(&self) -> bool {
+        matches!(self.kind, TransactionKind::Evidence { .. })
+    }
+
+    /* Transações com nonce: todas as assinadas, exceto os gastos UTXO, que não
+     ** podem ser repetidos porque consomem as suas entradas
+     */
+    pub fn uses_nonce(&self) -> bool {
+        !self.is_system() && !matches!(self.kind, TransactionKind::Spend { .. })
+    }
+
+    // Transações do sistema não têm remetente, destino, valor nem assinaturas
+    pub fn check_system(&self) -> Result<(), String> {
+        if !self.from.is_empty()
+            || !self.to.is_empty()
+            || self.value != 0.0
+            || self.signature.is_some()
+            || !self.signatures.is_empty()
+        {
+            return Err(String::from("Transação do sistema inválida"));
+        }
+        Ok(())
+    }
+
+    // Dados da assinatura incluídos no cálculo da hash do bloco
+    pub fn signature_hash_data(&self) -> String {
+        self.signature
+            .iter()
+            .chain(&self.signatures)
+            .map(|signature| format!("sig{}{}", signature.public_key, signature.signature))
+            .collect()
+    }
+}
+
+impl TransactionKind {
+    /* Dados do tipo da transação incluídos no cálculo da hash do bloco.
+     ** Transferências não adicionam dados, mantendo a hash dos blocos antigos
+     */
+    pub fn hash_data(&self) -> String {
+        match self {
+            TransactionKind::Transfer => String::new(),
+            TransactionKind::Deploy { code, gas_price } => {
+                let hex = code
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                // O preço zero não adiciona dados, mantendo a hash dos blocos antigos
+                if *gas_price == 0.0 {
+                    format!("deploy{}", hex)
+                } else {
+                    format!("deploy{}gas{}", hex, gas_price)
+                }
+            }
+            TransactionKind::Call {
+                function,
+                args,
+                gas_limit,
+                gas_price,
+            } => {
+                let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                format!(
+                    "call{}({}){}{}",
+                    function,
+                    args.join(","),
+                    gas_limit,
+                    gas_price
+                )
+            }
+            TransactionKind::RegisterMultisig {
+                threshold,
+                public_keys,
+            } => format!("multisig{}{}", threshold, public_keys.join(",")),
+            TransactionKind::Spend { inputs, outputs } => {
+                let inputs = inputs
+                    .iter()
+                    .map(|input| format!("{}:{}", input.transaction_hash, input.index))
+                    .collect::<Vec<_>>();
+                let outputs = outputs
+                    .iter()
+                    .map(|output| format!("{}={}", output.address, output.value))
+                    .collect::<Vec<_>>();
+                format!("spend{}>{}", inputs.join(","), outputs.join(","))
+            }
+            TransactionKind::Governance { action } => format!(
+                "governance{}",
+                serde_json::to_string(action).expect("Erro ao serializar governança")
+            ),
+            TransactionKind::Bond { validator } => format!("bond{}", validator),
+            TransactionKind::Unbond { validator, amount } => {
+                format!("unbond{}{}", validator, amount)
+            }
+            TransactionKind::Evidence { first, second } => {
+                format!("evidence{}{}", first.hash, second.hash)
+            }
+        }
+    }
 }
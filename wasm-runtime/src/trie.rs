@@ -0,0 +1,404 @@
+/* Mod Trie
+** - Árvore de Merkle esparsa sobre chaves de 256 bits: o bit `i` da chave
+**   escolhe o filho (0 esquerda, 1 direita) no nível `i`
+** - Compactada: uma subárvore com apenas uma folha é a própria folha, e a
+**   subárvore vazia tem a hash zero. A forma da árvore depende apenas do
+**   conjunto de chaves, a mesma coleção gera a mesma raiz em qualquer ordem
+** - Hash da folha = sha256(0x00, chave, sha256(valor)) e do nó interno =
+**   sha256(0x01, esquerda, direita)
+** - Nós gravados no Storage pela hash (`trie/<hash>`) e nunca removidos, por
+**   isso raízes antigas continuam consultáveis e geram provas
+** - Prova: hashes irmãs do caminho da chave até a folha ou subárvore vazia,
+**   prova a presença (com o valor) ou a ausência da chave
+*/
+use crate::storage::{decode_hex, encode_hex, load, save, Storage};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub type NodeHash = [u8; 32];
+
+// Hash da subárvore vazia, também a raiz da árvore vazia
+pub const EMPTY_ROOT: NodeHash = [0; 32];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Node {
+    // Chave e valor completos em hexadecimal
+    Leaf { key: String, value: String },
+    Internal { left: String, right: String },
+}
+
+// Folha alcançada pela prova, identificada pela chave e pela hash do valor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofLeaf {
+    pub key: String,
+    pub value_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Proof {
+    // Hashes irmãs do caminho, a partir da raiz
+    pub siblings: Vec<String>,
+    // Folha no fim do caminho, None quando o caminho termina em uma subárvore vazia
+    pub leaf: Option<ProofLeaf>,
+}
+
+fn node_key(hash: &NodeHash) -> String {
+    format!("trie/{}", encode_hex(hash))
+}
+
+fn parse_hash(hex: &str) -> Result<NodeHash, String> {
+    decode_hex(hex)?
+        .try_into()
+        .map_err(|_| format!("Hash inválida: {}", hex))
+}
+
+fn sha256(data: &[u8]) -> NodeHash {
+    Sha256::digest(data).into()
+}
+
+fn leaf_hash(key: &NodeHash, value_hash: &NodeHash) -> NodeHash {
+    let mut hasher = Sha256::new();
+    hasher.update([0]);
+    hasher.update(key);
+    hasher.update(value_hash);
+    hasher.finalize().into()
+}
+
+fn internal_hash(left: &NodeHash, right: &NodeHash) -> NodeHash {
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// Bit `depth` da chave, a partir do bit mais significativo
+fn bit(key: &NodeHash, depth: usize) -> bool {
+    (key[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+fn load_node(storage: &dyn Storage, hash: &NodeHash) -> Node {
+    load(storage, &node_key(hash)).expect("Nó da árvore ausente no storage")
+}
+
+fn store_leaf(storage: &mut dyn Storage, key: &NodeHash, value: &[u8]) -> NodeHash {
+    let hash = leaf_hash(key, &sha256(value));
+    let node = Node::Leaf {
+        key: encode_hex(key),
+        value: encode_hex(value),
+    };
+    save(storage, &node_key(&hash), &node);
+    hash
+}
+
+fn store_internal(storage: &mut dyn Storage, left: &NodeHash, right: &NodeHash) -> NodeHash {
+    let hash = internal_hash(left, right);
+    let node = Node::Internal {
+        left: encode_hex(left),
+        right: encode_hex(right),
+    };
+    save(storage, &node_key(&hash), &node);
+    hash
+}
+
+fn children(left: &str, right: &str) -> (NodeHash, NodeHash) {
+    (
+        parse_hash(left).expect("Nó da árvore inválido"),
+        parse_hash(right).expect("Nó da árvore inválido"),
+    )
+}
+
+fn is_leaf(storage: &dyn Storage, hash: &NodeHash) -> bool {
+    *hash != EMPTY_ROOT && matches!(load_node(storage, hash), Node::Leaf { .. })
+}
+
+// Grava `value` na chave, retorna a nova raiz
+pub fn insert(
+    storage: &mut dyn Storage,
+    root: &NodeHash,
+    key: &NodeHash,
+    value: &[u8],
+) -> NodeHash {
+    insert_at(storage, root, key, value, 0)
+}
+
+fn insert_at(
+    storage: &mut dyn Storage,
+    node: &NodeHash,
+    key: &NodeHash,
+    value: &[u8],
+    depth: usize,
+) -> NodeHash {
+    if *node == EMPTY_ROOT {
+        return store_leaf(storage, key, value);
+    }
+    match load_node(storage, node) {
+        Node::Leaf { key: other, .. } => {
+            let other = parse_hash(&other).expect("Nó da árvore inválido");
+            let leaf = store_leaf(storage, key, value);
+            if other == *key {
+                leaf
+            } else {
+                split(storage, (node, &other), (&leaf, key), depth)
+            }
+        }
+        Node::Internal { left, right } => {
+            let (left, right) = children(&left, &right);
+            if bit(key, depth) {
+                let right = insert_at(storage, &right, key, value, depth + 1);
+                store_internal(storage, &left, &right)
+            } else {
+                let left = insert_at(storage, &left, key, value, depth + 1);
+                store_internal(storage, &left, &right)
+            }
+        }
+    }
+}
+
+// Nós internos que separam duas folhas a partir do nível `depth`
+fn split(
+    storage: &mut dyn Storage,
+    (first, first_key): (&NodeHash, &NodeHash),
+    (second, second_key): (&NodeHash, &NodeHash),
+    depth: usize,
+) -> NodeHash {
+    match (bit(first_key, depth), bit(second_key, depth)) {
+        (false, true) => store_internal(storage, first, second),
+        (true, false) => store_internal(storage, second, first),
+        (side, _) => {
+            let child = split(storage, (first, first_key), (second, second_key), depth + 1);
+            if side {
+                store_internal(storage, &EMPTY_ROOT, &child)
+            } else {
+                store_internal(storage, &child, &EMPTY_ROOT)
+            }
+        }
+    }
+}
+
+// Remove a chave, retorna a nova raiz
+pub fn remove(storage: &mut dyn Storage, root: &NodeHash, key: &NodeHash) -> NodeHash {
+    remove_at(storage, root, key, 0)
+}
+
+fn remove_at(storage: &mut dyn Storage, node: &NodeHash, key: &NodeHash, depth: usize) -> NodeHash {
+    if *node == EMPTY_ROOT {
+        return EMPTY_ROOT;
+    }
+    match load_node(storage, node) {
+        Node::Leaf { key: other, .. } => {
+            if parse_hash(&other).expect("Nó da árvore inválido") == *key {
+                EMPTY_ROOT
+            } else {
+                *node
+            }
+        }
+        Node::Internal { left, right } => {
+            let (mut left, mut right) = children(&left, &right);
+            if bit(key, depth) {
+                right = remove_at(storage, &right, key, depth + 1);
+            } else {
+                left = remove_at(storage, &left, key, depth + 1);
+            }
+            // Uma folha sozinha sobe no lugar do nó interno
+            if left == EMPTY_ROOT && (right == EMPTY_ROOT || is_leaf(storage, &right)) {
+                right
+            } else if right == EMPTY_ROOT && is_leaf(storage, &left) {
+                left
+            } else {
+                store_internal(storage, &left, &right)
+            }
+        }
+    }
+}
+
+// Valor gravado na chave na árvore da raiz informada
+pub fn get(storage: &dyn Storage, root: &NodeHash, key: &NodeHash) -> Option<Vec<u8>> {
+    let mut node = *root;
+    let mut depth = 0;
+    while node != EMPTY_ROOT {
+        match load_node(storage, &node) {
+            Node::Leaf { key: other, value } => {
+                return if other == encode_hex(key) {
+                    decode_hex(&value).ok()
+                } else {
+                    None
+                };
+            }
+            Node::Internal { left, right } => {
+                let (left, right) = children(&left, &right);
+                node = if bit(key, depth) { right } else { left };
+                depth += 1;
+            }
+        }
+    }
+    None
+}
+
+// Prova da chave na árvore da raiz informada
+pub fn prove(storage: &dyn Storage, root: &NodeHash, key: &NodeHash) -> Proof {
+    let mut siblings = vec![];
+    let mut node = *root;
+    let mut depth = 0;
+    while node != EMPTY_ROOT {
+        match load_node(storage, &node) {
+            Node::Leaf { key, value } => {
+                let value = decode_hex(&value).expect("Nó da árvore inválido");
+                return Proof {
+                    siblings,
+                    leaf: Some(ProofLeaf {
+                        key,
+                        value_hash: encode_hex(&sha256(&value)),
+                    }),
+                };
+            }
+            Node::Internal { left, right } => {
+                let (left, right) = children(&left, &right);
+                if bit(key, depth) {
+                    siblings.push(encode_hex(&left));
+                    node = right;
+                } else {
+                    siblings.push(encode_hex(&right));
+                    node = left;
+                }
+                depth += 1;
+            }
+        }
+    }
+    Proof {
+        siblings,
+        leaf: None,
+    }
+}
+
+/* Checa a prova: com `value` Some a chave precisa existir com esse valor, com
+** None a prova precisa mostrar a ausência da chave
+*/
+pub fn verify(
+    root: &NodeHash,
+    key: &NodeHash,
+    value: Option<&[u8]>,
+    proof: &Proof,
+) -> Result<(), String> {
+    if proof.siblings.len() > 256 {
+        return Err(String::from("Prova inválida"));
+    }
+    let mut hash = match (&proof.leaf, value) {
+        (Some(leaf), _) => {
+            let leaf_key = parse_hash(&leaf.key)?;
+            let value_hash = parse_hash(&leaf.value_hash)?;
+            // A folha precisa estar no caminho da chave
+            if (0..proof.siblings.len()).any(|depth| bit(&leaf_key, depth) != bit(key, depth)) {
+                return Err(String::from("Folha fora do caminho da chave"));
+            }
+            match value {
+                Some(value) if leaf_key == *key && sha256(value) == value_hash => {}
+                None if leaf_key != *key => {}
+                _ => return Err(String::from("Valor não corresponde à prova")),
+            }
+            leaf_hash(&leaf_key, &value_hash)
+        }
+        (None, None) => EMPTY_ROOT,
+        (None, Some(_)) => return Err(String::from("Valor não corresponde à prova")),
+    };
+    for (depth, sibling) in proof.siblings.iter().enumerate().rev() {
+        let sibling = parse_hash(sibling)?;
+        hash = if bit(key, depth) {
+            internal_hash(&sibling, &hash)
+        } else {
+            internal_hash(&hash, &sibling)
+        };
+    }
+    if hash == *root {
+        Ok(())
+    } else {
+        Err(String::from("Raiz não corresponde à prova"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn key(name: &str) -> NodeHash {
+        sha256(name.as_bytes())
+    }
+
+    #[test]
+    fn test_insert_remove_canonical() {
+        let mut storage = MemoryStorage::new();
+        let names = ["a", "b", "c", "d", "e"];
+
+        let mut forward = EMPTY_ROOT;
+        for name in names {
+            forward = insert(&mut storage, &forward, &key(name), name.as_bytes());
+        }
+        let mut backward = EMPTY_ROOT;
+        for name in names.iter().rev() {
+            backward = insert(&mut storage, &backward, &key(name), name.as_bytes());
+        }
+        // A raiz não depende da ordem das inserções
+        assert_eq!(forward, backward);
+        assert_eq!(get(&storage, &forward, &key("c")), Some(b"c".to_vec()));
+        assert_eq!(get(&storage, &forward, &key("z")), None);
+
+        // Alterar e restaurar um valor volta à mesma raiz
+        let changed = insert(&mut storage, &forward, &key("c"), b"outro");
+        assert_ne!(changed, forward);
+        assert_eq!(insert(&mut storage, &changed, &key("c"), b"c"), forward);
+
+        // Remover todas as chaves volta à árvore vazia, passando pelas mesmas raízes
+        let mut partial = EMPTY_ROOT;
+        for name in &names[..3] {
+            partial = insert(&mut storage, &partial, &key(name), name.as_bytes());
+        }
+        let mut root = forward;
+        for name in &names[3..] {
+            root = remove(&mut storage, &root, &key(name));
+        }
+        assert_eq!(root, partial);
+        for name in &names[..3] {
+            root = remove(&mut storage, &root, &key(name));
+        }
+        assert_eq!(root, EMPTY_ROOT);
+        assert_eq!(remove(&mut storage, &EMPTY_ROOT, &key("a")), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_proofs() {
+        let mut storage = MemoryStorage::new();
+        let mut root = EMPTY_ROOT;
+        for name in ["a", "b", "c", "d"] {
+            root = insert(&mut storage, &root, &key(name), name.as_bytes());
+        }
+
+        // Presença
+        let proof = prove(&storage, &root, &key("b"));
+        assert_eq!(verify(&root, &key("b"), Some(b"b"), &proof), Ok(()));
+        assert!(verify(&root, &key("b"), Some(b"x"), &proof).is_err());
+        assert!(verify(&root, &key("b"), None, &proof).is_err());
+
+        // Ausência
+        let proof = prove(&storage, &root, &key("z"));
+        assert_eq!(verify(&root, &key("z"), None, &proof), Ok(()));
+        assert!(verify(&root, &key("z"), Some(b"z"), &proof).is_err());
+
+        // Prova de outra raiz ou alterada
+        let old_root = root;
+        let old_proof = prove(&storage, &old_root, &key("a"));
+        root = insert(&mut storage, &root, &key("a"), b"novo");
+        assert!(verify(&root, &key("a"), Some(b"a"), &old_proof).is_err());
+        // A raiz antiga continua consultável
+        assert_eq!(prove(&storage, &old_root, &key("a")), old_proof);
+        assert_eq!(verify(&old_root, &key("a"), Some(b"a"), &old_proof), Ok(()));
+
+        let mut forged = prove(&storage, &root, &key("a"));
+        forged.siblings[0] = encode_hex(&[1; 32]);
+        assert!(verify(&root, &key("a"), Some(b"novo"), &forged).is_err());
+
+        // Árvore vazia
+        let empty = prove(&storage, &EMPTY_ROOT, &key("a"));
+        assert_eq!(verify(&EMPTY_ROOT, &key("a"), None, &empty), Ok(()));
+    }
+}
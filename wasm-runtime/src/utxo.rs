@@ -0,0 +1,405 @@
+/* Mod Utxo
+** - Modelo de transações alternativo, escolhido no genesis (`ledger: "utxo"`):
+**   transações `Spend` gastam saídas não gastas de transações anteriores e
+**   criam novas saídas
+** - Saídas identificadas pela hash do conteúdo da transação que as criou
+**   (`Transaction::hash`, igual em todos os nós) e pela posição da saída
+** - Conjunto de saídas não gastas (UTXO) gravado no Storage: cada saída em
+**   `utxo/<hash da transação>/<índice>` e as saídas de cada endereço em
+**   `utxos/<endereço>`. O saldo da conta acompanha a soma das saídas do endereço
+** - Regras: a transação precisa da assinatura do remetente, as entradas
+**   precisam existir, pertencer ao remetente e não se repetir; a primeira
+**   saída é o destino e o valor da transação (`to` e `value`); a soma das
+**   saídas não pode passar a soma das entradas (a diferença é a taxa,
+**   queimada). O troco é uma saída para o próprio remetente
+** - Gasto duplo: uma saída gasta na cadeia não existe mais no conjunto, e uma
+**   saída gasta por uma transação pendente não é aceita por outra
+*/
+use crate::{
+    hash::Hash,
+    state,
+    storage::{load, save, Storage},
+    transaction::{Transaction, TransactionKind},
+    wallet,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Hash da transação das saídas criadas pelo genesis
+pub const GENESIS_TRANSACTION_HASH: Hash = Hash::ZERO;
+
+// Referência a uma saída: hash da transação que a criou e posição da saída
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub transaction_hash: Hash,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub address: String,
+    pub value: f64,
+}
+
+fn output_key(outpoint: &OutPoint) -> String {
+    format!("utxo/{}/{}", outpoint.transaction_hash, outpoint.index)
+}
+
+fn address_key(address: &str) -> String {
+    format!("utxos/{}", address)
+}
+
+// Saída não gasta, None se não existe ou já foi gasta
+pub fn get_output(storage: &dyn Storage, outpoint: &OutPoint) -> Option<TxOutput> {
+    load(storage, &output_key(outpoint))
+}
+
+// Saídas não gastas do endereço, ordenadas
+pub fn unspent_outputs(storage: &dyn Storage, address: &str) -> Vec<(OutPoint, TxOutput)> {
+    load::<Vec<OutPoint>>(storage, &address_key(address))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|outpoint| Some((outpoint, get_output(storage, &outpoint)?)))
+        .collect()
+}
+
+fn add_output(storage: &mut dyn Storage, outpoint: OutPoint, output: &TxOutput) {
+    let key = output_key(&outpoint);
+    save(storage, &key, output);
+    let value = serde_json::to_vec(output).expect("Erro ao serializar saída");
+    state::record_change(storage, key.as_bytes(), Some(&value));
+
+    let mut outpoints =
+        load::<Vec<OutPoint>>(storage, &address_key(&output.address)).unwrap_or_default();
+    outpoints.push(outpoint);
+    outpoints.sort();
+    save(storage, &address_key(&output.address), &outpoints);
+    state::add_balance(storage, &output.address, output.value);
+}
+
+fn spend_output(storage: &mut dyn Storage, outpoint: &OutPoint, output: &TxOutput) {
+    let key = output_key(outpoint);
+    storage.remove(key.as_bytes());
+    state::record_change(storage, key.as_bytes(), None);
+
+    let mut outpoints =
+        load::<Vec<OutPoint>>(storage, &address_key(&output.address)).unwrap_or_default();
+    outpoints.retain(|other| other != outpoint);
+    save(storage, &address_key(&output.address), &outpoints);
+    state::add_balance(storage, &output.address, -output.value);
+}
+
+// Saídas iniciais do genesis, uma por endereço na ordem do `alloc`
+pub fn create_genesis_outputs(storage: &mut dyn Storage, alloc: &BTreeMap<String, f64>) {
+    for (index, (address, value)) in alloc.iter().enumerate() {
+        let outpoint = OutPoint {
+            transaction_hash: GENESIS_TRANSACTION_HASH,
+            index: index as u32,
+        };
+        let output = TxOutput {
+            address: address.clone(),
+            value: *value,
+        };
+        add_output(storage, outpoint, &output);
+    }
+}
+
+// Checa as regras da transação contra o conjunto de saídas, sem alterá-lo
+fn check_spend(
+    storage: &dyn Storage,
+    transaction: &Transaction,
+) -> Result<Vec<(OutPoint, TxOutput)>, String> {
+    let TransactionKind::Spend { inputs, outputs } = &transaction.kind else {
+        return Err(String::from(
+            "No modo UTXO apenas transações Spend são aceitas",
+        ));
+    };
+    wallet::verify_transaction(transaction)?;
+    if inputs.is_empty() {
+        return Err(String::from("Transação sem entradas"));
+    }
+    if inputs.iter().collect::<HashSet<_>>().len() != inputs.len() {
+        return Err(String::from("Entrada repetida na transação"));
+    }
+    if outputs.iter().any(|output| output.value <= 0.0) {
+        return Err(String::from("Saída com valor inválido"));
+    }
+    // `to` e `value` descrevem a transação nos índices e no explorador
+    match outputs.first() {
+        Some(output) if output.address == transaction.to && output.value == transaction.value => {}
+        _ => {
+            return Err(String::from(
+                "Destino e valor da transação diferem da primeira saída",
+            ))
+        }
+    }
+
+    let mut spent = vec![];
+    for outpoint in inputs {
+        let output = get_output(storage, outpoint).ok_or_else(|| {
+            format!(
+                "Saída {}:{} inexistente ou já gasta",
+                outpoint.transaction_hash, outpoint.index
+            )
+        })?;
+        if output.address != transaction.from {
+            return Err(String::from("Entrada não pertence ao remetente"));
+        }
+        spent.push((*outpoint, output));
+    }
+    let input_value = spent.iter().map(|(_, output)| output.value).sum::<f64>();
+    let output_value = outputs.iter().map(|output| output.value).sum::<f64>();
+    if output_value > input_value {
+        return Err(String::from("Saídas maiores que as entradas"));
+    }
+    Ok(spent)
+}
+
+/* Aplica a transação ao conjunto de saídas: as entradas são removidas e as
+** saídas criadas com a hash da transação. Transações inválidas não alteram o estado
+*/
+pub fn apply_spend(storage: &mut dyn Storage, transaction: &Transaction) -> Result<(), String> {
+    let spent = check_spend(storage, transaction)?;
+    for (outpoint, output) in &spent {
+        spend_output(storage, outpoint, output);
+    }
+    if let TransactionKind::Spend { outputs, .. } = &transaction.kind {
+        let transaction_hash = transaction.hash();
+        for (index, output) in outputs.iter().enumerate() {
+            let outpoint = OutPoint {
+                transaction_hash,
+                index: index as u32,
+            };
+            add_output(storage, outpoint, output);
+        }
+    }
+    Ok(())
+}
+
+// Saídas gastas pelas transações pendentes
+fn pending_inputs(pending: &HashMap<u64, Transaction>) -> HashSet<OutPoint> {
+    pending
+        .values()
+        .filter_map(|transaction| match &transaction.kind {
+            TransactionKind::Spend { inputs, .. } => Some(inputs.iter().copied()),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+/* Checa uma nova transação pendente: regras da transação contra a cadeia e
+** gasto duplo de saídas já gastas por outra transação pendente
+*/
+pub fn check_pending(
+    storage: &dyn Storage,
+    transaction: &Transaction,
+    pending: &HashMap<u64, Transaction>,
+) -> Result<(), String> {
+    let spent = check_spend(storage, transaction)?;
+    let pending_inputs = pending_inputs(pending);
+    if spent
+        .iter()
+        .any(|(outpoint, _)| pending_inputs.contains(outpoint))
+    {
+        return Err(String::from(
+            "Gasto duplo: saída já gasta por uma transação pendente",
+        ));
+    }
+    Ok(())
+}
+
+// Saídas do endereço ainda disponíveis, sem as gastas pelas transações pendentes
+pub fn available_outputs(
+    storage: &dyn Storage,
+    address: &str,
+    pending: &HashMap<u64, Transaction>,
+) -> Vec<(OutPoint, TxOutput)> {
+    let pending_inputs = pending_inputs(pending);
+    unspent_outputs(storage, address)
+        .into_iter()
+        .filter(|(outpoint, _)| !pending_inputs.contains(outpoint))
+        .collect()
+}
+
+/* Monta a transferência de `value` de `from` para `to`, escolhendo as saídas
+** disponíveis em ordem até cobrir o valor. O excedente volta como troco
+*/
+pub fn build_transfer(
+    available: &[(OutPoint, TxOutput)],
+    from: &str,
+    to: &str,
+    value: f64,
+) -> Result<Transaction, String> {
+    if value <= 0.0 {
+        return Err(String::from("Valor da transferência inválido"));
+    }
+    let mut inputs = vec![];
+    let mut input_value = 0.0;
+    for (outpoint, output) in available {
+        if input_value >= value {
+            break;
+        }
+        inputs.push(*outpoint);
+        input_value += output.value;
+    }
+    if input_value < value {
+        return Err(format!(
+            "Saldo insuficiente: {} disponível, {} necessário",
+            input_value, value
+        ));
+    }
+
+    let mut outputs = vec![TxOutput {
+        address: to.to_string(),
+        value,
+    }];
+    if input_value > value {
+        outputs.push(TxOutput {
+            address: from.to_string(),
+            value: input_value - value,
+        });
+    }
+    let mut transaction = Transaction::new(from, to, value);
+    transaction.kind = TransactionKind::Spend { inputs, outputs };
+    Ok(transaction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::MemoryStorage, wallet::tests::test_account};
+    use ed25519_dalek::SigningKey;
+
+    // Saídas do genesis: 10 para a conta 1 e 5 para a conta 2
+    fn genesis_storage() -> (MemoryStorage, (SigningKey, String), (SigningKey, String)) {
+        let (first, second) = (test_account(1), test_account(2));
+        let mut storage = MemoryStorage::new();
+        let alloc = [(first.1.clone(), 10.0), (second.1.clone(), 5.0)].into();
+        create_genesis_outputs(&mut storage, &alloc);
+        (storage, first, second)
+    }
+
+    fn signed(key: &SigningKey, mut transaction: Transaction) -> Transaction {
+        wallet::sign_transaction(key, &mut transaction).unwrap();
+        transaction
+    }
+
+    #[test]
+    fn test_spend_and_change() {
+        let (mut storage, (key, owner), _) = genesis_storage();
+        let available = unspent_outputs(&storage, &owner);
+        assert_eq!(available.len(), 1);
+        assert_eq!(state::get_account(&storage, &owner).balance, 10.0);
+
+        let transaction = signed(
+            &key,
+            build_transfer(&available, &owner, "0x3", 4.0).unwrap(),
+        );
+        apply_spend(&mut storage, &transaction).unwrap();
+
+        // A saída do genesis foi gasta, troco de 6 para o remetente na saída 1
+        // da transação, identificada pela hash do conteúdo
+        assert!(get_output(&storage, &available[0].0).is_none());
+        let change = unspent_outputs(&storage, &owner);
+        assert_eq!(change.len(), 1);
+        assert_eq!(
+            change[0].0,
+            OutPoint {
+                transaction_hash: transaction.hash(),
+                index: 1
+            }
+        );
+        assert_eq!(state::get_account(&storage, &owner).balance, 6.0);
+        assert_eq!(state::get_account(&storage, "0x3").balance, 4.0);
+
+        // Gastar de novo a mesma saída falha sem alterar o estado
+        let root = state::state_root(&storage);
+        assert!(apply_spend(&mut storage, &transaction).is_err());
+        assert_eq!(state::state_root(&storage), root);
+    }
+
+    #[test]
+    fn test_spend_rules() {
+        let (storage, (key, owner), (other_key, other)) = genesis_storage();
+        let available = unspent_outputs(&storage, &owner);
+        assert!(build_transfer(&available, &owner, "0x3", 11.0).is_err());
+
+        // O gasto exige a assinatura do remetente
+        let unsigned = build_transfer(&available, &owner, "0x3", 1.0).unwrap();
+        assert_eq!(
+            check_spend(&storage, &unsigned).err(),
+            Some(String::from("Transação sem assinatura"))
+        );
+        let mut forged = unsigned.clone();
+        let other_spend = Transaction {
+            from: other.clone(),
+            ..unsigned.clone()
+        };
+        forged.signature = signed(&other_key, other_spend).signature;
+        assert!(check_spend(&storage, &forged).is_err());
+        assert!(check_spend(&storage, &signed(&key, unsigned)).is_ok());
+
+        // Entrada de outro endereço, mesmo com a assinatura do remetente
+        let others = unspent_outputs(&storage, &other);
+        let stolen = signed(&key, build_transfer(&others, &owner, "0x3", 1.0).unwrap());
+        assert_eq!(
+            check_spend(&storage, &stolen).err(),
+            Some(String::from("Entrada não pertence ao remetente"))
+        );
+
+        // Saídas maiores que as entradas
+        let mut inflated = build_transfer(&available, &owner, "0x3", 10.0).unwrap();
+        if let TransactionKind::Spend { outputs, .. } = &mut inflated.kind {
+            outputs[0].value = 20.0;
+        }
+        inflated.value = 20.0;
+        assert_eq!(
+            check_spend(&storage, &signed(&key, inflated)).err(),
+            Some(String::from("Saídas maiores que as entradas"))
+        );
+
+        // `to` e `value` precisam descrever a primeira saída
+        let mut mismatched = build_transfer(&available, &owner, "0x3", 4.0).unwrap();
+        mismatched.value = 1.0;
+        let mut redirected = build_transfer(&available, &owner, "0x3", 4.0).unwrap();
+        redirected.to = String::from("0x4");
+        for transaction in [mismatched, redirected] {
+            assert_eq!(
+                check_spend(&storage, &signed(&key, transaction)).err(),
+                Some(String::from(
+                    "Destino e valor da transação diferem da primeira saída"
+                ))
+            );
+        }
+
+        // Transferência de conta não é aceita
+        let transfer = signed(&key, Transaction::new(&owner, "0x3", 1.0));
+        assert!(check_spend(&storage, &transfer).is_err());
+    }
+
+    #[test]
+    fn test_pending_double_spend() {
+        let (storage, (key, owner), _) = genesis_storage();
+        let mut pending = HashMap::new();
+        let available = available_outputs(&storage, &owner, &pending);
+        let first = signed(
+            &key,
+            build_transfer(&available, &owner, "0x3", 1.0).unwrap(),
+        );
+        let second = signed(
+            &key,
+            build_transfer(&available, &owner, "0x4", 1.0).unwrap(),
+        );
+
+        assert!(check_pending(&storage, &first, &pending).is_ok());
+        pending.insert(1, first);
+        assert_eq!(
+            check_pending(&storage, &second, &pending).err(),
+            Some(String::from(
+                "Gasto duplo: saída já gasta por uma transação pendente"
+            ))
+        );
+        assert!(available_outputs(&storage, &owner, &pending).is_empty());
+    }
+}
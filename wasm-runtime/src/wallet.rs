@@ -0,0 +1,409 @@
+/* Mod Wallet
+** - Pares de chaves ed25519, o endereço é "0x" + os 20 primeiros bytes do
+**   sha256 da chave pública
+** - Assinatura de transações: o remetente assina `Transaction::signing_hash` e a
+**   assinatura é checada na aplicação do bloco (a chave pública precisa
+**   corresponder ao endereço `from`)
+** - Keystore: um arquivo JSON por conta em um diretório, a chave privada é
+**   cifrada com AES-256-GCM usando uma chave derivada da senha com scrypt. O
+**   endereço é validado antes de virar o nome do arquivo e os parâmetros do
+**   scrypt lidos do arquivo são limitados antes da derivação
+*/
+use crate::{
+    storage::{decode_hex, encode_hex},
+    transaction::{Transaction, TransactionSignature},
+};
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Endereço derivado da chave pública
+pub fn derive_address(public_key: &VerifyingKey) -> String {
+    let hash = Sha256::digest(public_key.as_bytes());
+    format!("0x{}", encode_hex(&hash[..20]))
+}
+
+// Par de chaves a partir de uma semente de 32 bytes
+pub fn keypair_from_seed(seed: &[u8; 32]) -> SigningKey {
+    SigningKey::from_bytes(seed)
+}
+
+pub fn generate_keypair() -> SigningKey {
+    let mut seed = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut seed);
+    keypair_from_seed(&seed)
+}
+
+// Assina a transação, o remetente precisa ser o endereço da chave
+pub fn sign_transaction(key: &SigningKey, transaction: &mut Transaction) -> Result<(), String> {
+    let public_key = key.verifying_key();
+    if derive_address(&public_key) != transaction.from {
+        return Err(String::from("A chave não corresponde ao remetente"));
+    }
+    transaction.signature = Some(sign_hash(key, &transaction.signing_hash()));
+    Ok(())
+}
+
+// Checa a assinatura da transação e se a chave pública corresponde ao remetente
+pub fn verify_transaction(transaction: &Transaction) -> Result<(), String> {
+    let signature = transaction
+        .signature
+        .as_ref()
+        .ok_or_else(|| String::from("Transação sem assinatura"))?;
+    let public_key = parse_public_key(&signature.public_key)?;
+    if derive_address(&public_key) != transaction.from {
+        return Err(String::from("Chave pública não corresponde ao remetente"));
+    }
+    verify_signature(signature, &transaction.signing_hash())
+}
+
+// Assina a hash da transação, sem checar o remetente (assinaturas parciais do multisig)
+pub fn sign_hash(key: &SigningKey, hash: &[u8; 32]) -> TransactionSignature {
+    TransactionSignature {
+        public_key: encode_hex(key.verifying_key().as_bytes()),
+        signature: encode_hex(&key.sign(hash).to_bytes()),
+    }
+}
+
+// Checa uma assinatura da hash com a chave pública informada na assinatura
+pub fn verify_signature(signature: &TransactionSignature, hash: &[u8; 32]) -> Result<(), String> {
+    let public_key = parse_public_key(&signature.public_key)?;
+    let bytes: [u8; 64] = decode_hex(&signature.signature)?
+        .try_into()
+        .map_err(|_| String::from("Assinatura inválida"))?;
+    public_key
+        .verify(hash, &Signature::from_bytes(&bytes))
+        .map_err(|_| String::from("Assinatura inválida"))
+}
+
+// Chave pública ed25519 em hexadecimal
+pub fn parse_public_key(public_key: &str) -> Result<VerifyingKey, String> {
+    decode_hex(public_key)?
+        .try_into()
+        .ok()
+        .and_then(|bytes: [u8; 32]| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| String::from("Chave pública inválida"))
+}
+
+// Parâmetros do scrypt, custo N = 2^log_n
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+// Limites dos parâmetros do scrypt aceitos: memória 128 * r * N e paralelismo
+const KDF_MAX_MEMORY: u128 = 256 * 1024 * 1024;
+const KDF_MAX_P: u32 = 16;
+
+impl KdfParams {
+    // Recusa parâmetros que exigiriam memória ou tempo excessivos na derivação
+    fn check(&self) -> Result<(), String> {
+        if self.log_n >= 32
+            || self.p > KDF_MAX_P
+            || (128 * u128::from(self.r)) << self.log_n > KDF_MAX_MEMORY
+        {
+            return Err(String::from("Parâmetros do scrypt inválidos"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+// Arquivo de uma conta no keystore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyFile {
+    address: String,
+    public_key: String,
+    kdf: String,
+    kdf_params: KdfParams,
+    salt: String,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keystore {
+    dir: PathBuf,
+    params: KdfParams,
+}
+
+impl Keystore {
+    pub fn open(dir: &Path) -> Self {
+        Keystore::with_params(dir, KdfParams::default())
+    }
+
+    pub fn with_params(dir: &Path, params: KdfParams) -> Self {
+        Keystore {
+            dir: dir.to_path_buf(),
+            params,
+        }
+    }
+
+    // Gera uma nova conta, retorna o endereço
+    pub fn create(&self, password: &str) -> Result<String, String> {
+        self.import(&generate_keypair(), password)
+    }
+
+    // Grava a chave cifrada com a senha, retorna o endereço
+    pub fn import(&self, key: &SigningKey, password: &str) -> Result<String, String> {
+        let address = derive_address(&key.verifying_key());
+        let mut salt = [0u8; 32];
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let cipher = self.cipher(password, &salt, &self.params)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), key.to_bytes().as_slice())
+            .map_err(|_| String::from("Erro ao cifrar a chave"))?;
+        let file = KeyFile {
+            address: address.clone(),
+            public_key: encode_hex(key.verifying_key().as_bytes()),
+            kdf: String::from("scrypt"),
+            kdf_params: self.params,
+            salt: encode_hex(&salt),
+            cipher: String::from("aes-256-gcm"),
+            nonce: encode_hex(&nonce),
+            ciphertext: encode_hex(&ciphertext),
+        };
+
+        fs::create_dir_all(&self.dir).map_err(|erro| erro.to_string())?;
+        let data = serde_json::to_vec_pretty(&file).map_err(|erro| erro.to_string())?;
+        fs::write(self.key_path(&address)?, data).map_err(|erro| erro.to_string())?;
+        Ok(address)
+    }
+
+    // Endereços das contas do keystore, em ordem
+    pub fn list(&self) -> Result<Vec<String>, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(vec![]),
+        };
+        let mut addresses = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        addresses.sort();
+        Ok(addresses)
+    }
+
+    // Chave pública da conta, disponível sem a senha
+    pub fn public_key(&self, address: &str) -> Result<String, String> {
+        Ok(self.read_key_file(address)?.public_key)
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.key_path(address).is_ok_and(|path| path.exists())
+    }
+
+    // Decifra a chave privada da conta com a senha
+    pub fn unlock(&self, address: &str, password: &str) -> Result<SigningKey, String> {
+        let file = self.read_key_file(address)?;
+        if file.kdf != "scrypt" || file.cipher != "aes-256-gcm" {
+            return Err(String::from("Formato do keystore não suportado"));
+        }
+
+        let cipher = self.cipher(password, &decode_hex(&file.salt)?, &file.kdf_params)?;
+        let nonce = decode_hex(&file.nonce)?;
+        if nonce.len() != 12 {
+            return Err(String::from("Arquivo do keystore inválido"));
+        }
+        let seed: [u8; 32] = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode_hex(&file.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| String::from("Senha incorreta"))?
+            .try_into()
+            .map_err(|_| String::from("Arquivo do keystore inválido"))?;
+        let key = keypair_from_seed(&seed);
+        if derive_address(&key.verifying_key()) != file.address {
+            return Err(String::from("Arquivo do keystore inválido"));
+        }
+        Ok(key)
+    }
+
+    // Assina a transação com a chave da conta `from`
+    pub fn sign_transaction(
+        &self,
+        transaction: &mut Transaction,
+        password: &str,
+    ) -> Result<(), String> {
+        let key = self.unlock(&transaction.from, password)?;
+        sign_transaction(&key, transaction)
+    }
+
+    fn read_key_file(&self, address: &str) -> Result<KeyFile, String> {
+        let data = fs::read(self.key_path(address)?)
+            .map_err(|_| format!("Conta {} não encontrada no keystore", address))?;
+        serde_json::from_slice::<KeyFile>(&data)
+            .map_err(|erro| format!("Arquivo do keystore inválido: {}", erro))
+    }
+
+    // Caminho do arquivo da conta, apenas para endereços no formato de `derive_address`
+    fn key_path(&self, address: &str) -> Result<PathBuf, String> {
+        let hex = address.strip_prefix("0x").unwrap_or_default();
+        if hex.len() != 40
+            || !hex
+                .bytes()
+                .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return Err(format!("Endereço inválido: {}", address));
+        }
+        Ok(self.dir.join(format!("{}.json", address)))
+    }
+
+    fn cipher(&self, password: &str, salt: &[u8], params: &KdfParams) -> Result<Aes256Gcm, String> {
+        params.check()?;
+        let params = scrypt::Params::new(params.log_n, params.r, params.p, 32)
+            .map_err(|_| String::from("Parâmetros do scrypt inválidos"))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+            .map_err(|_| String::from("Erro ao derivar a chave"))?;
+        Aes256Gcm::new_from_slice(&key).map_err(|_| String::from("Erro ao derivar a chave"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    // Parâmetros baixos do scrypt, apenas para os testes
+    pub(crate) const TEST_PARAMS: KdfParams = KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    // Conta de teste com chave derivada de uma semente fixa, e o seu endereço
+    pub(crate) fn test_account(seed: u8) -> (SigningKey, String) {
+        let key = keypair_from_seed(&[seed; 32]);
+        let address = derive_address(&key.verifying_key());
+        (key, address)
+    }
+
+    #[test]
+    fn test_address_from_fixed_seed() {
+        let key = keypair_from_seed(&[7u8; 32]);
+        assert_eq!(
+            derive_address(&key.verifying_key()),
+            "0xfe812c12f3ab4ce6ac5db69ac352f906cb1b11ef"
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = keypair_from_seed(&[1u8; 32]);
+        let from = derive_address(&key.verifying_key());
+        let mut transaction =
+            Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 4.0);
+
+        assert_eq!(
+            verify_transaction(&transaction),
+            Err(String::from("Transação sem assinatura"))
+        );
+        sign_transaction(&key, &mut transaction).unwrap();
+        assert_eq!(verify_transaction(&transaction), Ok(()));
+
+        // Assinatura de ed25519 é determinística
+        let mut again = Transaction::new(&from, "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962", 4.0);
+        sign_transaction(&key, &mut again).unwrap();
+        assert_eq!(again.signature, transaction.signature);
+
+        // Qualquer alteração invalida a assinatura
+        let mut tampered = transaction.clone();
+        tampered.value = 40.0;
+        assert_eq!(
+            verify_transaction(&tampered),
+            Err(String::from("Assinatura inválida"))
+        );
+
+        // Chave de outro endereço
+        let other = keypair_from_seed(&[2u8; 32]);
+        assert!(sign_transaction(&other, &mut transaction.clone()).is_err());
+        let mut stolen = Transaction::new(&from, "0x0", 1.0);
+        stolen.signature = Some(TransactionSignature {
+            public_key: encode_hex(other.verifying_key().as_bytes()),
+            signature: encode_hex(&other.sign(&stolen.signing_hash()).to_bytes()),
+        });
+        assert_eq!(
+            verify_transaction(&stolen),
+            Err(String::from("Chave pública não corresponde ao remetente"))
+        );
+    }
+
+    #[test]
+    fn test_keystore() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = Keystore::with_params(dir.path(), TEST_PARAMS);
+        let key = keypair_from_seed(&[3u8; 32]);
+
+        let address = keystore.import(&key, "senha").unwrap();
+        let created = keystore.create("outra senha").unwrap();
+        let mut expected = vec![address.clone(), created];
+        expected.sort();
+        assert_eq!(keystore.list().unwrap(), expected);
+
+        // A chave privada não é gravada em texto claro
+        let file = fs::read_to_string(dir.path().join(format!("{}.json", address))).unwrap();
+        assert!(!file.contains(&encode_hex(&key.to_bytes())));
+
+        assert_eq!(
+            keystore.unlock(&address, "senha").unwrap().to_bytes(),
+            key.to_bytes()
+        );
+        assert_eq!(
+            keystore.unlock(&address, "errada").err(),
+            Some(String::from("Senha incorreta"))
+        );
+
+        let mut transaction = Transaction::new(&address, "0x0", 1.0);
+        keystore
+            .sign_transaction(&mut transaction, "senha")
+            .unwrap();
+        assert_eq!(verify_transaction(&transaction), Ok(()));
+
+        // Endereços fora do formato não viram caminhos de arquivo
+        for invalid in ["../x", "0x../../etc/passwd", &address.to_uppercase()] {
+            assert!(!keystore.contains(invalid));
+            assert_eq!(
+                keystore.unlock(invalid, "senha").err(),
+                Some(format!("Endereço inválido: {}", invalid))
+            );
+        }
+
+        // Parâmetros do scrypt adulterados no arquivo são recusados antes da derivação
+        let path = dir.path().join(format!("{}.json", address));
+        for (log_n, r, p) in [(40, 8, 1), (22, 8, 1), (4, 8, 1000)] {
+            let mut file = serde_json::from_str::<Value>(&file).unwrap();
+            file["kdf_params"] = json!({ "log_n": log_n, "r": r, "p": p });
+            fs::write(&path, file.to_string()).unwrap();
+            assert_eq!(
+                keystore.unlock(&address, "senha").err(),
+                Some(String::from("Parâmetros do scrypt inválidos"))
+            );
+        }
+    }
+}
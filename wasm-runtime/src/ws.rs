@@ -0,0 +1,464 @@
+/* Mod Ws
+** - Servidor WebSocket de assinaturas, escuta apenas em localhost
+** - Pedidos no formato JSON-RPC 2.0:
+**    - subscribe ["newBlocks"]: cada bloco adicionado a cadeia
+**    - subscribe ["pendingTransactions"]: cada transação aceita nas pendentes
+**    - subscribe ["address", endereço]: transações pendentes e incluídas em blocos
+**      com o endereço como origem ou destino
+**    - unsubscribe [id da assinatura]
+** - As notificações são enviadas com o método `subscription` e os parâmetros
+**   `{ "subscription": id, "result": ... }`
+** - Os eventos vêm de `Blockchain::subscribe`, enviados por `mine_block`,
+**   `import_block` e pelas transações aceitas
+** - Cada conexão processa em ordem, em uma fila limitada, os bytes recebidos do
+**   cliente (lidos por uma thread com leitura bloqueante) e os eventos da
+**   blockchain. Sem entradas por PING_INTERVAL um ping é enviado ao cliente.
+**   Um cliente lento que deixa a fila de eventos encher é desconectado
+*/
+use crate::{
+    blockchain::{Blockchain, ChainEvent},
+    debug, info,
+    rpc::{error_response, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR},
+    transaction::Transaction,
+    warn,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tungstenite::{Message, WebSocket};
+
+// Tempo máximo do handshake WebSocket
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Intervalo sem entradas até o envio de um ping ao cliente
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+// Tempo máximo de um envio ao cliente
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Entradas guardadas na fila de cada conexão
+const INPUT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct WsConfig {
+    // Endereço de escuta, precisa ser um endereço de loopback
+    pub listen_addr: SocketAddr,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        WsConfig {
+            listen_addr: "127.0.0.1:8546".parse().unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Topic {
+    NewBlocks,
+    PendingTransactions,
+    Address(String),
+}
+
+pub struct WsServer {
+    local_addr: SocketAddr,
+}
+
+impl WsServer {
+    // Inicia o servidor: aceita conexões em uma thread, uma thread por conexão
+    pub fn start(config: WsConfig, blockchain: Arc<Mutex<Blockchain>>) -> Result<WsServer, String> {
+        if !config.listen_addr.ip().is_loopback() {
+            return Err(String::from(
+                "O servidor WebSocket só pode escutar em localhost",
+            ));
+        }
+        let listener = TcpListener::bind(config.listen_addr).map_err(|erro| erro.to_string())?;
+        let local_addr = listener.local_addr().map_err(|erro| erro.to_string())?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let blockchain = blockchain.clone();
+                thread::spawn(move || {
+                    if let Err(erro) = handle_connection(stream, &blockchain) {
+                        warn!("Erro na conexão WebSocket"; erro = erro);
+                    }
+                });
+            }
+        });
+
+        info!("Servidor WebSocket iniciado"; addr = local_addr);
+        Ok(WsServer { local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+// Assinaturas de uma conexão
+struct Connection {
+    socket: WebSocket<ConnectionStream>,
+    subscriptions: HashMap<u64, Topic>,
+    next_id: u64,
+}
+
+// Entradas da conexão, processadas em ordem pela thread da conexão
+enum Input {
+    // Bytes recebidos do cliente
+    Data(Vec<u8>),
+    Event(Box<ChainEvent>),
+    // Conexão encerrada pelo cliente ou assinante removido pela blockchain
+    Closed(&'static str),
+}
+
+/* Stream do WebSocket: as escritas vão para o socket e as leituras retornam os
+** bytes recebidos pela thread de leitura, ou WouldBlock quando não há bytes.
+** No handshake (`direct`) as leituras são feitas no socket
+*/
+struct ConnectionStream {
+    stream: TcpStream,
+    received: VecDeque<u8>,
+    direct: bool,
+}
+
+impl Read for ConnectionStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.direct {
+            return self.stream.read(buf);
+        }
+        if self.received.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.received.read(buf)
+    }
+}
+
+impl Write for ConnectionStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn handle_connection(stream: TcpStream, blockchain: &Mutex<Blockchain>) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+        .map_err(|erro| erro.to_string())?;
+    let reader = stream.try_clone().map_err(|erro| erro.to_string())?;
+    let mut socket = tungstenite::accept(ConnectionStream {
+        stream,
+        received: VecDeque::new(),
+        direct: true,
+    })
+    .map_err(|erro| erro.to_string())?;
+    socket.get_mut().direct = false;
+    reader
+        .set_read_timeout(None)
+        .map_err(|erro| erro.to_string())?;
+
+    let (inputs, receiver) = mpsc::sync_channel(INPUT_CAPACITY);
+    let events = blockchain.lock().unwrap().subscribe();
+    spawn_reader(reader, inputs.clone());
+    spawn_forwarder(events, inputs);
+    let mut connection = Connection {
+        socket,
+        subscriptions: HashMap::new(),
+        next_id: 1,
+    };
+    debug!("Cliente WebSocket conectado");
+
+    let result = connection.run(&receiver);
+    let _ = connection.socket.get_ref().stream.shutdown(Shutdown::Both);
+    debug!("Cliente WebSocket desconectado");
+    result
+}
+
+// Thread de leitura bloqueante dos bytes enviados pelo cliente
+fn spawn_reader(mut stream: TcpStream, inputs: SyncSender<Input>) {
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            let input = match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => Input::Closed("Conexão encerrada pelo cliente"),
+                Ok(read) => Input::Data(buffer[..read].to_vec()),
+            };
+            let closed = matches!(input, Input::Closed(_));
+            if inputs.send(input).is_err() || closed {
+                break;
+            }
+        }
+    });
+}
+
+/* Thread que repassa os eventos da blockchain para a fila da conexão. Quando a
+** blockchain remove o assinante (fila cheia) a conexão é encerrada
+*/
+fn spawn_forwarder(events: Receiver<ChainEvent>, inputs: SyncSender<Input>) {
+    thread::spawn(move || {
+        for event in events {
+            if inputs.send(Input::Event(Box::new(event))).is_err() {
+                return;
+            }
+        }
+        let _ = inputs.send(Input::Closed("Assinante lento removido"));
+    });
+}
+
+impl Connection {
+    // Processa as entradas até o encerramento da conexão
+    fn run(&mut self, inputs: &Receiver<Input>) -> Result<(), String> {
+        // Pedidos enviados junto com o handshake
+        if !self.read_requests()? {
+            return Ok(());
+        }
+        loop {
+            match inputs.recv_timeout(PING_INTERVAL) {
+                Ok(Input::Data(data)) => {
+                    self.socket.get_mut().received.extend(data);
+                    if !self.read_requests()? {
+                        return Ok(());
+                    }
+                }
+                Ok(Input::Event(event)) => {
+                    for notification in self.notifications(&event) {
+                        self.send(notification)?;
+                    }
+                }
+                Ok(Input::Closed(reason)) => {
+                    debug!("Conexão WebSocket encerrada"; motivo = reason);
+                    return Ok(());
+                }
+                Err(RecvTimeoutError::Timeout) => self
+                    .socket
+                    .send(Message::Ping(vec![]))
+                    .map_err(|erro| erro.to_string())?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+
+    // Responde os pedidos completos recebidos, retorna false no fechamento
+    fn read_requests(&mut self) -> Result<bool, String> {
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => {
+                    let response = self.handle_request(&text);
+                    self.send(response)?;
+                }
+                Ok(Message::Close(_)) => return Ok(false),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(erro)) if erro.kind() == ErrorKind::WouldBlock => {
+                    return Ok(true)
+                }
+                Err(tungstenite::Error::ConnectionClosed) => return Ok(false),
+                Err(erro) => return Err(erro.to_string()),
+            }
+        }
+    }
+
+    fn send(&mut self, message: Value) -> Result<(), String> {
+        self.socket
+            .send(Message::Text(message.to_string()))
+            .map_err(|erro| erro.to_string())
+    }
+
+    fn handle_request(&mut self, text: &str) -> Value {
+        let request = match serde_json::from_str::<Value>(text) {
+            Ok(request) => request,
+            Err(_) => return error_response(Value::Null, PARSE_ERROR, "JSON inválido"),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        if request["jsonrpc"] != "2.0" {
+            return error_response(id, INVALID_REQUEST, "Pedido inválido");
+        }
+        let params = &request["params"];
+
+        match request["method"].as_str() {
+            Some("subscribe") => {
+                let topic = match (params[0].as_str(), params[1].as_str()) {
+                    (Some("newBlocks"), _) => Topic::NewBlocks,
+                    (Some("pendingTransactions"), _) => Topic::PendingTransactions,
+                    (Some("address"), Some(address)) => Topic::Address(address.to_string()),
+                    _ => return error_response(id, INVALID_PARAMS, "Assinatura inválida"),
+                };
+                let subscription = self.next_id;
+                self.next_id += 1;
+                self.subscriptions.insert(subscription, topic);
+                json!({ "jsonrpc": "2.0", "result": subscription, "id": id })
+            }
+            Some("unsubscribe") => match params[0].as_u64() {
+                Some(subscription) => {
+                    let removed = self.subscriptions.remove(&subscription).is_some();
+                    json!({ "jsonrpc": "2.0", "result": removed, "id": id })
+                }
+                None => error_response(id, INVALID_PARAMS, "Parâmetro 0 inválido"),
+            },
+            _ => error_response(id, METHOD_NOT_FOUND, "Método não encontrado"),
+        }
+    }
+
+    // Notificações do evento para as assinaturas da conexão
+    fn notifications(&self, event: &ChainEvent) -> Vec<Value> {
+        let mut subscriptions = self.subscriptions.iter().collect::<Vec<_>>();
+        subscriptions.sort_by_key(|(id, _)| **id);
+
+        let mut notifications = vec![];
+        for (subscription, topic) in subscriptions {
+            let results = match (topic, event) {
+                (Topic::NewBlocks, ChainEvent::NewBlock(block)) => vec![json!(block)],
+                (
+                    Topic::PendingTransactions,
+                    ChainEvent::PendingTransaction { id, transaction },
+                ) => {
+                    vec![json!({ "id": id, "transaction": transaction })]
+                }
+                (Topic::Address(address), ChainEvent::PendingTransaction { id, transaction })
+                    if involves(transaction, address) =>
+                {
+                    vec![json!({ "status": "pending", "id": id, "transaction": transaction })]
+                }
+                (Topic::Address(address), ChainEvent::NewBlock(block)) => {
+                    let mut transactions = block
+                        .transactions
+                        .iter()
+                        .filter(|(_, transaction)| involves(transaction, address))
+                        .collect::<Vec<_>>();
+                    transactions.sort_by_key(|(id, _)| **id);
+                    transactions
+                        .into_iter()
+                        .map(|(id, transaction)| {
+                            json!({
+                                "status": "included",
+                                "block_id": block.id,
+                                "id": id,
+                                "transaction": transaction,
+                            })
+                        })
+                        .collect()
+                }
+                _ => vec![],
+            };
+            notifications.extend(results.into_iter().map(|result| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "subscription",
+                    "params": { "subscription": subscription, "result": result },
+                })
+            }));
+        }
+        notifications
+    }
+}
+
+fn involves(transaction: &Transaction, address: &str) -> bool {
+    transaction.from == address || transaction.to == address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tungstenite::stream::MaybeTlsStream;
+
+    type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+    fn start_server() -> (Arc<Mutex<Blockchain>>, Client) {
+        let config = WsConfig {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+        };
+        let blockchain = Arc::new(Mutex::new(Blockchain::new()));
+        let server = WsServer::start(config, blockchain.clone()).unwrap();
+        let (client, _) = tungstenite::connect(format!("ws://{}", server.local_addr())).unwrap();
+        if let MaybeTlsStream::Plain(stream) = client.get_ref() {
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+        }
+        (blockchain, client)
+    }
+
+    fn request(client: &mut Client, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        client.send(Message::Text(request.to_string())).unwrap();
+        receive(client)
+    }
+
+    fn receive(client: &mut Client) -> Value {
+        match client.read().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("Mensagem inesperada: {:?}", message),
+        }
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let (blockchain, mut client) = start_server();
+        let blocks = request(&mut client, "subscribe", json!(["newBlocks"]))["result"].clone();
+        let pending =
+            request(&mut client, "subscribe", json!(["pendingTransactions"]))["result"].clone();
+        let address = request(
+            &mut client,
+            "subscribe",
+            json!(["address", "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962"]),
+        )["result"]
+            .clone();
+        assert_ne!(blocks, pending);
+
+        blockchain.lock().unwrap().create_transaction(
+            "0xEf8801eaf234ff82801821FFe2d780237F9967",
+            "0x889b8abc7aA5D9Ad5f7f531d68453f9984Fd6962",
+            4.0,
+        );
+        let notification = receive(&mut client);
+        assert_eq!(notification["method"], "subscription");
+        assert_eq!(notification["params"]["subscription"], pending);
+        assert_eq!(notification["params"]["result"]["id"], 1);
+        let notification = receive(&mut client);
+        assert_eq!(notification["params"]["subscription"], address);
+        assert_eq!(notification["params"]["result"]["status"], "pending");
+
+        blockchain.lock().unwrap().mine_block();
+        let notification = receive(&mut client);
+        assert_eq!(notification["params"]["subscription"], blocks);
+        assert_eq!(notification["params"]["result"]["id"], 1);
+        let notification = receive(&mut client);
+        assert_eq!(notification["params"]["subscription"], address);
+        assert_eq!(notification["params"]["result"]["status"], "included");
+        assert_eq!(notification["params"]["result"]["block_id"], 1);
+
+        // Sem assinaturas, o próximo bloco não é notificado
+        for subscription in [&blocks, &pending, &address] {
+            let response = request(&mut client, "unsubscribe", json!([subscription]));
+            assert_eq!(response["result"], true);
+        }
+        blockchain.lock().unwrap().mine_block();
+        let response = request(&mut client, "unsubscribe", json!([blocks]));
+        assert_eq!(response["result"], false);
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let (_, mut client) = start_server();
+        let response = request(&mut client, "subscribe", json!(["blocos"]));
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = request(&mut client, "publish", json!([]));
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        client
+            .send(Message::Text(String::from("{ invalido")))
+            .unwrap();
+        assert_eq!(receive(&mut client)["error"]["code"], PARSE_ERROR);
+    }
+}